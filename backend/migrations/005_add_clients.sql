@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS clients (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    inbound_id TEXT NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    uuid TEXT NOT NULL,
+    flow TEXT NOT NULL DEFAULT '',
+    enable BOOLEAN NOT NULL DEFAULT 1,
+    up BIGINT NOT NULL DEFAULT 0,
+    down BIGINT NOT NULL DEFAULT 0,
+    total BIGINT NOT NULL DEFAULT 0,
+    expiry BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_clients_inbound_id ON clients(inbound_id);
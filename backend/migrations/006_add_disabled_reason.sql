@@ -0,0 +1,3 @@
+ALTER TABLE inbounds ADD COLUMN disabled_reason TEXT;
+
+ALTER TABLE clients ADD COLUMN disabled_reason TEXT;
@@ -0,0 +1,5 @@
+ALTER TABLE clients ADD COLUMN sub_id TEXT;
+
+UPDATE clients SET sub_id = lower(hex(randomblob(8))) WHERE sub_id IS NULL OR sub_id = '';
+
+CREATE INDEX IF NOT EXISTS idx_clients_sub_id ON clients(sub_id);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS config_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    config TEXT NOT NULL,
+    username TEXT,
+    reason TEXT NOT NULL DEFAULT '',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
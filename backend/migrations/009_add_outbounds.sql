@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS outbounds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tag TEXT UNIQUE NOT NULL,
+    remark TEXT NOT NULL DEFAULT '',
+    protocol TEXT NOT NULL,
+    enable BOOLEAN NOT NULL DEFAULT 1,
+    settings TEXT,
+    stream_settings TEXT,
+    proxy_tag TEXT,
+    mux TEXT,
+    send_through TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS routing_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    priority INTEGER NOT NULL DEFAULT 0,
+    remark TEXT NOT NULL DEFAULT '',
+    enable BOOLEAN NOT NULL DEFAULT 1,
+    domain TEXT,
+    ip TEXT,
+    port TEXT,
+    source_port TEXT,
+    source TEXT,
+    user TEXT,
+    inbound_tag TEXT,
+    network TEXT,
+    protocol TEXT,
+    attrs TEXT,
+    outbound_tag TEXT,
+    balancer_tag TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_routing_rules_priority ON routing_rules(priority);
+
+CREATE TABLE IF NOT EXISTS xray_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
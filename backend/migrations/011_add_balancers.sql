@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS balancers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tag TEXT UNIQUE NOT NULL,
+    remark TEXT NOT NULL DEFAULT '',
+    selector TEXT NOT NULL,
+    strategy TEXT NOT NULL DEFAULT 'random',
+    strategy_settings TEXT,
+    fallback_tag TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1 @@
+ALTER TABLE clients ADD COLUMN level INTEGER NOT NULL DEFAULT 0;
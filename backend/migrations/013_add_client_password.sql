@@ -0,0 +1 @@
+ALTER TABLE clients ADD COLUMN password TEXT NOT NULL DEFAULT '';
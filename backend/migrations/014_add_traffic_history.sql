@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS traffic_history (
+    subject TEXT NOT NULL,
+    subject_id TEXT NOT NULL,
+    period TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    up INTEGER NOT NULL DEFAULT 0,
+    down INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (subject, subject_id, period, bucket)
+);
+
+CREATE INDEX IF NOT EXISTS idx_traffic_history_bucket ON traffic_history (period, bucket);
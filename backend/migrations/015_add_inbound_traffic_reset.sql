@@ -0,0 +1,5 @@
+ALTER TABLE inbounds ADD COLUMN traffic_reset TEXT NOT NULL DEFAULT 'never';
+
+ALTER TABLE inbounds ADD COLUMN traffic_reset_day INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE inbounds ADD COLUMN last_traffic_reset INTEGER NOT NULL DEFAULT 0;
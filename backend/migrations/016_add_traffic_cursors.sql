@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS traffic_cursors (
+    name TEXT PRIMARY KEY,
+    value INTEGER NOT NULL,
+    xray_started INTEGER
+);
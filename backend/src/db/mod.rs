@@ -36,13 +36,7 @@ pub async fn init_pool() -> anyhow::Result<SqlitePool> {
 pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
     tracing::info!("Running database migrations...");
 
-    let init_sql = include_str!("../../migrations/001_init.sql");
-    for statement in init_sql.split(';') {
-        let s = statement.trim();
-        if !s.is_empty() {
-            let _ = sqlx::query(s).execute(pool).await;
-        }
-    }
+    run_script(pool, include_str!("../../migrations/001_init.sql")).await;
 
     let columns = ["tag", "listen", "allocate"];
     for col in columns {
@@ -64,7 +58,77 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
         .execute(pool)
         .await;
 
+    run_script(pool, include_str!("../../migrations/005_add_clients.sql")).await;
+    run_script(
+        pool,
+        include_str!("../../migrations/006_add_disabled_reason.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/007_add_client_sub_id.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/008_add_config_revisions.sql"),
+    )
+    .await;
+    run_script(pool, include_str!("../../migrations/009_add_outbounds.sql")).await;
+    run_script(
+        pool,
+        include_str!("../../migrations/010_add_routing_rules.sql"),
+    )
+    .await;
+    run_script(pool, include_str!("../../migrations/011_add_balancers.sql")).await;
+    run_script(
+        pool,
+        include_str!("../../migrations/012_add_client_level.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/013_add_client_password.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/014_add_traffic_history.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/015_add_inbound_traffic_reset.sql"),
+    )
+    .await;
+    run_script(
+        pool,
+        include_str!("../../migrations/016_add_traffic_cursors.sql"),
+    )
+    .await;
+
     tracing::info!("Migrations completed successfully");
 
     Ok(())
 }
+
+/// A fresh in-memory database with every migration applied.
+#[cfg(test)]
+pub async fn test_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    run_migrations(&pool).await.unwrap();
+    pool
+}
+
+async fn run_script(pool: &SqlitePool, sql: &str) {
+    for statement in sql.split(';') {
+        let s = statement.trim();
+        if !s.is_empty() {
+            let _ = sqlx::query(s).execute(pool).await;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::balancer::{
+    Balancer, CreateBalancerRequest, DeleteBalancerRequest, ObservatorySettings, OutboundProbe,
+    UpdateBalancerRequest,
+};
+use crate::models::config_revision::ConfigTrigger;
+use crate::services::{balancer_service, system_service::SharedMonitor, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+
+use sqlx::SqlitePool;
+
+pub async fn list_balancers(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Vec<Balancer>>> {
+    let list = balancer_service::get_all_balancers(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(list))
+}
+
+pub async fn add_balancer(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<CreateBalancerRequest>,
+) -> ApiResult<ApiResponse<Balancer>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let balancer = balancer_service::add_balancer(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Add balancer {}", balancer.tag));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        balancer,
+        "Added successfully",
+    ))
+}
+
+pub async fn update_balancer(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<UpdateBalancerRequest>,
+) -> ApiResult<ApiResponse<Balancer>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let balancer = balancer_service::update_balancer(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Update balancer {}", balancer.tag));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        balancer,
+        "Updated successfully",
+    ))
+}
+
+pub async fn del_balancer(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<DeleteBalancerRequest>,
+) -> ApiResult<ApiResponse<()>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    balancer_service::delete_balancer(&mut tx, payload.id).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Delete balancer #{}", payload.id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_no_data("Deleted successfully"))
+}
+
+pub async fn get_observatory(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<ObservatorySettings>> {
+    let settings = balancer_service::get_observatory_settings(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(settings))
+}
+
+pub async fn update_observatory(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<ObservatorySettings>,
+) -> ApiResult<ApiResponse<ObservatorySettings>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let settings = balancer_service::update_observatory_settings(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Update observatory settings");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        settings,
+        "Updated successfully",
+    ))
+}
+
+pub async fn observatory_status(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Vec<OutboundProbe>>> {
+    let probes = balancer_service::get_observatory_status(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(probes))
+}
@@ -0,0 +1,110 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::client::{
+    Client, CreateClientRequest, ShareLinkResponse, ShareQuery, UpdateClientRequest,
+};
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::traffic_history::{HistoryQuery, HistorySubject, TrafficSeries};
+use crate::services::{
+    client_service, system_service::SharedMonitor, traffic_history_service, xray_service,
+};
+use crate::utils::qr::{self, QrFormat};
+use crate::utils::{response::ApiResponse, share_link};
+use axum::extract::{Extension, Json, Path, Query, State};
+use axum::http::HeaderMap;
+
+use sqlx::SqlitePool;
+
+pub async fn list_clients(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path(inbound_id): Path<String>,
+) -> ApiResult<ApiResponse<Vec<Client>>> {
+    let list = client_service::get_clients(&mut *pool.acquire().await?, &inbound_id).await?;
+    Ok(ApiResponse::success(list))
+}
+
+pub async fn add_client(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Path(inbound_id): Path<String>,
+    Json(payload): Json<CreateClientRequest>,
+) -> ApiResult<ApiResponse<Client>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let client = client_service::add_client(&mut tx, &inbound_id, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Add client {}", client.email));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(client, "Added successfully"))
+}
+
+pub async fn update_client(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Path((inbound_id, client_id)): Path<(String, i64)>,
+    Json(payload): Json<UpdateClientRequest>,
+) -> ApiResult<ApiResponse<Client>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let client = client_service::update_client(&mut tx, &inbound_id, client_id, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Update client {}", client.email));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        client,
+        "Updated successfully",
+    ))
+}
+
+pub async fn delete_client(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Path((inbound_id, client_id)): Path<(String, i64)>,
+) -> ApiResult<ApiResponse<()>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    client_service::delete_client(&mut tx, &inbound_id, client_id).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Delete client #{}", client_id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_no_data("Deleted successfully"))
+}
+
+pub async fn share_client(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path((inbound_id, client_id)): Path<(String, i64)>,
+    Query(query): Query<ShareQuery>,
+    headers: HeaderMap,
+) -> ApiResult<ApiResponse<ShareLinkResponse>> {
+    let format = QrFormat::parse(query.format.as_deref())?;
+    let address = share_link::public_address(&headers);
+
+    let node = client_service::get_share_node(
+        &mut *pool.acquire().await?,
+        &inbound_id,
+        client_id,
+        &address,
+    )
+    .await?;
+    let link = node.to_link();
+    let qr_code = qr::render_data_uri(&link, format)?;
+
+    Ok(ApiResponse::success(ShareLinkResponse { link, qr_code }))
+}
+
+pub async fn traffic_history(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path((inbound_id, client_id)): Path<(String, i64)>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<ApiResponse<TrafficSeries>> {
+    let client =
+        client_service::get_client(&mut *pool.acquire().await?, &inbound_id, client_id).await?;
+    let series = traffic_history_service::get_series(
+        &pool,
+        HistorySubject::Client,
+        &client.id.to_string(),
+        &query,
+    )
+    .await?;
+    Ok(ApiResponse::success(series))
+}
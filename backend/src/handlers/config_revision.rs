@@ -0,0 +1,47 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::{
+    ConfigRevision, ConfigRevisionSummary, RevisionDiff, RevisionDiffQuery,
+};
+use crate::services::{config_revision_service, system_service::SharedMonitor};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Path, Query, State};
+
+use sqlx::SqlitePool;
+
+pub async fn list_revisions(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Vec<ConfigRevisionSummary>>> {
+    let list = config_revision_service::list_revisions(&pool).await?;
+    Ok(ApiResponse::success(list))
+}
+
+pub async fn get_revision(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path(id): Path<i64>,
+) -> ApiResult<ApiResponse<ConfigRevision>> {
+    let revision = config_revision_service::get_revision(&pool, id).await?;
+    Ok(ApiResponse::success(revision))
+}
+
+pub async fn diff_revision(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path(id): Path<i64>,
+    Query(query): Query<RevisionDiffQuery>,
+) -> ApiResult<ApiResponse<RevisionDiff>> {
+    let diff = config_revision_service::diff_revisions(&pool, id, query.base).await?;
+    Ok(ApiResponse::success(diff))
+}
+
+pub async fn rollback_revision(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Path(id): Path<i64>,
+) -> ApiResult<ApiResponse<()>> {
+    config_revision_service::rollback(&pool, monitor, id, &user.username).await?;
+    Ok(ApiResponse::success_no_data("Rolled back successfully"))
+}
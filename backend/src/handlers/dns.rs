@@ -0,0 +1,33 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::xray_config::DnsConfig;
+use crate::services::{dns_service, system_service::SharedMonitor, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+
+use sqlx::SqlitePool;
+
+pub async fn get_dns(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<DnsConfig>> {
+    let settings = dns_service::get_dns_settings(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(settings))
+}
+
+pub async fn update_dns(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<DnsConfig>,
+) -> ApiResult<ApiResponse<DnsConfig>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let settings = dns_service::update_dns_settings(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Update DNS settings");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        settings,
+        "Updated successfully",
+    ))
+}
@@ -1,11 +1,15 @@
 use crate::errors::ApiResult;
 use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
 use crate::models::inbound::{
     CreateInboundRequest, DeleteInboundRequest, ResetTrafficRequest, UpdateInboundRequest,
 };
-use crate::services::{inbound_service, system_service::SharedMonitor, xray_service};
+use crate::models::traffic_history::{HistoryQuery, HistorySubject, TrafficSeries};
+use crate::services::{
+    inbound_service, system_service::SharedMonitor, traffic_history_service, xray_service,
+};
 use crate::utils::{reality, response::ApiResponse};
-use axum::extract::{Extension, Json, State};
+use axum::extract::{Extension, Json, Path, Query, State};
 
 use sqlx::SqlitePool;
 
@@ -13,18 +17,22 @@ pub async fn list_inbounds(
     _user: AuthUser,
     Extension(pool): Extension<SqlitePool>,
 ) -> ApiResult<ApiResponse<Vec<crate::models::inbound::Inbound>>> {
-    let list = inbound_service::get_all_inbounds(&pool).await?;
+    let list = inbound_service::get_all_inbounds(&mut *pool.acquire().await?).await?;
     Ok(ApiResponse::success(list))
 }
 
 pub async fn add_inbound(
-    _user: AuthUser,
+    user: AuthUser,
     State(monitor): State<SharedMonitor>,
     Extension(pool): Extension<SqlitePool>,
     Json(payload): Json<CreateInboundRequest>,
 ) -> ApiResult<ApiResponse<crate::models::inbound::Inbound>> {
     let port = payload.port;
-    let inbound = inbound_service::add_inbound(&pool, payload).await?;
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let inbound = inbound_service::add_inbound(&mut tx, payload).await?;
+
+    let trigger = ConfigTrigger::user(&user.username, format!("Add inbound {}", inbound.remark));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
 
     tokio::task::spawn_blocking(move || {
         tracing::info!("Starting background firewall task for port {}", port);
@@ -32,19 +40,21 @@ pub async fn add_inbound(
         tracing::info!("Finished background firewall task for port {}", port);
     });
 
-    xray_service::apply_config(&pool, monitor).await?;
-
     Ok(ApiResponse::success_with_msg(inbound, "Added successfully"))
 }
 
 pub async fn update_inbound(
-    _user: AuthUser,
+    user: AuthUser,
     State(monitor): State<SharedMonitor>,
     Extension(pool): Extension<SqlitePool>,
     Json(payload): Json<UpdateInboundRequest>,
 ) -> ApiResult<ApiResponse<crate::models::inbound::Inbound>> {
     let port = payload.port;
-    let inbound = inbound_service::update_inbound(&pool, payload).await?;
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let inbound = inbound_service::update_inbound(&mut tx, payload).await?;
+
+    let trigger = ConfigTrigger::user(&user.username, format!("Update inbound {}", inbound.remark));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
 
     if let Some(p) = port {
         tokio::task::spawn_blocking(move || {
@@ -54,8 +64,6 @@ pub async fn update_inbound(
         });
     }
 
-    xray_service::apply_config(&pool, monitor).await?;
-
     Ok(ApiResponse::success_with_msg(
         inbound,
         "Updated successfully",
@@ -63,13 +71,15 @@ pub async fn update_inbound(
 }
 
 pub async fn del_inbound_post(
-    _user: AuthUser,
+    user: AuthUser,
     State(monitor): State<SharedMonitor>,
     Extension(pool): Extension<SqlitePool>,
     Json(payload): Json<DeleteInboundRequest>,
 ) -> ApiResult<ApiResponse<()>> {
-    inbound_service::delete_inbound(&pool, &payload.id).await?;
-    xray_service::apply_config(&pool, monitor).await?;
+    let mut tx = xray_service::begin_change(&pool).await?;
+    inbound_service::delete_inbound(&mut tx, &payload.id).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Delete inbound {}", payload.id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
     Ok(ApiResponse::success_no_data("Deleted successfully"))
 }
 pub async fn check_reality(
@@ -83,10 +93,33 @@ pub async fn check_reality(
 }
 
 pub async fn reset_traffic(
-    _user: AuthUser,
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
     Extension(pool): Extension<SqlitePool>,
     Json(payload): Json<ResetTrafficRequest>,
 ) -> ApiResult<ApiResponse<()>> {
-    inbound_service::reset_inbound_traffic(&pool, &payload.id).await?;
+    let mut tx = xray_service::begin_change(&pool).await?;
+    if inbound_service::reset_inbound_traffic(&mut tx, &payload.id).await? {
+        let trigger = ConfigTrigger::user(
+            &user.username,
+            format!("Reset traffic of inbound {}", payload.id),
+        );
+        xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    } else {
+        tx.commit().await?;
+    }
     Ok(ApiResponse::success_no_data("Traffic reset successfully"))
 }
+
+pub async fn traffic_history(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Path(id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<ApiResponse<TrafficSeries>> {
+    let inbound = inbound_service::get_inbound(&mut *pool.acquire().await?, &id).await?;
+    let series =
+        traffic_history_service::get_series(&pool, HistorySubject::Inbound, &inbound.id, &query)
+            .await?;
+    Ok(ApiResponse::success(series))
+}
@@ -0,0 +1,29 @@
+use crate::errors::ApiResult;
+use crate::services::{metrics_service, system_service::SharedMonitor};
+use axum::extract::{Extension, State};
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+
+use sqlx::SqlitePool;
+
+/// Prometheus scrape endpoint, authorized with `METRICS_TOKEN` instead of a panel login.
+pub async fn metrics(
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    metrics_service::authorize(token)?;
+
+    let body = metrics_service::render(&pool, monitor).await?;
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    ))
+}
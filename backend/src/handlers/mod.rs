@@ -1,4 +1,14 @@
 pub mod auth;
+pub mod balancer;
+pub mod client;
+pub mod config_revision;
+pub mod dns;
 pub mod inbound;
+pub mod metrics;
+pub mod outbound;
+pub mod policy;
+pub mod routing;
+pub mod subscription;
 pub mod system;
+pub mod template;
 pub mod xray;
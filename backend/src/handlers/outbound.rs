@@ -0,0 +1,64 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::outbound::{
+    CreateOutboundRequest, DeleteOutboundRequest, Outbound, UpdateOutboundRequest,
+};
+use crate::services::{outbound_service, system_service::SharedMonitor, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+
+use sqlx::SqlitePool;
+
+pub async fn list_outbounds(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Vec<Outbound>>> {
+    let list = outbound_service::get_all_outbounds(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(list))
+}
+
+pub async fn add_outbound(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<CreateOutboundRequest>,
+) -> ApiResult<ApiResponse<Outbound>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let outbound = outbound_service::add_outbound(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Add outbound {}", outbound.tag));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        outbound,
+        "Added successfully",
+    ))
+}
+
+pub async fn update_outbound(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<UpdateOutboundRequest>,
+) -> ApiResult<ApiResponse<Outbound>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let outbound = outbound_service::update_outbound(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Update outbound {}", outbound.tag));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        outbound,
+        "Updated successfully",
+    ))
+}
+
+pub async fn del_outbound(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<DeleteOutboundRequest>,
+) -> ApiResult<ApiResponse<()>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    outbound_service::delete_outbound(&mut tx, payload.id).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Delete outbound #{}", payload.id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_no_data("Deleted successfully"))
+}
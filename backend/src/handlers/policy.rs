@@ -0,0 +1,33 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::xray_config::PolicyConfig;
+use crate::services::{policy_service, system_service::SharedMonitor, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+
+use sqlx::SqlitePool;
+
+pub async fn get_policy(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<PolicyConfig>> {
+    let policy = policy_service::get_policy(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(policy))
+}
+
+pub async fn update_policy(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<PolicyConfig>,
+) -> ApiResult<ApiResponse<PolicyConfig>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let policy = policy_service::update_policy(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Update policy levels");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        policy,
+        "Updated successfully",
+    ))
+}
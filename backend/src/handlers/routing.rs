@@ -0,0 +1,102 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::routing_rule::{
+    CreateRouteRuleRequest, DeleteRouteRuleRequest, ReorderRouteRulesRequest, RouteRule,
+    RoutingSettings, UpdateRouteRuleRequest,
+};
+use crate::services::{routing_service, system_service::SharedMonitor, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+
+use sqlx::SqlitePool;
+
+pub async fn list_rules(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Vec<RouteRule>>> {
+    let list = routing_service::get_all_rules(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(list))
+}
+
+pub async fn add_rule(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<CreateRouteRuleRequest>,
+) -> ApiResult<ApiResponse<RouteRule>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let rule = routing_service::add_rule(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Add routing rule #{}", rule.id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(rule, "Added successfully"))
+}
+
+pub async fn update_rule(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<UpdateRouteRuleRequest>,
+) -> ApiResult<ApiResponse<RouteRule>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let rule = routing_service::update_rule(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, format!("Update routing rule #{}", rule.id));
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(rule, "Updated successfully"))
+}
+
+pub async fn del_rule(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<DeleteRouteRuleRequest>,
+) -> ApiResult<ApiResponse<()>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    routing_service::delete_rule(&mut tx, payload.id).await?;
+    let trigger = ConfigTrigger::user(
+        &user.username,
+        format!("Delete routing rule #{}", payload.id),
+    );
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_no_data("Deleted successfully"))
+}
+
+pub async fn reorder_rules(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<ReorderRouteRulesRequest>,
+) -> ApiResult<ApiResponse<Vec<RouteRule>>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let list = routing_service::reorder_rules(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Reorder routing rules");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        list,
+        "Reordered successfully",
+    ))
+}
+
+pub async fn get_settings(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<RoutingSettings>> {
+    let settings = routing_service::get_settings(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(settings))
+}
+
+pub async fn update_settings(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<RoutingSettings>,
+) -> ApiResult<ApiResponse<RoutingSettings>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    let settings = routing_service::update_settings(&mut tx, payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Update routing settings");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        settings,
+        "Updated successfully",
+    ))
+}
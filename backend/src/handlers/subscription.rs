@@ -0,0 +1,52 @@
+use crate::errors::ApiResult;
+use crate::services::subscription_service::{self, SubscriptionFormat};
+use crate::utils::share_link;
+use axum::extract::{Extension, Path, Query};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionQuery {
+    pub format: Option<String>,
+}
+
+/// Serves the share links of a subscription. The unguessable subscription ID is the only
+/// credential, so this route sits outside the JWT-protected routers.
+pub async fn get_subscription(
+    Extension(pool): Extension<SqlitePool>,
+    Path(sub_id): Path<String>,
+    Query(query): Query<SubscriptionQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let address = share_link::public_address(&headers);
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|h| h.to_str().ok());
+    let format = SubscriptionFormat::detect(query.format.as_deref(), user_agent);
+
+    let Some(subscription) =
+        subscription_service::get_subscription(&pool, &sub_id, &address).await?
+    else {
+        return Ok((StatusCode::NOT_FOUND, "Subscription not found").into_response());
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::HeaderName::from_static("subscription-userinfo"),
+                subscription.userinfo_header(),
+            ),
+            (
+                header::HeaderName::from_static("profile-update-interval"),
+                "12".to_string(),
+            ),
+        ],
+        subscription.render(format)?,
+    )
+        .into_response())
+}
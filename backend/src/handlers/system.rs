@@ -1,4 +1,5 @@
 use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
 use axum::extract::{Json, State};
 
 use crate::{
@@ -59,9 +60,10 @@ pub async fn update_xray(
 pub async fn apply_config(
     State(monitor): State<SharedMonitor>,
     axum::Extension(pool): axum::Extension<sqlx::SqlitePool>,
-    _user: AuthUser,
+    user: AuthUser,
 ) -> ApiResult<ApiResponse<()>> {
-    crate::services::xray_service::apply_config(&pool, monitor).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Apply config");
+    crate::services::xray_service::apply_config(&pool, monitor, trigger).await?;
     Ok(ApiResponse::success_no_data(
         "Xray config applied and service restarted",
     ))
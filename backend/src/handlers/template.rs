@@ -0,0 +1,45 @@
+use crate::errors::ApiResult;
+use crate::middleware::auth::AuthUser;
+use crate::models::config_revision::ConfigTrigger;
+use crate::services::{system_service::SharedMonitor, template_service, xray_service};
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Json, State};
+use serde_json::Value;
+
+use sqlx::SqlitePool;
+
+pub async fn get_template(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+) -> ApiResult<ApiResponse<Value>> {
+    let template = template_service::get_template(&mut *pool.acquire().await?).await?;
+    Ok(ApiResponse::success(template))
+}
+
+/// Keeps the template only if the config merged from it passes `xray run -test`.
+pub async fn update_template(
+    user: AuthUser,
+    State(monitor): State<SharedMonitor>,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<Value>,
+) -> ApiResult<ApiResponse<Value>> {
+    let mut tx = xray_service::begin_change(&pool).await?;
+    template_service::save_template(&mut tx, &payload).await?;
+    let trigger = ConfigTrigger::user(&user.username, "Update config template");
+    xray_service::commit_config(&pool, monitor, tx, trigger).await?;
+    Ok(ApiResponse::success_with_msg(
+        payload,
+        "Updated successfully",
+    ))
+}
+
+/// Returns the config the panel would install with the given template, without applying it.
+pub async fn preview_template(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Json(payload): Json<Value>,
+) -> ApiResult<ApiResponse<Value>> {
+    template_service::validate_template(&payload)?;
+    let config = xray_service::render_config(&mut *pool.acquire().await?, Some(&payload)).await?;
+    Ok(ApiResponse::success(config))
+}
@@ -1,5 +1,14 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::middleware::auth::AuthUser;
+use crate::models::xray_config::ConfigQuery;
+use crate::services::xray_service;
+use crate::utils::response::ApiResponse;
+use axum::extract::{Extension, Query};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
 use axum::{http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use std::process::Command;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -9,8 +18,7 @@ pub struct RealityKeysResponse {
 }
 
 pub async fn generate_reality_keys() -> Result<Json<RealityKeysResponse>, StatusCode> {
-    let xray_bin =
-        std::env::var("XRAY_BIN_PATH").unwrap_or_else(|_| "/usr/local/x-ui/bin/xray".to_string());
+    let xray_bin = crate::services::xray_process::bin_path();
 
     let output = Command::new(&xray_bin)
         .arg("x25519")
@@ -59,3 +67,39 @@ pub async fn generate_reality_keys() -> Result<Json<RealityKeysResponse>, Status
         public_key,
     }))
 }
+
+/// Returns the config `apply_config` would install, rendered from the database and template
+/// without applying it.
+pub async fn get_config(
+    _user: AuthUser,
+    Extension(pool): Extension<SqlitePool>,
+    Query(query): Query<ConfigQuery>,
+) -> ApiResult<Response> {
+    let mut config = xray_service::render_config(&mut *pool.acquire().await?, None).await?;
+    if query.redact.unwrap_or(true) {
+        xray_service::redact_secrets(&mut config);
+    }
+
+    if !query.download.unwrap_or(false) {
+        return Ok(ApiResponse::success(config).into_response());
+    }
+
+    let body = serde_json::to_string_pretty(&config)
+        .map_err(|e| ApiError::InternalError(format!("Failed to serialize config: {}", e)))?;
+    let filename = format!(
+        "xray_config_{}.json",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    );
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -34,6 +34,12 @@ SERVER_PORT=8080
 XRAY_BIN_PATH=./bin/xray
 XRAY_CONFIG_PATH=./data/xray.json
 
+# Address written into subscription links (defaults to the Host the client requested)
+# PUBLIC_HOST=example.com
+
+# Bearer token Prometheus scrapes /metrics with (metrics are disabled while unset)
+# METRICS_TOKEN=
+
 # Log level
 RUST_LOG=debug,sqlx=warn
 "#,
@@ -125,6 +131,12 @@ async fn main() -> anyhow::Result<()> {
 
     services::auth_service::init_default_admin(&pool).await?;
 
+    let mut conn = pool.acquire().await?;
+    if let Err(e) = services::client_service::import_legacy_clients(&mut conn).await {
+        tracing::warn!("Failed to import legacy inbound clients: {}", e);
+    }
+    drop(conn);
+
     let monitor = std::sync::Arc::new(std::sync::Mutex::new(
         services::system_service::SystemMonitor::new(),
     ));
@@ -145,13 +157,15 @@ async fn main() -> anyhow::Result<()> {
     }
     std::env::set_var("WEB_ROOT", web_root);
 
-    if let Err(e) = services::xray_service::apply_config(&pool, monitor.clone()).await {
+    let trigger = models::config_revision::ConfigTrigger::system("Panel startup");
+    if let Err(e) = services::xray_service::apply_config(&pool, monitor.clone(), trigger).await {
         tracing::error!("Failed to apply config on startup: {}", e);
     } else {
         tracing::info!("Initial Xray core successfully started or updated");
     }
 
     services::traffic_service::start_traffic_stats_task(pool.clone(), monitor.clone());
+    services::traffic_history_service::start_pruning_task(pool.clone());
 
     #[cfg(debug_assertions)]
     let cors_layer = match std::env::var("SERVER_HOST") {
@@ -192,7 +206,11 @@ async fn main() -> anyhow::Result<()> {
         ])
         .allow_credentials(false);
 
+    let metrics_router = routes::create_metrics_router(pool.clone(), monitor.clone());
     let api_router = routes::create_router(pool, monitor)
+        .layer(axum::middleware::from_fn(
+            middleware::metrics::http_metrics_middleware,
+        ))
         .layer(axum::middleware::from_fn(
             middleware::security::security_headers_middleware,
         ))
@@ -281,6 +299,7 @@ async fn main() -> anyhow::Result<()> {
 
     let router = Router::new()
         .nest("/api", api_router)
+        .merge(metrics_router)
         .route("/", axum::routing::get(index_handler.clone()))
         .route("/index.html", axum::routing::get(index_handler.clone()))
         .fallback_service(file_service);
@@ -0,0 +1,23 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+
+use crate::services::metrics_service;
+
+/// Times every API request for the latency histogram, labelled by route template.
+pub async fn http_metrics_middleware(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(req).await;
+    metrics_service::observe_request(&method, &path, response.status().as_u16(), start.elapsed());
+    response
+}
@@ -1,4 +1,5 @@
 // src/middleware/mod.rs
 
 pub mod auth;
+pub mod metrics;
 pub mod security;
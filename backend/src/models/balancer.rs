@@ -0,0 +1,121 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Values accepted for a balancer's `strategy.type`.
+pub const BALANCER_STRATEGIES: &[&str] = &["random", "roundRobin", "leastPing", "leastLoad"];
+
+/// A persisted `routing.balancers` entry. `selector` is a JSON array of outbound tag prefixes.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Balancer {
+    pub id: i64,
+    pub tag: String,
+    pub remark: String,
+    pub selector: String,
+    pub strategy: String,
+    /// `strategy.settings` as JSON text; only used by `leastLoad`.
+    pub strategy_settings: Option<String>,
+    pub fallback_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl Balancer {
+    /// Strategies that rank outbounds by observatory probe results.
+    pub fn needs_observatory(&self) -> bool {
+        matches!(self.strategy.as_str(), "leastPing" | "leastLoad")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateBalancerRequest {
+    pub tag: String,
+    pub remark: Option<String>,
+    pub selector: Vec<String>,
+    pub strategy: Option<String>,
+    pub strategy_settings: Option<serde_json::Value>,
+    pub fallback_tag: Option<String>,
+}
+
+/// Omitted fields are left unchanged. An empty `fallbackTag` clears it, and a
+/// `strategySettings` of `{}` removes the settings.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateBalancerRequest {
+    pub id: i64,
+    pub tag: Option<String>,
+    pub remark: Option<String>,
+    pub selector: Option<Vec<String>>,
+    pub strategy: Option<String>,
+    pub strategy_settings: Option<serde_json::Value>,
+    pub fallback_tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteBalancerRequest {
+    pub id: i64,
+}
+
+/// Which observatory section, if any, the generated config contains.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ObservatoryMode {
+    #[default]
+    None,
+    Observatory,
+    BurstObservatory,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservatorySettings {
+    #[serde(default)]
+    pub mode: ObservatoryMode,
+    #[serde(default)]
+    pub subject_selector: Vec<String>,
+    /// `observatory` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe_url: Option<String>,
+    /// `observatory` only, e.g. `10s`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe_interval: Option<String>,
+    /// `observatory` only.
+    #[serde(default)]
+    pub enable_concurrency: bool,
+    /// `burstObservatory` only; passed through as `pingConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ping_config: Option<serde_json::Value>,
+}
+
+/// One outbound's latest probe result from the Xray ObservatoryService.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundProbe {
+    pub outbound_tag: String,
+    pub alive: bool,
+    /// Round-trip time of the last probe in milliseconds.
+    pub delay: i64,
+    pub last_error_reason: String,
+    /// Unix timestamps in seconds.
+    pub last_seen_time: i64,
+    pub last_try_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_ping: Option<HealthPing>,
+}
+
+/// Aggregated burst observatory measurements: probe counts and durations in milliseconds.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthPing {
+    pub all: i64,
+    pub fail: i64,
+    pub deviation: i64,
+    pub average: i64,
+    pub max: i64,
+    pub min: i64,
+}
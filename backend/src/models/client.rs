@@ -0,0 +1,92 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Client {
+    pub id: i64,
+    pub inbound_id: String,
+    pub email: String,
+    pub uuid: String,
+    /// Trojan, Shadowsocks, SOCKS and HTTP password; the user PSK for Shadowsocks 2022.
+    pub password: String,
+    pub flow: String,
+    /// Policy level from the `policy.levels` settings.
+    pub level: i64,
+    /// Subscription token; every client sharing it is served by `/sub/{sub_id}`.
+    pub sub_id: String,
+    pub enable: bool,
+    pub up: i64,
+    pub down: i64,
+    pub total: i64,
+    pub expiry: i64,
+    /// Why the background task disabled this client, see
+    /// [`DisabledReason`](crate::models::inbound::DisabledReason).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl Client {
+    /// Whether the client has used up its traffic quota. A `total` of 0 means unlimited.
+    pub fn is_exhausted(&self) -> bool {
+        self.total > 0 && self.up + self.down >= self.total
+    }
+
+    /// Whether the client has passed its expiry time. `expiry` is a unix timestamp in
+    /// milliseconds, 0 means never.
+    pub fn is_expired(&self, now_ms: i64) -> bool {
+        self.expiry > 0 && self.expiry <= now_ms
+    }
+
+    /// Whether the client should be written into the generated Xray config.
+    pub fn is_active(&self, now_ms: i64) -> bool {
+        self.enable && !self.is_exhausted() && !self.is_expired(now_ms)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClientRequest {
+    pub email: String,
+    pub uuid: Option<String>,
+    pub password: Option<String>,
+    pub flow: Option<String>,
+    pub level: Option<i64>,
+    pub sub_id: Option<String>,
+    pub enable: Option<bool>,
+    pub total: Option<i64>,
+    pub expiry: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClientRequest {
+    pub email: Option<String>,
+    pub uuid: Option<String>,
+    pub password: Option<String>,
+    pub flow: Option<String>,
+    pub level: Option<i64>,
+    pub sub_id: Option<String>,
+    pub enable: Option<bool>,
+    pub total: Option<i64>,
+    pub expiry: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    /// QR code image format, `png` (default) or `svg`.
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLinkResponse {
+    pub link: String,
+    /// The link rendered as a QR code `data:` URI.
+    pub qr_code: String,
+}
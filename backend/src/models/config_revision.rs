@@ -0,0 +1,59 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Who or what caused a config to be generated, stored with each revision.
+#[derive(Debug, Clone)]
+pub struct ConfigTrigger {
+    /// Panel user, `None` for changes made by the panel itself.
+    pub username: Option<String>,
+    pub reason: String,
+}
+
+impl ConfigTrigger {
+    pub fn user(username: &str, reason: impl Into<String>) -> Self {
+        Self {
+            username: Some(username.to_string()),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn system(reason: impl Into<String>) -> Self {
+        Self {
+            username: None,
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigRevisionSummary {
+    pub id: i64,
+    pub username: Option<String>,
+    pub reason: String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigRevision {
+    #[serde(flatten)]
+    pub summary: ConfigRevisionSummary,
+    pub config: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionDiffQuery {
+    /// Revision to compare against, defaults to the one before.
+    pub base: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionDiff {
+    /// `None` when diffing the first revision, which is compared with an empty config.
+    pub base: Option<i64>,
+    pub revision: i64,
+    pub changes: Vec<crate::utils::json_diff::JsonChange>,
+}
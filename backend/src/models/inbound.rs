@@ -25,12 +25,69 @@ pub struct Inbound {
     pub down: i64,
     pub total: i64,
     pub expiry: i64,
+    /// Why the background task disabled this inbound, see [`DisabledReason`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_reason: Option<String>,
+    /// Automatic traffic reset schedule, see [`TrafficReset`].
+    pub traffic_reset: String,
+    /// Day of the month (1-31) or of the week (1 = Monday) a monthly or weekly reset runs on.
+    pub traffic_reset_day: i64,
+    /// When the traffic counters were last reset, unix timestamp in milliseconds.
+    pub last_traffic_reset: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<NaiveDateTime>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<NaiveDateTime>,
 }
 
+/// Reason recorded when an inbound or client is disabled automatically rather than by an admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisabledReason {
+    Expired,
+    QuotaExceeded,
+}
+
+impl DisabledReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisabledReason::Expired => "expired",
+            DisabledReason::QuotaExceeded => "quota_exceeded",
+        }
+    }
+}
+
+/// How often the background task zeroes the traffic counters of an inbound. Resets run at
+/// local midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficReset {
+    Never,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl TrafficReset {
+    pub const ALL: [TrafficReset; 4] = [
+        TrafficReset::Never,
+        TrafficReset::Daily,
+        TrafficReset::Weekly,
+        TrafficReset::Monthly,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrafficReset::Never => "never",
+            TrafficReset::Daily => "daily",
+            TrafficReset::Weekly => "weekly",
+            TrafficReset::Monthly => "monthly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == value)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateInboundRequest {
@@ -49,6 +106,8 @@ pub struct CreateInboundRequest {
     pub sniffing: Option<serde_json::Value>,
     pub total: Option<i64>,
     pub expiry: Option<i64>,
+    pub traffic_reset: Option<String>,
+    pub traffic_reset_day: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -69,6 +128,8 @@ pub struct UpdateInboundRequest {
     pub sniffing: Option<serde_json::Value>,
     pub total: Option<i64>,
     pub expiry: Option<i64>,
+    pub traffic_reset: Option<String>,
+    pub traffic_reset_day: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -1,7 +1,13 @@
 // src/models/mod.rs
 
+pub mod balancer;
+pub mod client;
+pub mod config_revision;
 pub mod inbound;
+pub mod outbound;
 pub mod protocol_settings;
+pub mod routing_rule;
 pub mod stream_settings;
+pub mod traffic_history;
 pub mod user;
 pub mod xray_config;
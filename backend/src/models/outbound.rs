@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Outbound protocols that can be stored in the `outbounds` table.
+pub const OUTBOUND_PROTOCOLS: &[&str] = &[
+    "freedom",
+    "blackhole",
+    "socks",
+    "http",
+    "vless",
+    "vmess",
+    "trojan",
+    "shadowsocks",
+    "wireguard",
+];
+
+/// Tags of the outbounds every generated config already contains.
+pub const RESERVED_OUTBOUND_TAGS: &[&str] = &["api", "direct", "blocked"];
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Outbound {
+    pub id: i64,
+    pub tag: String,
+    pub remark: String,
+    pub protocol: String,
+    pub enable: bool,
+    pub settings: Option<String>,
+    pub stream_settings: Option<String>,
+    /// Tag of another outbound this one dials through (`proxySettings.tag`).
+    pub proxy_tag: Option<String>,
+    pub mux: Option<String>,
+    pub send_through: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOutboundRequest {
+    pub tag: String,
+    pub remark: Option<String>,
+    pub protocol: String,
+    pub enable: Option<bool>,
+    pub settings: Option<serde_json::Value>,
+    pub stream_settings: Option<serde_json::Value>,
+    pub proxy_tag: Option<String>,
+    pub mux: Option<serde_json::Value>,
+    pub send_through: Option<String>,
+}
+
+/// Omitted fields are left unchanged. An empty `proxyTag` or `sendThrough` clears it, and a
+/// `mux` of `{}` removes the mux settings.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateOutboundRequest {
+    pub id: i64,
+    pub tag: Option<String>,
+    pub remark: Option<String>,
+    pub protocol: Option<String>,
+    pub enable: Option<bool>,
+    pub settings: Option<serde_json::Value>,
+    pub stream_settings: Option<serde_json::Value>,
+    pub proxy_tag: Option<String>,
+    pub mux: Option<serde_json::Value>,
+    pub send_through: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteOutboundRequest {
+    pub id: i64,
+}
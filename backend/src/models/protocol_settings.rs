@@ -1 +1,188 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+/// Inbound protocols the panel can configure. Xray-core has no Hysteria2 server, so it is
+/// not offered here.
+pub const INBOUND_PROTOCOLS: &[&str] = &[
+    "vless",
+    "vmess",
+    "trojan",
+    "shadowsocks",
+    "socks",
+    "http",
+    "dokodemo-door",
+];
+
+/// Shadowsocks ciphers accepted for multi-user inbounds. `2022-blake3-chacha20-poly1305` is
+/// left out because Xray only supports it for a single user.
+pub const SHADOWSOCKS_METHODS: &[&str] = &[
+    "aes-128-gcm",
+    "aes-256-gcm",
+    "chacha20-poly1305",
+    "chacha20-ietf-poly1305",
+    "xchacha20-poly1305",
+    "xchacha20-ietf-poly1305",
+    "2022-blake3-aes-128-gcm",
+    "2022-blake3-aes-256-gcm",
+];
+
+/// Values accepted for the `network` of Shadowsocks and dokodemo-door inbounds.
+pub const INBOUND_NETWORKS: &[&str] = &["tcp", "udp", "tcp,udp"];
+
+/// How the clients of a protocol authenticate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credential {
+    /// VLESS and VMess: the client UUID.
+    Uuid,
+    /// Trojan, legacy Shadowsocks, SOCKS and HTTP: a free-form password.
+    Password,
+    /// Shadowsocks 2022: a base64 encoded key of the given length in bytes.
+    Key(usize),
+}
+
+impl Credential {
+    /// The credential used by clients of `protocol`, `None` if it has no clients. `method` is
+    /// the Shadowsocks cipher and ignored for other protocols.
+    pub fn for_protocol(protocol: &str, method: Option<&str>) -> Option<Self> {
+        match protocol {
+            "vless" | "vmess" => Some(Credential::Uuid),
+            "trojan" | "socks" | "http" => Some(Credential::Password),
+            "shadowsocks" => Some(
+                method
+                    .and_then(shadowsocks_key_len)
+                    .map_or(Credential::Password, Credential::Key),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Key length in bytes of a Shadowsocks 2022 cipher, `None` for the legacy AEAD ciphers.
+pub fn shadowsocks_key_len(method: &str) -> Option<usize> {
+    match method {
+        "2022-blake3-aes-128-gcm" => Some(16),
+        "2022-blake3-aes-256-gcm" | "2022-blake3-chacha20-poly1305" => Some(32),
+        _ => None,
+    }
+}
+
+/// Settings key holding the users of `protocol`: SOCKS and HTTP call them `accounts`.
+pub fn users_key(protocol: &str) -> &'static str {
+    match protocol {
+        "socks" | "http" => "accounts",
+        _ => "clients",
+    }
+}
+
+/// The typed `settings` of an inbound, without the user list which comes from the
+/// `clients` table.
+#[derive(Debug, Clone)]
+pub enum InboundSettings {
+    Vless(VlessSettings),
+    Vmess,
+    Trojan(TrojanSettings),
+    Shadowsocks(ShadowsocksSettings),
+    Socks(SocksSettings),
+    Http,
+    Dokodemo(DokodemoSettings),
+}
+
+impl InboundSettings {
+    /// Deserializes `settings` for `protocol`. Unknown keys, such as `clients`, are ignored.
+    pub fn parse(protocol: &str, settings: &Value) -> Result<Self, String> {
+        fn typed<T: for<'de> Deserialize<'de>>(settings: &Value) -> Result<T, String> {
+            serde_json::from_value(settings.clone()).map_err(|e| e.to_string())
+        }
+
+        Ok(match protocol {
+            "vless" => InboundSettings::Vless(typed(settings)?),
+            "vmess" => InboundSettings::Vmess,
+            "trojan" => InboundSettings::Trojan(typed(settings)?),
+            "shadowsocks" => InboundSettings::Shadowsocks(typed(settings)?),
+            "socks" => InboundSettings::Socks(typed(settings)?),
+            "http" => InboundSettings::Http,
+            "dokodemo-door" => InboundSettings::Dokodemo(typed(settings)?),
+            _ => return Err(format!("Unsupported protocol: {}", protocol)),
+        })
+    }
+}
+
+fn default_decryption() -> String {
+    "none".to_string()
+}
+
+fn default_network() -> String {
+    "tcp".to_string()
+}
+
+fn default_auth() -> String {
+    "noauth".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VlessSettings {
+    #[serde(default = "default_decryption")]
+    pub decryption: String,
+    #[serde(default)]
+    pub fallbacks: Vec<Fallback>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrojanSettings {
+    #[serde(default)]
+    pub fallbacks: Vec<Fallback>,
+}
+
+/// A VLESS or Trojan fallback. `dest` is a port number, `addr:port` or a unix socket path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fallback {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub alpn: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub dest: Value,
+    #[serde(default)]
+    pub xver: Option<u8>,
+}
+
+/// Shadowsocks settings. With a 2022 cipher `password` is the server PSK and each client
+/// holds its own user PSK.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowsocksSettings {
+    pub method: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default = "default_network")]
+    pub network: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SocksSettings {
+    #[serde(default = "default_auth")]
+    pub auth: String,
+    /// Local address announced for UDP associate.
+    #[serde(default)]
+    pub ip: Option<String>,
+}
+
+/// dokodemo-door settings. `address` may only be omitted together with `followRedirect`,
+/// where the original destination of redirected traffic is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DokodemoSettings {
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default = "default_network")]
+    pub network: String,
+    #[serde(default)]
+    pub follow_redirect: bool,
+}
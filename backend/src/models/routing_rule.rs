@@ -0,0 +1,82 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+
+/// Values accepted for `routing.domainStrategy`.
+pub const ROUTING_DOMAIN_STRATEGIES: &[&str] = &["AsIs", "IPIfNonMatch", "IPOnDemand"];
+
+/// A persisted routing rule. List-valued matchers (`domain`, `ip`, `source`, `user`,
+/// `inboundTag`, `protocol`) and `attrs` are stored as JSON text.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteRule {
+    pub id: i64,
+    /// Position in the generated rule list; lower values match first.
+    pub priority: i64,
+    pub remark: String,
+    pub enable: bool,
+    pub domain: Option<String>,
+    pub ip: Option<String>,
+    pub port: Option<String>,
+    pub source_port: Option<String>,
+    pub source: Option<String>,
+    pub user: Option<String>,
+    pub inbound_tag: Option<String>,
+    pub network: Option<String>,
+    pub protocol: Option<String>,
+    pub attrs: Option<String>,
+    pub outbound_tag: Option<String>,
+    pub balancer_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateRouteRuleRequest {
+    pub remark: Option<String>,
+    pub enable: Option<bool>,
+    pub domain: Option<Vec<String>>,
+    pub ip: Option<Vec<String>>,
+    pub port: Option<String>,
+    pub source_port: Option<String>,
+    pub source: Option<Vec<String>>,
+    pub user: Option<Vec<String>>,
+    pub inbound_tag: Option<Vec<String>>,
+    pub network: Option<String>,
+    pub protocol: Option<Vec<String>>,
+    pub attrs: Option<BTreeMap<String, String>>,
+    pub outbound_tag: Option<String>,
+    pub balancer_tag: Option<String>,
+}
+
+/// Omitted fields are left unchanged; an empty list, map or string clears the matcher.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRouteRuleRequest {
+    pub id: i64,
+    #[serde(flatten)]
+    pub rule: CreateRouteRuleRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteRouteRuleRequest {
+    pub id: i64,
+}
+
+/// The complete list of rule ids in their new order.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderRouteRulesRequest {
+    pub ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingSettings {
+    pub domain_strategy: String,
+}
@@ -1 +1,247 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+/// Transports accepted for `streamSettings.network`. `raw` is the newer name of `tcp`.
+pub const NETWORKS: &[&str] = &["raw", "tcp", "xhttp", "ws", "grpc", "httpupgrade", "kcp"];
+
+/// Values accepted for `streamSettings.security`.
+pub const SECURITIES: &[&str] = &["none", "tls", "reality"];
+
+/// Values accepted for `xhttpSettings.mode`.
+pub const XHTTP_MODES: &[&str] = &["auto", "packet-up", "stream-up", "stream-one"];
+
+/// Header obfuscation types of mKCP.
+pub const KCP_HEADERS: &[&str] = &[
+    "none",
+    "srtp",
+    "utp",
+    "wechat-video",
+    "dtls",
+    "wireguard",
+    "dns",
+];
+
+/// uTLS fingerprints a share link may ask the client to mimic.
+pub const FINGERPRINTS: &[&str] = &[
+    "chrome",
+    "firefox",
+    "safari",
+    "ios",
+    "android",
+    "edge",
+    "360",
+    "qq",
+    "random",
+    "randomized",
+];
+
+/// Protocols `sniffing.destOverride` can sniff.
+pub const SNIFF_PROTOCOLS: &[&str] = &["http", "tls", "quic", "fakedns"];
+
+/// The typed `streamSettings` of an inbound. Only the fields the panel validates are modelled;
+/// the stored JSON keeps everything else untouched.
+#[derive(Debug, Clone, Default)]
+pub struct StreamSettings {
+    pub network: String,
+    pub security: String,
+    pub tls_settings: Option<TlsSettings>,
+    pub reality_settings: Option<RealitySettings>,
+    pub tcp_settings: Option<TcpSettings>,
+    pub raw_settings: Option<TcpSettings>,
+    pub xhttp_settings: Option<XhttpSettings>,
+    pub ws_settings: Option<WsSettings>,
+    pub grpc_settings: Option<GrpcSettings>,
+    pub httpupgrade_settings: Option<HttpUpgradeSettings>,
+    pub kcp_settings: Option<KcpSettings>,
+}
+
+impl StreamSettings {
+    /// Builds the typed settings section by section so a malformed section is reported by
+    /// its key, e.g. `kcpSettings: invalid type: string "x", expected u32`.
+    pub fn from_value(value: &Value) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        if !value.is_object() {
+            return Err(vec!["streamSettings must be a JSON object".to_string()]);
+        }
+
+        let settings = StreamSettings {
+            network: section(value, "network", &mut errors).unwrap_or_else(|| "tcp".to_string()),
+            security: section(value, "security", &mut errors).unwrap_or_else(|| "none".to_string()),
+            tls_settings: section(value, "tlsSettings", &mut errors),
+            reality_settings: section(value, "realitySettings", &mut errors),
+            tcp_settings: section(value, "tcpSettings", &mut errors),
+            raw_settings: section(value, "rawSettings", &mut errors),
+            xhttp_settings: section(value, "xhttpSettings", &mut errors),
+            ws_settings: section(value, "wsSettings", &mut errors),
+            grpc_settings: section(value, "grpcSettings", &mut errors),
+            httpupgrade_settings: section(value, "httpupgradeSettings", &mut errors),
+            kcp_settings: section(value, "kcpSettings", &mut errors),
+        };
+
+        if errors.is_empty() {
+            Ok(settings)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Deserializes `value[key]`, recording a `key: reason` error if it has the wrong shape.
+fn section<T: DeserializeOwned>(value: &Value, key: &str, errors: &mut Vec<String>) -> Option<T> {
+    let field = value.get(key).filter(|v| !v.is_null())?;
+    serde_json::from_value(field.clone())
+        .map_err(|e| errors.push(format!("{}: {}", key, e)))
+        .ok()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSettings {
+    #[serde(default)]
+    pub server_name: Option<String>,
+    #[serde(default)]
+    pub alpn: Vec<String>,
+    #[serde(default)]
+    pub certificates: Vec<TlsCertificate>,
+    #[serde(default)]
+    pub min_version: Option<String>,
+    #[serde(default)]
+    pub max_version: Option<String>,
+    /// Client fingerprint written into share links.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+/// A TLS certificate, either as file paths or inline PEM lines.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsCertificate {
+    #[serde(default)]
+    pub certificate_file: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    #[serde(default)]
+    pub certificate: Vec<String>,
+    #[serde(default)]
+    pub key: Vec<String>,
+    #[serde(default)]
+    pub usage: Option<String>,
+}
+
+/// REALITY server settings. Newer Xray calls `dest` `target`; either is accepted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealitySettings {
+    #[serde(default)]
+    pub dest: Option<Value>,
+    #[serde(default)]
+    pub target: Option<Value>,
+    #[serde(default)]
+    pub xver: u8,
+    #[serde(default)]
+    pub server_names: Vec<String>,
+    #[serde(default)]
+    pub private_key: String,
+    #[serde(default)]
+    pub short_ids: Vec<String>,
+    /// Public key written into share links.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+/// `tcpSettings`/`rawSettings`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpSettings {
+    #[serde(default)]
+    pub header: Option<TcpHeader>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpHeader {
+    #[serde(rename = "type", default)]
+    pub header_type: String,
+    #[serde(default)]
+    pub request: Option<TcpHeaderRequest>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpHeaderRequest {
+    #[serde(default)]
+    pub path: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XhttpSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub host: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcSettings {
+    #[serde(default)]
+    pub service_name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpUpgradeSettings {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub host: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KcpSettings {
+    #[serde(default)]
+    pub mtu: Option<u32>,
+    #[serde(default)]
+    pub tti: Option<u32>,
+    #[serde(default)]
+    pub uplink_capacity: Option<u32>,
+    #[serde(default)]
+    pub downlink_capacity: Option<u32>,
+    #[serde(default)]
+    pub header: Option<KcpHeader>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KcpHeader {
+    #[serde(rename = "type", default)]
+    pub header_type: String,
+}
+
+/// The typed `sniffing` object of an inbound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sniffing {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub dest_override: Vec<String>,
+    #[serde(default)]
+    pub domains_excluded: Vec<String>,
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// What a `traffic_history` row counts traffic for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistorySubject {
+    Inbound,
+    Client,
+}
+
+impl HistorySubject {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistorySubject::Inbound => "inbound",
+            HistorySubject::Client => "client",
+        }
+    }
+}
+
+/// Bucket size of a traffic history series. Hours are aligned to UTC, days to local
+/// midnight of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryPeriod {
+    Hour,
+    Day,
+}
+
+impl HistoryPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryPeriod::Hour => "hour",
+            HistoryPeriod::Day => "day",
+        }
+    }
+}
+
+/// Traffic counted for one inbound or client during one poll of the Xray stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficDelta {
+    pub subject: HistorySubject,
+    pub subject_id: String,
+    pub up: i64,
+    pub down: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    /// Range start as a unix timestamp in milliseconds. Defaults to one day (hourly) or
+    /// 30 days (daily) before `to`.
+    pub from: Option<i64>,
+    /// Range end in milliseconds, defaults to now.
+    pub to: Option<i64>,
+    /// `hour` or `day`; picked from the length of the range when omitted.
+    pub period: Option<HistoryPeriod>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficPoint {
+    /// Bucket start in milliseconds.
+    pub time: i64,
+    pub up: i64,
+    pub down: i64,
+}
+
+/// A gap-free series of buckets covering `from..=to`, with the totals over the range.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficSeries {
+    pub period: HistoryPeriod,
+    pub from: i64,
+    pub to: i64,
+    pub up: i64,
+    pub down: i64,
+    pub points: Vec<TrafficPoint>,
+}
@@ -1,16 +1,21 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct XrayConfig {
     pub log: LogConfig,
     pub api: ApiConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dns: Option<DnsConfig>,
     pub stats: Option<StatsConfig>,
     pub policy: Option<PolicyConfig>,
     pub inbounds: Vec<InboundConfig>,
     pub outbounds: Vec<OutboundConfig>,
     pub routing: Option<RoutingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observatory: Option<ObservatoryConfig>,
+    #[serde(rename = "burstObservatory", skip_serializing_if = "Option::is_none")]
+    pub burst_observatory: Option<BurstObservatoryConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,26 +43,92 @@ pub struct ApiConfig {
     pub services: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
+pub struct ConfigQuery {
+    /// Replace credentials with a placeholder; defaults to `true`.
+    pub redact: Option<bool>,
+    /// Serve the config as a JSON file attachment instead of an API response.
+    pub download: Option<bool>,
+}
+
+/// The Xray `dns` object. It doubles as the stored DNS settings, so every field has a default.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct DnsConfig {
-    pub servers: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, DnsHosts>,
+    #[serde(default)]
+    pub servers: Vec<DnsServer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_strategy: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_cache: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_fallback: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_fallback_if_match: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// A `hosts` value: one address or a list of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DnsHosts {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A `servers` entry: a bare address such as `8.8.8.8` or `https://dns.google/dns-query`, or
+/// a server object with per-domain selection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DnsServer {
+    Address(String),
+    Detailed(DnsServerObject),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsServerObject {
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    #[serde(rename = "expectIPs", default, skip_serializing_if = "Vec::is_empty")]
+    pub expect_ips: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_fallback: bool,
+    #[serde(rename = "clientIP", default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_strategy: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct StatsConfig {}
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+fn default_true() -> bool {
+    true
+}
+
+/// The Xray `policy` object, also stored as the panel's policy settings. Level keys are
+/// numeric strings that clients reference through their `level`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PolicyConfig {
     pub levels: HashMap<String, LevelPolicy>,
     pub system: Option<SystemPolicy>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct LevelPolicy {
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub stats_user_uplink: bool,
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub stats_user_downlink: bool,
     #[serde(default)]
     pub handshake: u32,
@@ -71,12 +142,12 @@ pub struct LevelPolicy {
     pub buffer_size: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct SystemPolicy {
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub stats_inbound_uplink: bool,
-    #[serde(default)]
+    #[serde(default = "default_true")]
     pub stats_inbound_downlink: bool,
     #[serde(default)]
     pub stats_outbound_uplink: bool,
@@ -113,15 +184,66 @@ pub struct OutboundConfig {
     pub tag: String,
     pub protocol: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_through: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub settings: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_settings: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_settings: Option<ProxySettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mux: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProxySettings {
+    pub tag: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct RoutingConfig {
     pub domain_strategy: String,
     pub rules: Vec<RoutingRule>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub balancers: Vec<BalancerConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalancerConfig {
+    pub tag: String,
+    pub selector: Vec<String>,
+    pub strategy: BalancerStrategy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_tag: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalancerStrategy {
+    #[serde(rename = "type")]
+    pub strategy_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservatoryConfig {
+    pub subject_selector: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_interval: Option<String>,
+    pub enable_concurrency: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BurstObservatoryConfig {
+    pub subject_selector: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping_config: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -130,15 +252,27 @@ pub struct RoutingRule {
     #[serde(rename = "type")]
     pub rule_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub port: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub inbound_tag: Option<Vec<String>>,
+    pub source_port: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub outbound_tag: Option<String>,
+    pub source: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ip: Option<Vec<String>>,
+    pub user: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub domain: Option<Vec<String>>,
+    pub inbound_tag: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub protocol: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attrs: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbound_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balancer_tag: Option<String>,
 }
@@ -1,6 +1,6 @@
 use axum::{
     middleware,
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 use sqlx::SqlitePool;
@@ -51,6 +51,23 @@ pub fn create_router(pool: SqlitePool, monitor: SharedMonitor) -> Router {
         .route("/del", post(handlers::inbound::del_inbound_post))
         .route("/reset-traffic", post(handlers::inbound::reset_traffic))
         .route("/check-reality", post(handlers::inbound::check_reality))
+        .route("/:id/history", get(handlers::inbound::traffic_history))
+        .route(
+            "/:id/clients",
+            get(handlers::client::list_clients).post(handlers::client::add_client),
+        )
+        .route(
+            "/:id/clients/:client_id",
+            put(handlers::client::update_client).delete(handlers::client::delete_client),
+        )
+        .route(
+            "/:id/clients/:client_id/share",
+            get(handlers::client::share_client),
+        )
+        .route(
+            "/:id/clients/:client_id/history",
+            get(handlers::client::traffic_history),
+        )
         .route_layer(middleware::from_fn_with_state(
             pool.clone(),
             auth_middleware,
@@ -58,14 +75,120 @@ pub fn create_router(pool: SqlitePool, monitor: SharedMonitor) -> Router {
         .layer(axum::Extension(pool.clone()))
         .with_state(monitor.clone());
 
-    let xray_routes = Router::new().route(
-        "/generate-reality-keys",
-        get(crate::handlers::xray::generate_reality_keys),
-    );
+    let outbound_routes = Router::new()
+        .route("/list", get(handlers::outbound::list_outbounds))
+        .route("/add", post(handlers::outbound::add_outbound))
+        .route("/update", post(handlers::outbound::update_outbound))
+        .route("/del", post(handlers::outbound::del_outbound))
+        .route_layer(middleware::from_fn_with_state(
+            pool.clone(),
+            auth_middleware,
+        ))
+        .layer(axum::Extension(pool.clone()))
+        .with_state(monitor.clone());
+
+    let routing_routes = Router::new()
+        .route("/list", get(handlers::routing::list_rules))
+        .route("/add", post(handlers::routing::add_rule))
+        .route("/update", post(handlers::routing::update_rule))
+        .route("/del", post(handlers::routing::del_rule))
+        .route("/reorder", post(handlers::routing::reorder_rules))
+        .route(
+            "/settings",
+            get(handlers::routing::get_settings).post(handlers::routing::update_settings),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            pool.clone(),
+            auth_middleware,
+        ))
+        .layer(axum::Extension(pool.clone()))
+        .with_state(monitor.clone());
+
+    let balancer_routes = Router::new()
+        .route("/list", get(handlers::balancer::list_balancers))
+        .route("/add", post(handlers::balancer::add_balancer))
+        .route("/update", post(handlers::balancer::update_balancer))
+        .route("/del", post(handlers::balancer::del_balancer))
+        .route(
+            "/observatory",
+            get(handlers::balancer::get_observatory).post(handlers::balancer::update_observatory),
+        )
+        .route(
+            "/observatory/status",
+            get(handlers::balancer::observatory_status),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            pool.clone(),
+            auth_middleware,
+        ))
+        .layer(axum::Extension(pool.clone()))
+        .with_state(monitor.clone());
+
+    let xray_routes = Router::new()
+        .route(
+            "/generate-reality-keys",
+            get(crate::handlers::xray::generate_reality_keys),
+        )
+        .merge(
+            Router::new()
+                .route("/config", get(handlers::xray::get_config))
+                .route(
+                    "/dns",
+                    get(handlers::dns::get_dns).post(handlers::dns::update_dns),
+                )
+                .route(
+                    "/template",
+                    get(handlers::template::get_template).post(handlers::template::update_template),
+                )
+                .route(
+                    "/template/preview",
+                    post(handlers::template::preview_template),
+                )
+                .route(
+                    "/policy",
+                    get(handlers::policy::get_policy).post(handlers::policy::update_policy),
+                )
+                .route("/revisions", get(handlers::config_revision::list_revisions))
+                .route(
+                    "/revisions/:id",
+                    get(handlers::config_revision::get_revision),
+                )
+                .route(
+                    "/revisions/:id/diff",
+                    get(handlers::config_revision::diff_revision),
+                )
+                .route(
+                    "/revisions/:id/rollback",
+                    post(handlers::config_revision::rollback_revision),
+                )
+                .route_layer(middleware::from_fn_with_state(
+                    pool.clone(),
+                    auth_middleware,
+                ))
+                .layer(axum::Extension(pool.clone()))
+                .with_state(monitor.clone()),
+        );
+
+    let sub_routes = Router::new()
+        .route("/:sub_id", get(handlers::subscription::get_subscription))
+        .layer(axum::Extension(pool.clone()));
 
     Router::new()
         .nest("/auth", auth_routes)
         .nest("/server", system_routes)
         .nest("/inbound", inbound_routes)
+        .nest("/outbound", outbound_routes)
+        .nest("/routing", routing_routes)
+        .nest("/balancer", balancer_routes)
         .nest("/xray", xray_routes)
+        .nest("/sub", sub_routes)
+}
+
+/// `/metrics` lives outside `/api` where Prometheus expects it and carries its own token
+/// check instead of the panel login.
+pub fn create_metrics_router(pool: SqlitePool, monitor: SharedMonitor) -> Router {
+    Router::new()
+        .route("/metrics", get(handlers::metrics::metrics))
+        .layer(axum::Extension(pool))
+        .with_state(monitor)
 }
@@ -0,0 +1,543 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::balancer::{
+    Balancer, CreateBalancerRequest, HealthPing, ObservatoryMode, ObservatorySettings,
+    OutboundProbe, UpdateBalancerRequest, BALANCER_STRATEGIES,
+};
+use crate::services::xray_api::XrayApiClient;
+use crate::services::xray_settings_service;
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+const OBSERVATORY_KEY: &str = "observatory";
+
+pub async fn get_all_balancers(conn: &mut SqliteConnection) -> ApiResult<Vec<Balancer>> {
+    let balancers = sqlx::query_as::<_, Balancer>("SELECT * FROM balancers ORDER BY id ASC")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(balancers)
+}
+
+pub async fn add_balancer(
+    conn: &mut SqliteConnection,
+    req: CreateBalancerRequest,
+) -> ApiResult<Balancer> {
+    let tag = req.tag.trim().to_string();
+    validate_tag(&tag)?;
+    if find_by_tag(conn, &tag).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "Balancer tag {} is already in use",
+            tag
+        )));
+    }
+
+    let strategy = req.strategy.unwrap_or_else(|| "random".to_string());
+    let settings = normalize_strategy_settings(&strategy, req.strategy_settings)?;
+    let selector = clean_selector(req.selector);
+    let fallback_tag = non_empty(req.fallback_tag);
+    validate_targets(conn, &selector, fallback_tag.as_deref()).await?;
+    ensure_observatory_for(conn, &strategy).await?;
+
+    let now = chrono::Local::now().naive_local();
+
+    let balancer = sqlx::query_as::<_, Balancer>(
+        r#"
+        INSERT INTO balancers (tag, remark, selector, strategy, strategy_settings, fallback_tag, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(tag)
+    .bind(req.remark.unwrap_or_default())
+    .bind(serde_json::to_string(&selector).unwrap_or_default())
+    .bind(strategy)
+    .bind(settings.map(|v| v.to_string()))
+    .bind(fallback_tag)
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(balancer)
+}
+
+pub async fn update_balancer(
+    conn: &mut SqliteConnection,
+    req: UpdateBalancerRequest,
+) -> ApiResult<Balancer> {
+    let current = get_balancer(conn, req.id).await?;
+
+    let tag = req
+        .tag
+        .map(|t| t.trim().to_string())
+        .unwrap_or_else(|| current.tag.clone());
+    if tag != current.tag {
+        validate_tag(&tag)?;
+        if find_by_tag(conn, &tag).await?.is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "Balancer tag {} is already in use",
+                tag
+            )));
+        }
+        ensure_not_referenced(conn, &current.tag).await?;
+    }
+
+    let strategy = req.strategy.unwrap_or_else(|| current.strategy.clone());
+    let settings = match req.strategy_settings {
+        Some(settings) => Some(settings),
+        None if strategy == current.strategy => current
+            .strategy_settings
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok()),
+        None => None,
+    };
+    let settings = normalize_strategy_settings(&strategy, settings)?;
+
+    let selector = match req.selector {
+        Some(selector) => clean_selector(selector),
+        None => serde_json::from_str(&current.selector).unwrap_or_default(),
+    };
+    let fallback_tag = match req.fallback_tag {
+        Some(f) => non_empty(Some(f)),
+        None => current.fallback_tag.clone(),
+    };
+    validate_targets(conn, &selector, fallback_tag.as_deref()).await?;
+    ensure_observatory_for(conn, &strategy).await?;
+
+    let now = chrono::Local::now().naive_local();
+
+    let balancer = sqlx::query_as::<_, Balancer>(
+        r#"
+        UPDATE balancers
+        SET
+            tag = ?,
+            remark = COALESCE(?, remark),
+            selector = ?,
+            strategy = ?,
+            strategy_settings = ?,
+            fallback_tag = ?,
+            updated_at = ?
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(tag)
+    .bind(req.remark)
+    .bind(serde_json::to_string(&selector).unwrap_or_default())
+    .bind(strategy)
+    .bind(settings.map(|v| v.to_string()))
+    .bind(fallback_tag)
+    .bind(now)
+    .bind(req.id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(balancer)
+}
+
+pub async fn delete_balancer(conn: &mut SqliteConnection, id: i64) -> ApiResult<()> {
+    let balancer = get_balancer(conn, id).await?;
+    ensure_not_referenced(conn, &balancer.tag).await?;
+
+    sqlx::query("DELETE FROM balancers WHERE id = ?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn balancer_exists(conn: &mut SqliteConnection, tag: &str) -> ApiResult<bool> {
+    Ok(find_by_tag(conn, tag).await?.is_some())
+}
+
+pub async fn get_observatory_settings(
+    conn: &mut SqliteConnection,
+) -> ApiResult<ObservatorySettings> {
+    let settings = xray_settings_service::get_setting(conn, OBSERVATORY_KEY)
+        .await?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+pub async fn update_observatory_settings(
+    conn: &mut SqliteConnection,
+    mut settings: ObservatorySettings,
+) -> ApiResult<ObservatorySettings> {
+    settings.subject_selector = clean_selector(settings.subject_selector);
+    settings.probe_url = non_empty(settings.probe_url);
+    settings.probe_interval = non_empty(settings.probe_interval);
+
+    match settings.mode {
+        ObservatoryMode::None => {
+            let dependent: Vec<String> = get_all_balancers(conn)
+                .await?
+                .into_iter()
+                .filter(|b| b.needs_observatory())
+                .map(|b| b.tag)
+                .collect();
+            if !dependent.is_empty() {
+                return Err(ApiError::BadRequest(format!(
+                    "Observatory is required by balancers: {}",
+                    dependent.join(", ")
+                )));
+            }
+        }
+        ObservatoryMode::Observatory | ObservatoryMode::BurstObservatory => {
+            validate_observatory(&settings)?;
+            validate_targets(conn, &settings.subject_selector, None).await?;
+        }
+    }
+
+    let value = serde_json::to_string(&settings)
+        .map_err(|e| ApiError::InternalError(format!("Failed to serialize settings: {}", e)))?;
+    xray_settings_service::set_setting(conn, OBSERVATORY_KEY, &value).await?;
+    Ok(settings)
+}
+
+/// Fetches the latest probe results from the running Xray's ObservatoryService.
+pub async fn get_observatory_status(conn: &mut SqliteConnection) -> ApiResult<Vec<OutboundProbe>> {
+    if get_observatory_settings(conn).await?.mode == ObservatoryMode::None {
+        return Err(ApiError::BadRequest(
+            "Observatory is not enabled".to_string(),
+        ));
+    }
+
+    let mut client = XrayApiClient::connect_local().await?;
+    let status = client.get_outbound_status().await?;
+
+    let ns_to_ms = |ns: i64| ns / 1_000_000;
+    let probes = status
+        .into_iter()
+        .map(|s| OutboundProbe {
+            outbound_tag: s.outbound_tag,
+            alive: s.alive,
+            delay: s.delay,
+            last_error_reason: s.last_error_reason,
+            last_seen_time: s.last_seen_time,
+            last_try_time: s.last_try_time,
+            health_ping: s.health_ping.map(|h| HealthPing {
+                all: h.all,
+                fail: h.fail,
+                deviation: ns_to_ms(h.deviation),
+                average: ns_to_ms(h.average),
+                max: ns_to_ms(h.max),
+                min: ns_to_ms(h.min),
+            }),
+        })
+        .collect();
+    Ok(probes)
+}
+
+async fn get_balancer(conn: &mut SqliteConnection, id: i64) -> ApiResult<Balancer> {
+    sqlx::query_as::<_, Balancer>("SELECT * FROM balancers WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Balancer not found".to_string()))
+}
+
+async fn find_by_tag(conn: &mut SqliteConnection, tag: &str) -> ApiResult<Option<Balancer>> {
+    let balancer = sqlx::query_as::<_, Balancer>("SELECT * FROM balancers WHERE tag = ?")
+        .bind(tag)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(balancer)
+}
+
+/// Rejects removing or renaming a balancer that enabled routing rules send traffic to.
+async fn ensure_not_referenced(conn: &mut SqliteConnection, tag: &str) -> ApiResult<()> {
+    let rules: Vec<(i64,)> =
+        sqlx::query_as("SELECT id FROM routing_rules WHERE balancer_tag = ? AND enable = 1")
+            .bind(tag)
+            .fetch_all(&mut *conn)
+            .await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<String> = rules.into_iter().map(|(id,)| format!("#{}", id)).collect();
+    Err(ApiError::BadRequest(format!(
+        "Balancer {} is used by routing rules: {}",
+        tag,
+        ids.join(", ")
+    )))
+}
+
+/// Tags of the outbounds the generated config contains.
+async fn outbound_tags(conn: &mut SqliteConnection) -> ApiResult<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as("SELECT tag FROM outbounds WHERE enable = 1")
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut tags = vec!["direct".to_string(), "blocked".to_string()];
+    tags.extend(rows.into_iter().map(|(t,)| t));
+    Ok(tags)
+}
+
+/// Checks that every selector prefix matches at least one outbound and that the fallback
+/// outbound exists.
+async fn validate_targets(
+    conn: &mut SqliteConnection,
+    selector: &[String],
+    fallback_tag: Option<&str>,
+) -> ApiResult<()> {
+    if selector.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Selector must list at least one outbound tag prefix".to_string(),
+        ));
+    }
+
+    let tags = outbound_tags(conn).await?;
+    if let Some(prefix) = selector
+        .iter()
+        .find(|p| !tags.iter().any(|t| t.starts_with(p.as_str())))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Selector {} does not match any outbound",
+            prefix
+        )));
+    }
+    if let Some(fallback) = fallback_tag {
+        if !tags.iter().any(|t| t == fallback) {
+            return Err(ApiError::BadRequest(format!(
+                "Fallback outbound {} does not exist or is disabled",
+                fallback
+            )));
+        }
+    }
+    Ok(())
+}
+
+async fn ensure_observatory_for(conn: &mut SqliteConnection, strategy: &str) -> ApiResult<()> {
+    if matches!(strategy, "leastPing" | "leastLoad")
+        && get_observatory_settings(conn).await?.mode == ObservatoryMode::None
+    {
+        return Err(ApiError::BadRequest(format!(
+            "The {} strategy needs the observatory to be enabled first",
+            strategy
+        )));
+    }
+    Ok(())
+}
+
+fn validate_tag(tag: &str) -> ApiResult<()> {
+    let valid_chars = tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if tag.is_empty() || tag.len() > 64 || !valid_chars {
+        return Err(ApiError::BadRequest(
+            "Balancer tag must be 1-64 letters, numbers, dots, underscores or hyphens".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates `strategy` and its settings; only `leastLoad` takes settings, and `{}` means none.
+fn normalize_strategy_settings(
+    strategy: &str,
+    settings: Option<Value>,
+) -> ApiResult<Option<Value>> {
+    if !BALANCER_STRATEGIES.contains(&strategy) {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported balancer strategy: {}",
+            strategy
+        )));
+    }
+
+    let Some(settings) = settings else {
+        return Ok(None);
+    };
+    let Some(obj) = settings.as_object() else {
+        return Err(ApiError::BadRequest(
+            "strategySettings must be a JSON object".to_string(),
+        ));
+    };
+    if obj.is_empty() {
+        return Ok(None);
+    }
+    if strategy != "leastLoad" {
+        return Err(ApiError::BadRequest(format!(
+            "The {} strategy takes no settings",
+            strategy
+        )));
+    }
+
+    for (key, value) in obj {
+        let valid = match key.as_str() {
+            "expected" => value.as_u64().is_some(),
+            "maxRTT" => value.as_str().is_some_and(is_duration),
+            "tolerance" => value.as_f64().is_some_and(|t| (0.0..=1.0).contains(&t)),
+            "baselines" => value
+                .as_array()
+                .is_some_and(|a| a.iter().all(|v| v.as_str().is_some_and(is_duration))),
+            "costs" => value.as_array().is_some_and(|costs| {
+                costs.iter().all(|c| {
+                    c.get("match").and_then(|m| m.as_str()).is_some()
+                        && c.get("value").and_then(|v| v.as_f64()).is_some()
+                        && c.get("regexp").is_none_or(|r| r.is_boolean())
+                })
+            }),
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown leastLoad setting: {}",
+                    key
+                )))
+            }
+        };
+        if !valid {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid leastLoad {}: {}",
+                key, value
+            )));
+        }
+    }
+    Ok(Some(settings))
+}
+
+fn validate_observatory(settings: &ObservatorySettings) -> ApiResult<()> {
+    if let Some(url) = &settings.probe_url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(ApiError::BadRequest(format!(
+                "probeUrl must be an http(s) URL: {}",
+                url
+            )));
+        }
+    }
+    if let Some(interval) = &settings.probe_interval {
+        if !is_duration(interval) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid probeInterval: {}",
+                interval
+            )));
+        }
+    }
+
+    let Some(ping) = &settings.ping_config else {
+        return Ok(());
+    };
+    let Some(obj) = ping.as_object() else {
+        return Err(ApiError::BadRequest(
+            "pingConfig must be a JSON object".to_string(),
+        ));
+    };
+    for (key, value) in obj {
+        let valid = match key.as_str() {
+            "destination" | "connectivity" => value.as_str().is_some(),
+            "interval" | "timeout" => value.as_str().is_some_and(is_duration),
+            "sampling" => value.as_u64().is_some_and(|n| n > 0),
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown pingConfig option: {}",
+                    key
+                )))
+            }
+        };
+        if !valid {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid pingConfig {}: {}",
+                key, value
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Accepts Go duration strings as Xray parses them, e.g. `500ms`, `10s` or `1m30s`.
+fn is_duration(value: &str) -> bool {
+    let mut rest = value;
+    if rest.is_empty() {
+        return false;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        if digits == 0 || rest[..digits].parse::<f64>().is_err() {
+            return false;
+        }
+        rest = &rest[digits..];
+        let Some(unit) = ["ns", "us", "µs", "ms", "s", "m", "h"]
+            .iter()
+            .filter(|u| rest.starts_with(*u))
+            .max_by_key(|u| u.len())
+        else {
+            return false;
+        };
+        rest = &rest[unit.len()..];
+    }
+    true
+}
+
+fn clean_selector(selector: Vec<String>) -> Vec<String> {
+    selector
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_is_duration() {
+        assert!(is_duration("10s"));
+        assert!(is_duration("500ms"));
+        assert!(is_duration("1m30s"));
+        assert!(is_duration("1.5h"));
+        assert!(!is_duration(""));
+        assert!(!is_duration("10"));
+        assert!(!is_duration("s"));
+        assert!(!is_duration("10 s"));
+    }
+
+    #[test]
+    fn test_strategy_settings() {
+        assert_eq!(normalize_strategy_settings("random", None).unwrap(), None);
+        assert!(normalize_strategy_settings("fastest", None).is_err());
+        assert!(normalize_strategy_settings("roundRobin", Some(json!({ "expected": 2 }))).is_err());
+        assert_eq!(
+            normalize_strategy_settings("leastPing", Some(json!({}))).unwrap(),
+            None
+        );
+
+        let least_load = json!({
+            "expected": 2,
+            "maxRTT": "1s",
+            "tolerance": 0.01,
+            "baselines": ["300ms", "500ms"],
+            "costs": [{ "regexp": true, "match": "warp", "value": 0.5 }],
+        });
+        assert!(normalize_strategy_settings("leastLoad", Some(least_load)).is_ok());
+        assert!(normalize_strategy_settings("leastLoad", Some(json!({ "tolerance": 2 }))).is_err());
+        assert!(
+            normalize_strategy_settings("leastLoad", Some(json!({ "maxRTT": "fast" }))).is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_observatory() {
+        let mut settings = ObservatorySettings {
+            mode: ObservatoryMode::Observatory,
+            subject_selector: vec!["warp".to_string()],
+            probe_url: Some("https://www.google.com/generate_204".to_string()),
+            probe_interval: Some("10s".to_string()),
+            ..Default::default()
+        };
+        assert!(validate_observatory(&settings).is_ok());
+
+        settings.probe_url = Some("ftp://example.com".to_string());
+        assert!(validate_observatory(&settings).is_err());
+
+        settings.probe_url = None;
+        settings.ping_config = Some(json!({ "interval": "1m", "sampling": 0 }));
+        assert!(validate_observatory(&settings).is_err());
+        settings.ping_config = Some(json!({ "interval": "1m", "sampling": 3, "timeout": "5s" }));
+        assert!(validate_observatory(&settings).is_ok());
+    }
+}
@@ -0,0 +1,637 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::client::{Client, CreateClientRequest, UpdateClientRequest};
+use crate::models::inbound::Inbound;
+use crate::models::protocol_settings::{shadowsocks_key_len, users_key, Credential};
+use crate::services::{policy_service, xray_settings_service};
+use crate::utils::share_link::ProxyNode;
+use crate::utils::validation;
+use base64::Engine;
+use rand_core::RngCore;
+use sqlx::SqliteConnection;
+
+/// `xray_settings` key set once the clients embedded in inbound settings have been imported.
+const LEGACY_IMPORT_KEY: &str = "legacy_clients_imported";
+
+/// Protocols whose user list (`settings.clients`, or `settings.accounts` for SOCKS and HTTP)
+/// is managed through the `clients` table.
+pub fn supports_clients(protocol: &str) -> bool {
+    Credential::for_protocol(protocol, None).is_some()
+}
+
+/// The credential clients of `inbound` authenticate with, taking the Shadowsocks cipher
+/// from its settings.
+pub fn credential(inbound: &Inbound) -> Option<Credential> {
+    let settings: Option<serde_json::Value> = inbound
+        .settings
+        .as_deref()
+        .and_then(|s| serde_json::from_str(s).ok());
+    let method = settings
+        .as_ref()
+        .and_then(|s| s.get("method"))
+        .and_then(|m| m.as_str());
+    Credential::for_protocol(&inbound.protocol, method)
+}
+
+/// A new random password for `credential`: 32 hex characters, or a base64 key of the
+/// required length for Shadowsocks 2022. UUID credentials have no password.
+pub fn generate_password(credential: Credential) -> String {
+    match credential {
+        Credential::Uuid => String::new(),
+        Credential::Password => uuid::Uuid::new_v4().simple().to_string(),
+        Credential::Key(len) => {
+            let mut key = vec![0u8; len];
+            rand_core::OsRng.fill_bytes(&mut key);
+            base64::engine::general_purpose::STANDARD.encode(key)
+        }
+    }
+}
+
+/// Checks a client supplied password against `credential` and returns it trimmed.
+pub fn validate_password(credential: Credential, password: &str) -> ApiResult<String> {
+    let password = password.trim();
+    match credential {
+        Credential::Uuid => {
+            if !password.is_empty() {
+                return Err(ApiError::BadRequest(
+                    "Clients of this protocol authenticate by UUID, not password".to_string(),
+                ));
+            }
+        }
+        Credential::Password => {
+            if password.is_empty() || password.len() > 128 {
+                return Err(ApiError::BadRequest(
+                    "Client password must be between 1 and 128 characters".to_string(),
+                ));
+            }
+            if password
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control())
+            {
+                return Err(ApiError::BadRequest(
+                    "Client password cannot contain whitespace".to_string(),
+                ));
+            }
+        }
+        Credential::Key(len) => {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(password);
+            if decoded.map(|k| k.len()) != Ok(len) {
+                return Err(ApiError::BadRequest(format!(
+                    "Client key must be a base64 encoded {}-byte key",
+                    len
+                )));
+            }
+        }
+    }
+    Ok(password.to_string())
+}
+
+pub async fn get_all_clients(conn: &mut SqliteConnection) -> ApiResult<Vec<Client>> {
+    let clients = sqlx::query_as::<_, Client>("SELECT * FROM clients ORDER BY id ASC")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(clients)
+}
+
+pub async fn get_clients(conn: &mut SqliteConnection, inbound_id: &str) -> ApiResult<Vec<Client>> {
+    let clients =
+        sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE inbound_id = ? ORDER BY id ASC")
+            .bind(inbound_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(clients)
+}
+
+pub async fn add_client(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+    req: CreateClientRequest,
+) -> ApiResult<Client> {
+    let inbound = get_client_inbound(conn, inbound_id).await?;
+
+    let email = req.email.trim().to_string();
+    validation::validate_client_email(&email)?;
+    ensure_email_available(conn, &email, None).await?;
+
+    let credential = credential(&inbound).unwrap_or(Credential::Uuid);
+    let uuid = match req.uuid {
+        Some(u) => validate_uuid(&u)?,
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let password = match req.password {
+        Some(p) => validate_password(credential, &p)?,
+        None => generate_password(credential),
+    };
+    let flow = req.flow.unwrap_or_default();
+    validate_flow(&inbound.protocol, &flow)?;
+    let level = req.level.unwrap_or(0);
+    policy_service::ensure_level_exists(conn, level).await?;
+    let sub_id = match req.sub_id {
+        Some(s) => validate_sub_id(&s)?,
+        None => generate_sub_id(),
+    };
+
+    let now = chrono::Local::now().naive_local();
+
+    let client = sqlx::query_as::<_, Client>(
+        r#"
+        INSERT INTO clients (inbound_id, email, uuid, password, flow, level, sub_id, enable, total, expiry, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(&inbound.id)
+    .bind(email)
+    .bind(uuid)
+    .bind(password)
+    .bind(flow)
+    .bind(level)
+    .bind(sub_id)
+    .bind(req.enable.unwrap_or(true))
+    .bind(req.total.unwrap_or(0))
+    .bind(req.expiry.unwrap_or(0))
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(client)
+}
+
+pub async fn update_client(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+    client_id: i64,
+    req: UpdateClientRequest,
+) -> ApiResult<Client> {
+    let inbound = get_client_inbound(conn, inbound_id).await?;
+    get_client(conn, inbound_id, client_id).await?;
+
+    let email = req.email.map(|e| e.trim().to_string());
+    if let Some(ref e) = email {
+        validation::validate_client_email(e)?;
+        ensure_email_available(conn, e, Some(client_id)).await?;
+    }
+    let uuid = req.uuid.map(|u| validate_uuid(&u)).transpose()?;
+    let password = match (req.password, credential(&inbound)) {
+        (Some(p), Some(credential)) => Some(validate_password(credential, &p)?),
+        _ => None,
+    };
+    if let Some(ref f) = req.flow {
+        validate_flow(&inbound.protocol, f)?;
+    }
+    if let Some(level) = req.level {
+        policy_service::ensure_level_exists(conn, level).await?;
+    }
+    let sub_id = req.sub_id.map(|s| validate_sub_id(&s)).transpose()?;
+
+    let now = chrono::Local::now().naive_local();
+
+    let client = sqlx::query_as::<_, Client>(
+        r#"
+        UPDATE clients
+        SET
+            email = COALESCE(?, email),
+            uuid = COALESCE(?, uuid),
+            password = COALESCE(?, password),
+            flow = COALESCE(?, flow),
+            level = COALESCE(?, level),
+            sub_id = COALESCE(?, sub_id),
+            enable = COALESCE(?, enable),
+            disabled_reason = CASE WHEN ? IS NULL THEN disabled_reason ELSE NULL END,
+            total = COALESCE(?, total),
+            expiry = COALESCE(?, expiry),
+            updated_at = ?
+        WHERE id = ? AND inbound_id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(email)
+    .bind(uuid)
+    .bind(password)
+    .bind(req.flow)
+    .bind(req.level)
+    .bind(sub_id)
+    .bind(req.enable)
+    .bind(req.enable)
+    .bind(req.total)
+    .bind(req.expiry)
+    .bind(now)
+    .bind(client_id)
+    .bind(inbound_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(client)
+}
+
+pub async fn get_clients_by_sub_id(
+    conn: &mut SqliteConnection,
+    sub_id: &str,
+) -> ApiResult<Vec<Client>> {
+    let clients =
+        sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE sub_id = ? ORDER BY id ASC")
+            .bind(sub_id)
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(clients)
+}
+
+/// Resolves the connection details of one client, as written into its share link.
+pub async fn get_share_node(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+    client_id: i64,
+    address: &str,
+) -> ApiResult<ProxyNode> {
+    let inbound = get_client_inbound(conn, inbound_id).await?;
+    let client = get_client(conn, inbound_id, client_id).await?;
+
+    ProxyNode::from_inbound(&inbound, &client, address).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "Share links are not supported for protocol {}",
+            inbound.protocol
+        ))
+    })
+}
+
+pub async fn delete_client(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+    client_id: i64,
+) -> ApiResult<()> {
+    let result = sqlx::query("DELETE FROM clients WHERE id = ? AND inbound_id = ?")
+        .bind(client_id)
+        .bind(inbound_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::BadRequest("Client not found".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn delete_inbound_clients(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+) -> ApiResult<()> {
+    sqlx::query("DELETE FROM clients WHERE inbound_id = ?")
+        .bind(inbound_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// The settings entry of `client` on an inbound of `protocol`. SOCKS and HTTP accounts carry
+/// no email, so Xray keeps no per-user traffic stats for them.
+pub fn settings_entry(protocol: &str, method: Option<&str>, client: &Client) -> serde_json::Value {
+    match protocol {
+        "socks" | "http" => {
+            return serde_json::json!({ "user": client.email, "pass": client.password });
+        }
+        "trojan" | "shadowsocks" => {
+            let mut entry = serde_json::json!({
+                "password": client.password,
+                "email": client.email,
+                "level": client.level,
+            });
+            if let Some(method) = method.filter(|m| shadowsocks_key_len(m).is_none()) {
+                entry["method"] = serde_json::Value::String(method.to_string());
+            }
+            return entry;
+        }
+        _ => {}
+    }
+
+    let mut entry = serde_json::json!({
+        "id": client.uuid,
+        "email": client.email,
+        "level": client.level,
+    });
+    if protocol == "vless" && !client.flow.is_empty() {
+        entry["flow"] = serde_json::Value::String(client.flow.clone());
+    }
+    entry
+}
+
+/// Removes the user list from an inbound's `settings` and returns it. The `clients` table is
+/// the only store of users, so the stored settings keep just the protocol options.
+pub fn take_blob_clients(
+    protocol: &str,
+    settings: &mut serde_json::Value,
+) -> Option<Vec<serde_json::Value>> {
+    if !supports_clients(protocol) {
+        return None;
+    }
+    match settings.as_object_mut()?.remove(users_key(protocol))? {
+        serde_json::Value::Array(clients) => Some(clients),
+        _ => Some(Vec::new()),
+    }
+}
+
+/// Imports a user list taken from an inbound's settings JSON into the `clients` table.
+///
+/// Clients are matched by credential (UUID or password) first, then by email, so re-saving an
+/// inbound updates the existing rows instead of creating duplicates. With `remove_missing` the
+/// list is treated as complete and clients it does not mention are deleted.
+pub async fn sync_from_settings(
+    conn: &mut SqliteConnection,
+    inbound: &Inbound,
+    blob_clients: &[serde_json::Value],
+    remove_missing: bool,
+) -> ApiResult<()> {
+    let Some(credential) = credential(inbound) else {
+        return Ok(());
+    };
+
+    let existing = get_clients(conn, &inbound.id).await?;
+    let mut kept = Vec::new();
+    let now = chrono::Local::now().naive_local();
+
+    for blob in blob_clients {
+        let Some(imported) = ImportedClient::from_blob(credential, blob) else {
+            continue;
+        };
+
+        let matched = existing
+            .iter()
+            .find(|c| match &imported.uuid {
+                Some(uuid) => c.uuid == *uuid,
+                None => c.password == imported.password,
+            })
+            .or_else(|| {
+                existing
+                    .iter()
+                    .find(|c| Some(&c.email) == imported.email.as_ref())
+            });
+
+        if let Some(client) = matched {
+            sqlx::query(
+                "UPDATE clients SET uuid = COALESCE(?, uuid), password = ?, flow = ?, updated_at = ? WHERE id = ?",
+            )
+            .bind(&imported.uuid)
+            .bind(&imported.password)
+            .bind(&imported.flow)
+            .bind(now)
+            .bind(client.id)
+            .execute(&mut *conn)
+            .await?;
+            kept.push(client.id);
+            continue;
+        }
+
+        let uuid = imported
+            .uuid
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let email = unique_email(conn, imported.email, &uuid).await?;
+        let (id,): (i64,) = sqlx::query_as(
+            "INSERT INTO clients (inbound_id, email, uuid, password, flow, sub_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+        )
+        .bind(&inbound.id)
+        .bind(&email)
+        .bind(&uuid)
+        .bind(&imported.password)
+        .bind(&imported.flow)
+        .bind(generate_sub_id())
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *conn)
+        .await?;
+        kept.push(id);
+
+        tracing::info!(
+            "Imported client {} into inbound {} from settings",
+            email,
+            inbound.remark
+        );
+    }
+
+    if remove_missing {
+        for client in existing.iter().filter(|c| !kept.contains(&c.id)) {
+            sqlx::query("DELETE FROM clients WHERE id = ?")
+                .bind(client.id)
+                .execute(&mut *conn)
+                .await?;
+            tracing::info!(
+                "Removed client {} from inbound {}: not in the saved settings",
+                client.email,
+                inbound.remark
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// A client entry read from an inbound's settings JSON.
+struct ImportedClient {
+    /// Set for UUID protocols only.
+    uuid: Option<String>,
+    password: String,
+    email: Option<String>,
+    flow: String,
+}
+
+impl ImportedClient {
+    /// Reads `{id, email, flow}` for UUID protocols, `{password, email}` for Trojan and
+    /// Shadowsocks and `{user, pass}` for SOCKS and HTTP. Entries with a missing or invalid
+    /// credential are skipped.
+    fn from_blob(credential: Credential, blob: &serde_json::Value) -> Option<Self> {
+        let str_field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| blob.get(*k).and_then(|v| v.as_str()))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let email = str_field(&["email", "user"]);
+        if credential == Credential::Uuid {
+            let uuid = uuid::Uuid::parse_str(&str_field(&["id"])?).ok()?;
+            return Some(ImportedClient {
+                uuid: Some(uuid.to_string()),
+                password: String::new(),
+                email,
+                flow: str_field(&["flow"]).unwrap_or_default(),
+            });
+        }
+
+        let password = validate_password(credential, &str_field(&["password", "pass"])?).ok()?;
+        Some(ImportedClient {
+            uuid: None,
+            password,
+            email,
+            flow: String::new(),
+        })
+    }
+}
+
+/// Gives every client of `inbound` a password that fits its protocol, for instance after the
+/// inbound switched from VLESS to Trojan or to a Shadowsocks 2022 cipher with another key size.
+pub async fn ensure_credentials(conn: &mut SqliteConnection, inbound: &Inbound) -> ApiResult<()> {
+    let Some(credential) = credential(inbound) else {
+        return Ok(());
+    };
+    if credential == Credential::Uuid {
+        return Ok(());
+    }
+
+    for client in get_clients(conn, &inbound.id).await? {
+        if validate_password(credential, &client.password).is_ok() {
+            continue;
+        }
+        sqlx::query("UPDATE clients SET password = ?, flow = '' WHERE id = ?")
+            .bind(generate_password(credential))
+            .bind(client.id)
+            .execute(&mut *conn)
+            .await?;
+        tracing::info!(
+            "Generated a new {} credential for client {}",
+            inbound.protocol,
+            client.email
+        );
+    }
+
+    Ok(())
+}
+
+/// Moves the clients embedded in the settings of inbounds created before the `clients` table
+/// existed into the table. Runs once: a flag in `xray_settings` records that it is done, and
+/// the settings are stripped so later saves cannot bring deleted clients back.
+pub async fn import_legacy_clients(conn: &mut SqliteConnection) -> ApiResult<()> {
+    if xray_settings_service::get_setting(conn, LEGACY_IMPORT_KEY)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for inbound in inbounds {
+        let mut settings: serde_json::Value = match inbound
+            .settings
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+        {
+            Some(v) => v,
+            None => continue,
+        };
+        let Some(blob_clients) = take_blob_clients(&inbound.protocol, &mut settings) else {
+            continue;
+        };
+
+        // Inbounds that already have rows were imported by an earlier version; their blob
+        // may still list clients deleted since, so it is dropped rather than imported.
+        if get_clients(conn, &inbound.id).await?.is_empty() {
+            sync_from_settings(conn, &inbound, &blob_clients, false).await?;
+        }
+        sqlx::query("UPDATE inbounds SET settings = ? WHERE id = ?")
+            .bind(settings.to_string())
+            .bind(&inbound.id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    xray_settings_service::set_setting(conn, LEGACY_IMPORT_KEY, "1").await
+}
+
+async fn get_client_inbound(conn: &mut SqliteConnection, inbound_id: &str) -> ApiResult<Inbound> {
+    let inbound = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE id = ?")
+        .bind(inbound_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Inbound not found".to_string()))?;
+
+    if !supports_clients(&inbound.protocol) {
+        return Err(ApiError::BadRequest(format!(
+            "Protocol {} does not support clients",
+            inbound.protocol
+        )));
+    }
+
+    Ok(inbound)
+}
+
+pub async fn get_client(
+    conn: &mut SqliteConnection,
+    inbound_id: &str,
+    client_id: i64,
+) -> ApiResult<Client> {
+    sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = ? AND inbound_id = ?")
+        .bind(client_id)
+        .bind(inbound_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Client not found".to_string()))
+}
+
+async fn ensure_email_available(
+    conn: &mut SqliteConnection,
+    email: &str,
+    exclude_id: Option<i64>,
+) -> ApiResult<()> {
+    let taken: Option<(i64,)> = sqlx::query_as("SELECT id FROM clients WHERE email = ? AND id != ?")
+        .bind(email)
+        .bind(exclude_id.unwrap_or(0))
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    if taken.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "Client email {} is already in use",
+            email
+        )));
+    }
+    Ok(())
+}
+
+async fn unique_email(
+    conn: &mut SqliteConnection,
+    email: Option<String>,
+    uuid: &str,
+) -> ApiResult<String> {
+    let fallback = format!("user-{}", &uuid[..8]);
+    let candidate = email.unwrap_or_else(|| fallback.clone());
+
+    if ensure_email_available(conn, &candidate, None).await.is_ok() {
+        return Ok(candidate);
+    }
+    Ok(format!("{}-{}", candidate, &uuid[..8]))
+}
+
+fn validate_uuid(uuid: &str) -> ApiResult<String> {
+    uuid::Uuid::parse_str(uuid.trim())
+        .map(|u| u.to_string())
+        .map_err(|_| ApiError::BadRequest(format!("Invalid client UUID: {}", uuid)))
+}
+
+fn generate_sub_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+fn validate_sub_id(sub_id: &str) -> ApiResult<String> {
+    let sub_id = sub_id.trim();
+    let valid_chars = sub_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if sub_id.len() < 8 || sub_id.len() > 64 || !valid_chars {
+        return Err(ApiError::BadRequest(
+            "Subscription ID must be 8-64 letters, numbers, underscores or hyphens".to_string(),
+        ));
+    }
+    Ok(sub_id.to_string())
+}
+
+fn validate_flow(protocol: &str, flow: &str) -> ApiResult<()> {
+    match flow {
+        "" => Ok(()),
+        "xtls-rprx-vision" | "xtls-rprx-vision-udp443" if protocol == "vless" => Ok(()),
+        "xtls-rprx-vision" | "xtls-rprx-vision-udp443" => Err(ApiError::BadRequest(format!(
+            "Flow is only supported for VLESS, not {}",
+            protocol
+        ))),
+        _ => Err(ApiError::BadRequest(format!("Unsupported flow: {}", flow))),
+    }
+}
@@ -0,0 +1,128 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::config_revision::{
+    ConfigRevision, ConfigRevisionSummary, ConfigTrigger, RevisionDiff,
+};
+use crate::services::system_service::SharedMonitor;
+use crate::services::xray_service;
+use crate::utils::json_diff;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+/// Older revisions beyond this count are pruned.
+const MAX_REVISIONS: i64 = 200;
+
+/// Stores `config` as a new revision unless it is identical to the latest one, which happens
+/// whenever an apply does not change the generated config.
+pub async fn record_revision(
+    pool: &SqlitePool,
+    config: &Value,
+    trigger: &ConfigTrigger,
+) -> ApiResult<()> {
+    let latest: Option<(String,)> =
+        sqlx::query_as("SELECT config FROM config_revisions ORDER BY id DESC LIMIT 1")
+            .fetch_optional(pool)
+            .await?;
+    if let Some((latest,)) = latest {
+        if serde_json::from_str::<Value>(&latest).ok().as_ref() == Some(config) {
+            return Ok(());
+        }
+    }
+
+    let now = chrono::Local::now().naive_local();
+    sqlx::query(
+        "INSERT INTO config_revisions (config, username, reason, created_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(config.to_string())
+    .bind(&trigger.username)
+    .bind(&trigger.reason)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "DELETE FROM config_revisions WHERE id NOT IN (SELECT id FROM config_revisions ORDER BY id DESC LIMIT ?)",
+    )
+    .bind(MAX_REVISIONS)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_revisions(pool: &SqlitePool) -> ApiResult<Vec<ConfigRevisionSummary>> {
+    let revisions = sqlx::query_as::<_, ConfigRevisionSummary>(
+        "SELECT id, username, reason, created_at FROM config_revisions ORDER BY id DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(revisions)
+}
+
+#[derive(sqlx::FromRow)]
+struct RevisionRow {
+    #[sqlx(flatten)]
+    summary: ConfigRevisionSummary,
+    config: String,
+}
+
+pub async fn get_revision(pool: &SqlitePool, id: i64) -> ApiResult<ConfigRevision> {
+    let row = sqlx::query_as::<_, RevisionRow>(
+        "SELECT id, username, reason, created_at, config FROM config_revisions WHERE id = ?",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::BadRequest(format!("Config revision {} not found", id)))?;
+
+    let config = serde_json::from_str(&row.config).map_err(|e| {
+        ApiError::InternalError(format!("Config revision {} is corrupt: {}", id, e))
+    })?;
+
+    Ok(ConfigRevision {
+        summary: row.summary,
+        config,
+    })
+}
+
+/// Diffs revision `id` against `base`, or against the revision before it when `base` is unset.
+pub async fn diff_revisions(
+    pool: &SqlitePool,
+    id: i64,
+    base: Option<i64>,
+) -> ApiResult<RevisionDiff> {
+    let revision = get_revision(pool, id).await?;
+
+    let base = match base {
+        Some(base) => Some(base),
+        None => sqlx::query_as::<_, (i64,)>(
+            "SELECT id FROM config_revisions WHERE id < ? ORDER BY id DESC LIMIT 1",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(|(id,)| id),
+    };
+    let base_config = match base {
+        Some(base) => get_revision(pool, base).await?.config,
+        None => Value::Object(Default::default()),
+    };
+
+    Ok(RevisionDiff {
+        base,
+        revision: id,
+        changes: json_diff::diff(&base_config, &revision.config),
+    })
+}
+
+/// Reinstalls an earlier revision and restarts Xray with it. The database is not touched, so
+/// the next change to inbounds or clients regenerates the config from the current data.
+pub async fn rollback(
+    pool: &SqlitePool,
+    monitor: SharedMonitor,
+    id: i64,
+    username: &str,
+) -> ApiResult<()> {
+    let revision = get_revision(pool, id).await?;
+    let trigger = ConfigTrigger::user(username, format!("Rollback to revision #{}", id));
+    xray_service::install_config(pool, monitor, revision.config, trigger, true).await
+}
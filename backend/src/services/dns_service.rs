@@ -0,0 +1,242 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::xray_config::{DnsConfig, DnsHosts, DnsServer};
+use crate::services::xray_settings_service;
+use sqlx::SqliteConnection;
+use std::net::IpAddr;
+
+const DNS_KEY: &str = "dns";
+const QUERY_STRATEGIES: &[&str] = &["UseIP", "UseIPv4", "UseIPv6", "UseSystem"];
+const SERVER_SCHEMES: &[&str] = &[
+    "https",
+    "https+local",
+    "h2c",
+    "tcp",
+    "tcp+local",
+    "tls",
+    "quic+local",
+];
+
+/// Returns the stored DNS settings; the default has no servers, which omits the `dns` section.
+pub async fn get_dns_settings(conn: &mut SqliteConnection) -> ApiResult<DnsConfig> {
+    let settings = xray_settings_service::get_setting(conn, DNS_KEY)
+        .await?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    Ok(settings)
+}
+
+pub async fn update_dns_settings(
+    conn: &mut SqliteConnection,
+    settings: DnsConfig,
+) -> ApiResult<DnsConfig> {
+    validate_dns(&settings)?;
+    let value = serde_json::to_string(&settings)
+        .map_err(|e| ApiError::InternalError(format!("Failed to serialize settings: {}", e)))?;
+    xray_settings_service::set_setting(conn, DNS_KEY, &value).await?;
+    Ok(settings)
+}
+
+/// The `dns` section for the generated config, or `None` when nothing is configured.
+pub async fn dns_config(conn: &mut SqliteConnection) -> ApiResult<Option<DnsConfig>> {
+    let settings = get_dns_settings(conn).await?;
+    if settings.servers.is_empty() && settings.hosts.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(settings))
+}
+
+fn validate_dns(dns: &DnsConfig) -> ApiResult<()> {
+    for (domain, hosts) in &dns.hosts {
+        let addresses: Vec<&String> = match hosts {
+            DnsHosts::One(address) => vec![address],
+            DnsHosts::Many(addresses) => addresses.iter().collect(),
+        };
+        if domain.trim().is_empty()
+            || addresses.is_empty()
+            || addresses.iter().any(|a| a.trim().is_empty())
+        {
+            return Err(ApiError::BadRequest(format!(
+                "hosts entry {:?} needs a domain and at least one address",
+                domain
+            )));
+        }
+    }
+
+    for server in &dns.servers {
+        match server {
+            DnsServer::Address(address) => validate_address(address)?,
+            DnsServer::Detailed(server) => {
+                validate_address(&server.address)?;
+                if server.port == Some(0) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid port for DNS server {}",
+                        server.address
+                    )));
+                }
+                if let Some(domain) = server.domains.iter().find(|d| !is_matcher(d)) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid domain matcher {:?} for DNS server {}",
+                        domain, server.address
+                    )));
+                }
+                if let Some(ip) = server.expect_ips.iter().find(|ip| !is_ip_matcher(ip)) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid expectIPs entry {:?} for DNS server {}",
+                        ip, server.address
+                    )));
+                }
+                validate_client_ip(server.client_ip.as_deref())?;
+                validate_query_strategy(server.query_strategy.as_deref())?;
+            }
+        }
+    }
+
+    validate_client_ip(dns.client_ip.as_deref())?;
+    validate_query_strategy(dns.query_strategy.as_deref())?;
+
+    if let Some(tag) = &dns.tag {
+        let valid = !tag.is_empty()
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(ApiError::BadRequest(format!("Invalid DNS tag: {:?}", tag)));
+        }
+    }
+    Ok(())
+}
+
+/// Accepts `localhost`, `fakedns`, a plain IP, or a URL with a DNS scheme Xray understands
+/// (DoH `https://`, DoT `tls://`, `tcp://`, DoQ `quic+local://` and their `+local` forms).
+fn validate_address(address: &str) -> ApiResult<()> {
+    let valid = match address.split_once("://") {
+        Some((scheme, rest)) => SERVER_SCHEMES.contains(&scheme) && !rest.is_empty(),
+        None => matches!(address, "localhost" | "fakedns") || address.parse::<IpAddr>().is_ok(),
+    };
+    if !valid {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid DNS server address: {:?}",
+            address
+        )));
+    }
+    Ok(())
+}
+
+fn validate_client_ip(client_ip: Option<&str>) -> ApiResult<()> {
+    if let Some(ip) = client_ip {
+        if ip.parse::<IpAddr>().is_err() {
+            return Err(ApiError::BadRequest(format!("Invalid clientIp: {:?}", ip)));
+        }
+    }
+    Ok(())
+}
+
+fn validate_query_strategy(strategy: Option<&str>) -> ApiResult<()> {
+    if let Some(strategy) = strategy {
+        if !QUERY_STRATEGIES.contains(&strategy) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid queryStrategy: {} (expected one of {})",
+                strategy,
+                QUERY_STRATEGIES.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Domain matchers as used in routing: plain domains or `domain:`, `full:`, `regexp:`,
+/// `keyword:`, `geosite:`, `ext:` or `dotless:` prefixed values.
+fn is_matcher(value: &str) -> bool {
+    if value.is_empty() || value.chars().any(char::is_whitespace) {
+        return false;
+    }
+    match value.split_once(':') {
+        Some((prefix, rest)) => {
+            matches!(
+                prefix,
+                "domain" | "full" | "regexp" | "keyword" | "geosite" | "ext" | "dotless"
+            ) && (!rest.is_empty() || prefix == "dotless")
+        }
+        None => true,
+    }
+}
+
+/// `geoip:` or `ext:` references, plain IPs and CIDR ranges.
+fn is_ip_matcher(value: &str) -> bool {
+    if let Some(rest) = value
+        .strip_prefix("geoip:")
+        .or_else(|| value.strip_prefix("ext:"))
+    {
+        return !rest.is_empty();
+    }
+    match value.split_once('/') {
+        Some((ip, prefix)) => match (ip.parse::<IpAddr>(), prefix.parse::<u8>()) {
+            (Ok(IpAddr::V4(_)), Ok(len)) => len <= 32,
+            (Ok(IpAddr::V6(_)), Ok(len)) => len <= 128,
+            _ => false,
+        },
+        None => value.parse::<IpAddr>().is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: serde_json::Value) -> DnsConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_validate_dns() {
+        let dns = parse(json!({
+            "hosts": {
+                "domain:example.internal": "10.0.0.5",
+                "geosite:category-ads-all": ["127.0.0.1"],
+            },
+            "servers": [
+                "https://1.1.1.1/dns-query",
+                "tls://dns.google",
+                {
+                    "address": "223.5.5.5",
+                    "port": 53,
+                    "domains": ["geosite:cn"],
+                    "expectIPs": ["geoip:cn", "10.0.0.0/8"],
+                    "skipFallback": true,
+                },
+                "localhost",
+            ],
+            "queryStrategy": "UseIPv4",
+            "disableCache": true,
+        }));
+        assert!(validate_dns(&dns).is_ok());
+
+        assert!(validate_dns(&parse(json!({ "servers": ["ftp://dns.example"] }))).is_err());
+        assert!(validate_dns(&parse(json!({ "servers": ["dns.google"] }))).is_err());
+        assert!(
+            validate_dns(&parse(json!({ "servers": [], "queryStrategy": "UseIPv5" }))).is_err()
+        );
+        assert!(validate_dns(&parse(json!({
+            "servers": [{ "address": "8.8.8.8", "expectIPs": ["10.0.0.0/40"] }],
+        })))
+        .is_err());
+        assert!(validate_dns(&parse(json!({ "hosts": { "example.com": [] } }))).is_err());
+    }
+
+    #[test]
+    fn test_dns_serialization() {
+        let dns = parse(json!({
+            "servers": [{ "address": "8.8.8.8", "expectIPs": ["geoip:us"], "clientIP": "1.2.3.4" }],
+            "clientIp": "5.6.7.8",
+        }));
+        let value = serde_json::to_value(&dns).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "servers": [{ "address": "8.8.8.8", "expectIPs": ["geoip:us"], "clientIP": "1.2.3.4" }],
+                "clientIp": "5.6.7.8",
+            })
+        );
+    }
+}
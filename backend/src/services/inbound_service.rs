@@ -1,21 +1,79 @@
-use crate::errors::ApiResult;
-use crate::models::inbound::{CreateInboundRequest, Inbound, UpdateInboundRequest};
-use sqlx::SqlitePool;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::inbound::{
+    CreateInboundRequest, DisabledReason, Inbound, TrafficReset, UpdateInboundRequest,
+};
+use crate::models::protocol_settings::{
+    shadowsocks_key_len, users_key, Credential, Fallback, InboundSettings, INBOUND_NETWORKS,
+    INBOUND_PROTOCOLS, SHADOWSOCKS_METHODS,
+};
+use crate::models::stream_settings::{
+    Sniffing, StreamSettings, TcpSettings, FINGERPRINTS, KCP_HEADERS, NETWORKS, SECURITIES,
+    SNIFF_PROTOCOLS, XHTTP_MODES,
+};
+use crate::services::client_service;
+use crate::services::port_service::{self, PortRequest, Transports};
+use base64::Engine;
+use serde_json::Value;
+use sqlx::{Connection, SqliteConnection};
 
-pub async fn get_all_inbounds(pool: &SqlitePool) -> ApiResult<Vec<Inbound>> {
-    let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds ORDER BY id DESC")
-        .fetch_all(pool)
+/// Lists the inbounds with the user list from the `clients` table filled into their
+/// `settings`, where the panel reads share link credentials from.
+pub async fn get_all_inbounds(conn: &mut SqliteConnection) -> ApiResult<Vec<Inbound>> {
+    let mut inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds ORDER BY id DESC")
+        .fetch_all(&mut *conn)
         .await?;
+    let clients = client_service::get_all_clients(conn).await?;
+
+    for inbound in inbounds
+        .iter_mut()
+        .filter(|i| client_service::supports_clients(&i.protocol))
+    {
+        let mut settings = inbound
+            .settings
+            .as_deref()
+            .and_then(|s| serde_json::from_str::<Value>(s).ok())
+            .filter(Value::is_object)
+            .unwrap_or_else(|| serde_json::json!({}));
+        let method = settings["method"].as_str().map(str::to_string);
+        let entries = clients
+            .iter()
+            .filter(|c| c.inbound_id == inbound.id)
+            .map(|c| client_service::settings_entry(&inbound.protocol, method.as_deref(), c))
+            .collect();
+        settings[users_key(&inbound.protocol)] = Value::Array(entries);
+        inbound.settings = Some(settings.to_string());
+    }
+
     Ok(inbounds)
 }
 
-pub async fn add_inbound(pool: &SqlitePool, req: CreateInboundRequest) -> ApiResult<Inbound> {
+pub async fn add_inbound(
+    conn: &mut SqliteConnection,
+    req: CreateInboundRequest,
+) -> ApiResult<Inbound> {
     let now = chrono::Local::now().naive_local();
 
-    let settings_json = req
-        .settings
-        .map(|v| v.to_string())
-        .unwrap_or("{}".to_string());
+    let mut settings = req.settings.unwrap_or_else(|| serde_json::json!({}));
+    validate_settings(&req.protocol, &mut settings)?;
+    let blob_clients = client_service::take_blob_clients(&req.protocol, &mut settings);
+    let settings_json = settings.to_string();
+    if let Some(ref stream_settings) = req.stream_settings {
+        validate_stream_settings(stream_settings)?;
+    }
+    if let Some(ref sniffing) = req.sniffing {
+        validate_sniffing(sniffing)?;
+    }
+    let traffic_reset = req.traffic_reset.unwrap_or_else(|| "never".to_string());
+    let traffic_reset_day = req.traffic_reset_day.unwrap_or(1);
+    validate_traffic_reset(&traffic_reset, traffic_reset_day)?;
+    let stream = req.stream_settings.clone().unwrap_or(Value::Null);
+    let port_request = PortRequest {
+        port: req.port,
+        listen: req.listen.as_deref(),
+        transports: Transports::of(&req.protocol, &settings, &stream),
+    };
+    port_service::ensure_port_available(conn, port_request, None, true).await?;
+
     let stream_settings_json = req
         .stream_settings
         .map(|v| v.to_string())
@@ -29,18 +87,15 @@ pub async fn add_inbound(pool: &SqlitePool, req: CreateInboundRequest) -> ApiRes
     let enable = req.enable.unwrap_or(true);
 
     let tag = req.tag.or_else(|| {
-        Some(format!(
-            "inbound-{}",
-            uuid::Uuid::new_v4().to_string()[..8].to_string()
-        ))
+        Some(format!("inbound-{}", &uuid::Uuid::new_v4().to_string()[..8]))
     });
 
     let id = req.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
     let inbound = sqlx::query_as::<_, Inbound>(
         r#"
-        INSERT INTO inbounds (id, remark, protocol, port, enable, tag, listen, allocate, settings, stream_settings, sniffing, total, expiry, created_at, updated_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        INSERT INTO inbounds (id, remark, protocol, port, enable, tag, listen, allocate, settings, stream_settings, sniffing, total, expiry, traffic_reset, traffic_reset_day, last_traffic_reset, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         RETURNING *
         "#
     )
@@ -57,18 +112,102 @@ pub async fn add_inbound(pool: &SqlitePool, req: CreateInboundRequest) -> ApiRes
     .bind(sniffing_json)
     .bind(req.total.unwrap_or(0))
     .bind(req.expiry.unwrap_or(0))
+    .bind(traffic_reset)
+    .bind(traffic_reset_day)
+    .bind(chrono::Utc::now().timestamp_millis())
     .bind(now)
     .bind(now)
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
+    if let Some(clients) = blob_clients {
+        client_service::sync_from_settings(conn, &inbound, &clients, false).await?;
+    }
+
     Ok(inbound)
 }
 
-pub async fn update_inbound(pool: &SqlitePool, req: UpdateInboundRequest) -> ApiResult<Inbound> {
+pub async fn get_inbound(conn: &mut SqliteConnection, id: &str) -> ApiResult<Inbound> {
+    sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Inbound not found".to_string()))
+}
+
+pub async fn update_inbound(
+    conn: &mut SqliteConnection,
+    req: UpdateInboundRequest,
+) -> ApiResult<Inbound> {
     let now = chrono::Local::now().naive_local();
 
-    let settings_str = req.settings.map(|v| v.to_string());
+    let current = get_inbound(conn, &req.id).await?;
+
+    if let Some(ref stream_settings) = req.stream_settings {
+        validate_stream_settings(stream_settings)?;
+    }
+    if let Some(ref sniffing) = req.sniffing {
+        validate_sniffing(sniffing)?;
+    }
+
+    // A changed schedule counts from now, so switching to a monthly reset mid-month does
+    // not reset the counters straight away.
+    let traffic_reset = req
+        .traffic_reset
+        .as_deref()
+        .unwrap_or(&current.traffic_reset);
+    let traffic_reset_day = req.traffic_reset_day.unwrap_or(current.traffic_reset_day);
+    let schedule_changed =
+        traffic_reset != current.traffic_reset || traffic_reset_day != current.traffic_reset_day;
+    if schedule_changed {
+        validate_traffic_reset(traffic_reset, traffic_reset_day)?;
+    }
+    let last_traffic_reset = schedule_changed.then(|| chrono::Utc::now().timestamp_millis());
+
+    let settings_changed = req.settings.is_some();
+    let protocol_changed = req
+        .protocol
+        .as_ref()
+        .is_some_and(|p| *p != current.protocol);
+    let mut blob_clients = None;
+    let settings_str = if settings_changed || protocol_changed {
+        let protocol = req.protocol.as_deref().unwrap_or(&current.protocol);
+        let mut settings = match req.settings {
+            Some(settings) => settings,
+            None => current
+                .settings
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or_else(|| serde_json::json!({})),
+        };
+        validate_settings(protocol, &mut settings)?;
+        blob_clients = client_service::take_blob_clients(protocol, &mut settings);
+        Some(settings.to_string())
+    } else {
+        None
+    };
+
+    let port = req.port.unwrap_or(current.port);
+    let listen = req.listen.as_deref().or(current.listen.as_deref());
+    let moved = port != current.port || listen != current.listen.as_deref();
+    if moved || settings_str.is_some() || req.stream_settings.is_some() {
+        let parse = |s: Option<&str>| {
+            s.and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or(Value::Null)
+        };
+        let settings = parse(settings_str.as_deref().or(current.settings.as_deref()));
+        let stream = req
+            .stream_settings
+            .clone()
+            .unwrap_or_else(|| parse(current.stream_settings.as_deref()));
+        let protocol = req.protocol.as_deref().unwrap_or(&current.protocol);
+        let port_request = PortRequest {
+            port,
+            listen,
+            transports: Transports::of(protocol, &settings, &stream),
+        };
+        port_service::ensure_port_available(conn, port_request, Some(&current.id), moved).await?;
+    }
     let stream_settings_str = req.stream_settings.map(|v| v.to_string());
     let sniffing_str = req.sniffing.map(|v| v.to_string());
     let allocate_str = req.allocate.map(|v| v.to_string());
@@ -81,6 +220,7 @@ pub async fn update_inbound(pool: &SqlitePool, req: UpdateInboundRequest) -> Api
             protocol = COALESCE(?, protocol),
             port = COALESCE(?, port),
             enable = COALESCE(?, enable),
+            disabled_reason = CASE WHEN ? IS NULL THEN disabled_reason ELSE NULL END,
             tag = COALESCE(?, tag),
             listen = COALESCE(?, listen),
             allocate = COALESCE(?, allocate),
@@ -89,6 +229,9 @@ pub async fn update_inbound(pool: &SqlitePool, req: UpdateInboundRequest) -> Api
             sniffing = COALESCE(?, sniffing),
             total = COALESCE(?, total),
             expiry = COALESCE(?, expiry),
+            traffic_reset = COALESCE(?, traffic_reset),
+            traffic_reset_day = COALESCE(?, traffic_reset_day),
+            last_traffic_reset = COALESCE(?, last_traffic_reset),
             updated_at = ?
         WHERE id = ?
         RETURNING *
@@ -98,6 +241,7 @@ pub async fn update_inbound(pool: &SqlitePool, req: UpdateInboundRequest) -> Api
     .bind(req.protocol)
     .bind(req.port)
     .bind(req.enable)
+    .bind(req.enable)
     .bind(req.tag)
     .bind(req.listen)
     .bind(allocate_str)
@@ -106,26 +250,802 @@ pub async fn update_inbound(pool: &SqlitePool, req: UpdateInboundRequest) -> Api
     .bind(sniffing_str)
     .bind(req.total)
     .bind(req.expiry)
+    .bind(req.traffic_reset)
+    .bind(req.traffic_reset_day)
+    .bind(last_traffic_reset)
     .bind(now)
     .bind(req.id)
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
+    // A user list in the saved settings is the complete list for this inbound.
+    if let Some(clients) = blob_clients {
+        client_service::sync_from_settings(conn, &inbound, &clients, true).await?;
+    }
+    if settings_changed || protocol_changed {
+        client_service::ensure_credentials(conn, &inbound).await?;
+    }
+
     Ok(inbound)
 }
 
-pub async fn delete_inbound(pool: &SqlitePool, id: &str) -> ApiResult<()> {
+pub async fn delete_inbound(conn: &mut SqliteConnection, id: &str) -> ApiResult<()> {
     sqlx::query("DELETE FROM inbounds WHERE id = ?")
         .bind(id)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
+    client_service::delete_inbound_clients(conn, id).await?;
     Ok(())
 }
 
-pub async fn reset_inbound_traffic(pool: &SqlitePool, id: &str) -> ApiResult<()> {
-    sqlx::query("UPDATE inbounds SET up = 0, down = 0 WHERE id = ?")
+/// Zeroes the traffic counters of an inbound and its clients and re-enables whatever was
+/// disabled for exceeding its quota. Returns whether anything was re-enabled, in which case
+/// the config has to be reapplied.
+pub async fn reset_inbound_traffic(conn: &mut SqliteConnection, id: &str) -> ApiResult<bool> {
+    let quota = DisabledReason::QuotaExceeded.as_str();
+    let mut tx = conn.begin().await?;
+
+    let reset =
+        sqlx::query("UPDATE inbounds SET up = 0, down = 0, last_traffic_reset = ? WHERE id = ?")
+            .bind(chrono::Utc::now().timestamp_millis())
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    if reset.rows_affected() == 0 {
+        return Err(ApiError::BadRequest("Inbound not found".to_string()));
+    }
+    sqlx::query("UPDATE clients SET up = 0, down = 0 WHERE inbound_id = ?")
         .bind(id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
+
+    let inbound = sqlx::query(
+        "UPDATE inbounds SET enable = 1, disabled_reason = NULL WHERE id = ? AND disabled_reason = ?",
+    )
+    .bind(id)
+    .bind(quota)
+    .execute(&mut *tx)
+    .await?;
+    let clients = sqlx::query(
+        "UPDATE clients SET enable = 1, disabled_reason = NULL WHERE inbound_id = ? AND disabled_reason = ?",
+    )
+    .bind(id)
+    .bind(quota)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(inbound.rows_affected() + clients.rows_affected() > 0)
+}
+
+fn validate_traffic_reset(schedule: &str, day: i64) -> ApiResult<()> {
+    let Some(schedule) = TrafficReset::parse(schedule) else {
+        let expected: Vec<_> = TrafficReset::ALL.iter().map(|r| r.as_str()).collect();
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported traffic reset schedule: {}. Expected one of: {}",
+            schedule,
+            expected.join(", ")
+        )));
+    };
+    match schedule {
+        TrafficReset::Monthly if !(1..=31).contains(&day) => Err(ApiError::BadRequest(format!(
+            "Monthly traffic reset day must be between 1 and 31, got {}",
+            day
+        ))),
+        TrafficReset::Weekly if !(1..=7).contains(&day) => Err(ApiError::BadRequest(format!(
+            "Weekly traffic reset day must be between 1 (Monday) and 7 (Sunday), got {}",
+            day
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Checks `settings` against the typed model of `protocol`. A Shadowsocks 2022 inbound
+/// without a server PSK gets a freshly generated one.
+pub fn validate_settings(protocol: &str, settings: &mut Value) -> ApiResult<()> {
+    if !INBOUND_PROTOCOLS.contains(&protocol) {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported protocol: {}. Expected one of: {}",
+            protocol,
+            INBOUND_PROTOCOLS.join(", ")
+        )));
+    }
+    if !settings.is_object() {
+        return Err(ApiError::BadRequest(
+            "Inbound settings must be a JSON object".to_string(),
+        ));
+    }
+
+    let typed = InboundSettings::parse(protocol, settings)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid {} settings: {}", protocol, e)))?;
+
+    match typed {
+        InboundSettings::Vless(vless) => {
+            if vless.decryption != "none" {
+                return Err(ApiError::BadRequest(
+                    "VLESS decryption must be \"none\"".to_string(),
+                ));
+            }
+            vless.fallbacks.iter().try_for_each(validate_fallback)?;
+        }
+        InboundSettings::Trojan(trojan) => {
+            trojan.fallbacks.iter().try_for_each(validate_fallback)?;
+        }
+        InboundSettings::Shadowsocks(ss) => {
+            if !SHADOWSOCKS_METHODS.contains(&ss.method.as_str()) {
+                return Err(ApiError::BadRequest(format!(
+                    "Unsupported Shadowsocks method: {}. Expected one of: {}",
+                    ss.method,
+                    SHADOWSOCKS_METHODS.join(", ")
+                )));
+            }
+            validate_network(&ss.network)?;
+            if let Some(len) = shadowsocks_key_len(&ss.method) {
+                let psk = if ss.password.is_empty() {
+                    client_service::generate_password(Credential::Key(len))
+                } else {
+                    client_service::validate_password(Credential::Key(len), &ss.password)
+                        .map_err(|_| {
+                            ApiError::BadRequest(format!(
+                                "Shadowsocks server password must be a base64 encoded {}-byte key for {}",
+                                len, ss.method
+                            ))
+                        })?
+                };
+                settings["password"] = Value::String(psk);
+            }
+        }
+        InboundSettings::Socks(socks) => {
+            if socks.auth != "noauth" && socks.auth != "password" {
+                return Err(ApiError::BadRequest(format!(
+                    "SOCKS auth must be noauth or password, got {}",
+                    socks.auth
+                )));
+            }
+            if let Some(ip) = socks.ip.filter(|ip| !ip.is_empty()) {
+                ip.parse::<std::net::IpAddr>().map_err(|_| {
+                    ApiError::BadRequest(format!("Invalid SOCKS UDP address: {}", ip))
+                })?;
+            }
+        }
+        InboundSettings::Dokodemo(dokodemo) => {
+            validate_network(&dokodemo.network)?;
+            let address = dokodemo.address.unwrap_or_default();
+            let port = dokodemo.port.unwrap_or(0);
+            if !dokodemo.follow_redirect && (address.trim().is_empty() || port == 0) {
+                return Err(ApiError::BadRequest(
+                    "dokodemo-door needs a target address and port unless followRedirect is set"
+                        .to_string(),
+                ));
+            }
+        }
+        InboundSettings::Vmess | InboundSettings::Http => {}
+    }
+
+    Ok(())
+}
+
+/// Checks `streamSettings` field by field and reports every problem in one error, e.g.
+/// `Invalid stream settings: realitySettings.privateKey: ...; wsSettings.path: ...`.
+pub fn validate_stream_settings(value: &Value) -> ApiResult<()> {
+    let stream = StreamSettings::from_value(value).map_err(stream_error)?;
+    let mut errors = Vec::new();
+
+    if !NETWORKS.contains(&stream.network.as_str()) {
+        errors.push(format!(
+            "network: must be one of {}, got {}",
+            NETWORKS.join(", "),
+            stream.network
+        ));
+    }
+    if !SECURITIES.contains(&stream.security.as_str()) {
+        errors.push(format!(
+            "security: must be one of {}, got {}",
+            SECURITIES.join(", "),
+            stream.security
+        ));
+    }
+
+    match stream.security.as_str() {
+        "tls" => match &stream.tls_settings {
+            None => errors.push("tlsSettings: required when security is tls".to_string()),
+            Some(tls) => {
+                if tls.certificates.is_empty() {
+                    errors.push("tlsSettings.certificates: at least one is required".to_string());
+                }
+                for (i, cert) in tls.certificates.iter().enumerate() {
+                    let has = |v: &Option<String>| v.as_deref().is_some_and(|s| !s.is_empty());
+                    let files = has(&cert.certificate_file) && has(&cert.key_file);
+                    let inline = !cert.certificate.is_empty() && !cert.key.is_empty();
+                    if !files && !inline {
+                        errors.push(format!(
+                            "tlsSettings.certificates[{}]: needs certificateFile and keyFile, or certificate and key",
+                            i
+                        ));
+                    }
+                    if let Some(usage) = cert.usage.as_deref() {
+                        if !matches!(usage, "encipherment" | "verify" | "issue") {
+                            errors.push(format!(
+                                "tlsSettings.certificates[{}].usage: must be encipherment, verify or issue",
+                                i
+                            ));
+                        }
+                    }
+                }
+                for alpn in &tls.alpn {
+                    if !matches!(alpn.as_str(), "h2" | "http/1.1" | "h3") {
+                        errors.push(format!("tlsSettings.alpn: unsupported value {}", alpn));
+                    }
+                }
+                let versions = [
+                    ("minVersion", &tls.min_version),
+                    ("maxVersion", &tls.max_version),
+                ];
+                for (field, version) in versions {
+                    if let Some(v) = version.as_deref().filter(|v| !v.is_empty()) {
+                        if !matches!(v, "1.0" | "1.1" | "1.2" | "1.3") {
+                            errors.push(format!(
+                                "tlsSettings.{}: must be 1.0, 1.1, 1.2 or 1.3",
+                                field
+                            ));
+                        }
+                    }
+                }
+                if let (Some(min), Some(max)) = (&tls.min_version, &tls.max_version) {
+                    if !min.is_empty() && !max.is_empty() && min > max {
+                        errors.push(
+                            "tlsSettings.minVersion: must not be above maxVersion".to_string(),
+                        );
+                    }
+                }
+                if tls
+                    .server_name
+                    .as_deref()
+                    .is_some_and(|n| n.contains(char::is_whitespace))
+                {
+                    errors.push("tlsSettings.serverName: must not contain whitespace".to_string());
+                }
+                check_fingerprint("tlsSettings.fingerprint", &tls.fingerprint, &mut errors);
+            }
+        },
+        "reality" => {
+            if !matches!(stream.network.as_str(), "raw" | "tcp" | "xhttp" | "grpc") {
+                errors.push(format!(
+                    "security: reality works with raw, xhttp or grpc, not {}",
+                    stream.network
+                ));
+            }
+            match &stream.reality_settings {
+                None => {
+                    errors.push("realitySettings: required when security is reality".to_string())
+                }
+                Some(reality) => {
+                    match reality.target.as_ref().or(reality.dest.as_ref()) {
+                        Some(dest) if is_dest(dest) => {}
+                        Some(dest) => errors.push(format!(
+                            "realitySettings.dest: must be a port or addr:port, got {}",
+                            dest
+                        )),
+                        None => errors.push("realitySettings.dest: required".to_string()),
+                    }
+                    if reality.xver > 2 {
+                        errors.push("realitySettings.xver: must be 0, 1 or 2".to_string());
+                    }
+                    if reality.server_names.is_empty() {
+                        errors.push(
+                            "realitySettings.serverNames: at least one is required".to_string(),
+                        );
+                    }
+                    if reality
+                        .server_names
+                        .iter()
+                        .any(|n| n.is_empty() || n.contains(char::is_whitespace))
+                    {
+                        errors.push(
+                            "realitySettings.serverNames: names must be non-empty without whitespace"
+                                .to_string(),
+                        );
+                    }
+                    if !is_x25519_key(&reality.private_key) {
+                        errors.push(
+                            "realitySettings.privateKey: must be a base64url X25519 key as printed by xray x25519"
+                                .to_string(),
+                        );
+                    }
+                    if let Some(public_key) = reality.public_key.as_deref() {
+                        if !public_key.is_empty() && !is_x25519_key(public_key) {
+                            errors.push(
+                                "realitySettings.publicKey: must be a base64url X25519 key"
+                                    .to_string(),
+                            );
+                        }
+                    }
+                    if reality.short_ids.is_empty() {
+                        errors.push(
+                            "realitySettings.shortIds: at least one (possibly empty) is required"
+                                .to_string(),
+                        );
+                    }
+                    for short_id in &reality.short_ids {
+                        let hex = short_id.chars().all(|c| c.is_ascii_hexdigit());
+                        if !hex || short_id.len() % 2 != 0 || short_id.len() > 16 {
+                            errors.push(format!(
+                                "realitySettings.shortIds: {:?} must be an even number of hex digits, at most 16",
+                                short_id
+                            ));
+                        }
+                    }
+                    check_fingerprint(
+                        "realitySettings.fingerprint",
+                        &reality.fingerprint,
+                        &mut errors,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+
+    for (field, tcp) in [
+        ("tcpSettings", &stream.tcp_settings),
+        ("rawSettings", &stream.raw_settings),
+    ] {
+        if let Some(TcpSettings {
+            header: Some(header),
+        }) = tcp
+        {
+            match header.header_type.as_str() {
+                "" | "none" => {}
+                "http" => {
+                    let paths = header.request.as_ref().map(|r| r.path.as_slice());
+                    if paths
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|p| !p.starts_with('/'))
+                    {
+                        errors.push(format!(
+                            "{}.header.request.path: paths must start with /",
+                            field
+                        ));
+                    }
+                }
+                other => errors.push(format!(
+                    "{}.header.type: must be none or http, got {}",
+                    field, other
+                )),
+            }
+        }
+    }
+
+    if let Some(ws) = &stream.ws_settings {
+        check_path_host("wsSettings", &ws.path, &ws.host, &mut errors);
+    }
+    if let Some(upgrade) = &stream.httpupgrade_settings {
+        check_path_host(
+            "httpupgradeSettings",
+            &upgrade.path,
+            &upgrade.host,
+            &mut errors,
+        );
+    }
+    if let Some(xhttp) = &stream.xhttp_settings {
+        check_path_host("xhttpSettings", &xhttp.path, &xhttp.host, &mut errors);
+        if let Some(mode) = xhttp.mode.as_deref() {
+            if !XHTTP_MODES.contains(&mode) {
+                errors.push(format!(
+                    "xhttpSettings.mode: must be one of {}, got {}",
+                    XHTTP_MODES.join(", "),
+                    mode
+                ));
+            }
+        }
+    }
+    if let Some(grpc) = &stream.grpc_settings {
+        if grpc.service_name.contains(char::is_whitespace) {
+            errors.push("grpcSettings.serviceName: must not contain whitespace".to_string());
+        }
+    }
+    if let Some(kcp) = &stream.kcp_settings {
+        if kcp.mtu.is_some_and(|mtu| !(576..=1460).contains(&mtu)) {
+            errors.push("kcpSettings.mtu: must be between 576 and 1460".to_string());
+        }
+        if kcp.tti.is_some_and(|tti| !(10..=100).contains(&tti)) {
+            errors.push("kcpSettings.tti: must be between 10 and 100".to_string());
+        }
+        if let Some(header) = &kcp.header {
+            if !header.header_type.is_empty() && !KCP_HEADERS.contains(&header.header_type.as_str())
+            {
+                errors.push(format!(
+                    "kcpSettings.header.type: must be one of {}, got {}",
+                    KCP_HEADERS.join(", "),
+                    header.header_type
+                ));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(stream_error(errors))
+    }
+}
+
+/// Checks the `sniffing` object of an inbound.
+pub fn validate_sniffing(value: &Value) -> ApiResult<()> {
+    let sniffing: Sniffing = serde_json::from_value(value.clone())
+        .map_err(|e| ApiError::BadRequest(format!("Invalid sniffing settings: {}", e)))?;
+
+    let mut errors = Vec::new();
+    if sniffing.enabled && sniffing.dest_override.is_empty() {
+        errors.push("destOverride: required when sniffing is enabled".to_string());
+    }
+    for protocol in &sniffing.dest_override {
+        if !SNIFF_PROTOCOLS.contains(&protocol.as_str()) {
+            errors.push(format!(
+                "destOverride: must be one of {}, got {}",
+                SNIFF_PROTOCOLS.join(", "),
+                protocol
+            ));
+        }
+    }
+    if sniffing
+        .domains_excluded
+        .iter()
+        .any(|d| d.is_empty() || d.contains(char::is_whitespace))
+    {
+        errors.push("domainsExcluded: domains must be non-empty without whitespace".to_string());
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!(
+            "Invalid sniffing settings: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+fn stream_error(errors: Vec<String>) -> ApiError {
+    ApiError::BadRequest(format!("Invalid stream settings: {}", errors.join("; ")))
+}
+
+fn check_path_host(section: &str, path: &str, host: &str, errors: &mut Vec<String>) {
+    if !path.is_empty() && !path.starts_with('/') {
+        errors.push(format!("{}.path: must start with /", section));
+    }
+    if host.contains(char::is_whitespace) {
+        errors.push(format!("{}.host: must not contain whitespace", section));
+    }
+}
+
+fn check_fingerprint(field: &str, fingerprint: &Option<String>, errors: &mut Vec<String>) {
+    if let Some(fp) = fingerprint.as_deref().filter(|fp| !fp.is_empty()) {
+        if !FINGERPRINTS.contains(&fp) {
+            errors.push(format!(
+                "{}: must be one of {}, got {}",
+                field,
+                FINGERPRINTS.join(", "),
+                fp
+            ));
+        }
+    }
+}
+
+/// A 32-byte key in the unpadded base64url form `xray x25519` prints.
+fn is_x25519_key(key: &str) -> bool {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(key.trim_end_matches('='))
+        .is_ok_and(|k| k.len() == 32)
+}
+
+/// A fallback or REALITY destination: a port number, `addr:port` or a unix socket path.
+fn is_dest(dest: &Value) -> bool {
+    match dest {
+        Value::Number(n) => n.as_u64().is_some_and(|p| (1..=65535).contains(&p)),
+        Value::String(dest) => {
+            dest.starts_with('/')
+                || dest.starts_with('@')
+                || dest.parse::<u16>().is_ok_and(|p| p > 0)
+                || dest
+                    .rsplit_once(':')
+                    .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        }
+        _ => false,
+    }
+}
+
+fn validate_network(network: &str) -> ApiResult<()> {
+    if !INBOUND_NETWORKS.contains(&network) {
+        return Err(ApiError::BadRequest(format!(
+            "Network must be one of {}, got {}",
+            INBOUND_NETWORKS.join(", "),
+            network
+        )));
+    }
     Ok(())
 }
+
+fn validate_fallback(fallback: &Fallback) -> ApiResult<()> {
+    if !is_dest(&fallback.dest) {
+        return Err(ApiError::BadRequest(format!(
+            "Fallback dest must be a port, addr:port or unix socket path, got {}",
+            fallback.dest
+        )));
+    }
+    if let Some(alpn) = fallback.alpn.as_deref().filter(|a| !a.is_empty()) {
+        if alpn != "h2" && alpn != "http/1.1" {
+            return Err(ApiError::BadRequest(format!(
+                "Fallback alpn must be h2 or http/1.1, got {}",
+                alpn
+            )));
+        }
+    }
+    if let Some(path) = fallback.path.as_deref().filter(|p| !p.is_empty()) {
+        if !path.starts_with('/') {
+            return Err(ApiError::BadRequest(format!(
+                "Fallback path must start with /, got {}",
+                path
+            )));
+        }
+    }
+    if fallback
+        .name
+        .as_deref()
+        .is_some_and(|n| n.contains(char::is_whitespace))
+    {
+        return Err(ApiError::BadRequest(
+            "Fallback name must be a server name without whitespace".to_string(),
+        ));
+    }
+    if fallback.xver.unwrap_or(0) > 2 {
+        return Err(ApiError::BadRequest(
+            "Fallback xver must be 0, 1 or 2".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_settings_per_protocol() {
+        let mut vless = json!({ "clients": [], "decryption": "none" });
+        assert!(validate_settings("vless", &mut vless).is_ok());
+        assert!(validate_settings("vless", &mut json!({ "decryption": "aes" })).is_err());
+        assert!(validate_settings("hysteria2", &mut json!({})).is_err());
+
+        let mut trojan =
+            json!({ "fallbacks": [{ "dest": 8080 }, { "path": "/ws", "dest": "@ws" }] });
+        assert!(validate_settings("trojan", &mut trojan).is_ok());
+        assert!(
+            validate_settings("trojan", &mut json!({ "fallbacks": [{ "dest": "nope" }] })).is_err()
+        );
+        assert!(validate_settings(
+            "trojan",
+            &mut json!({ "fallbacks": [{ "dest": 80, "xver": 3 }] })
+        )
+        .is_err());
+
+        assert!(
+            validate_settings("socks", &mut json!({ "auth": "password", "udp": true })).is_ok()
+        );
+        assert!(validate_settings("socks", &mut json!({ "auth": "basic" })).is_err());
+
+        let mut dokodemo = json!({ "address": "1.1.1.1", "port": 53, "network": "tcp,udp" });
+        assert!(validate_settings("dokodemo-door", &mut dokodemo).is_ok());
+        assert!(validate_settings("dokodemo-door", &mut json!({ "followRedirect": true })).is_ok());
+        assert!(validate_settings("dokodemo-door", &mut json!({ "address": "1.1.1.1" })).is_err());
+    }
+
+    #[test]
+    fn test_validate_stream_settings() {
+        let key = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode([7u8; 32]);
+        let reality = json!({
+            "network": "tcp",
+            "security": "reality",
+            "realitySettings": {
+                "dest": "www.microsoft.com:443",
+                "serverNames": ["www.microsoft.com"],
+                "privateKey": key,
+                "shortIds": ["", "ab12"],
+                "fingerprint": "chrome"
+            }
+        });
+        assert!(validate_stream_settings(&reality).is_ok());
+        assert!(validate_stream_settings(&json!({})).is_ok());
+
+        let broken = json!({
+            "network": "ws",
+            "security": "reality",
+            "realitySettings": {
+                "serverNames": ["www.microsoft.com"],
+                "privateKey": "short",
+                "shortIds": ["xyz"]
+            },
+            "wsSettings": { "path": "ws" }
+        });
+        let message = validate_stream_settings(&broken).unwrap_err().to_string();
+        for field in [
+            "security: reality works with raw, xhttp or grpc",
+            "realitySettings.dest: required",
+            "realitySettings.privateKey",
+            "realitySettings.shortIds",
+            "wsSettings.path",
+        ] {
+            assert!(
+                message.contains(field),
+                "{} missing from {}",
+                field,
+                message
+            );
+        }
+
+        let tls = json!({
+            "network": "grpc",
+            "security": "tls",
+            "tlsSettings": {
+                "certificates": [{ "certificateFile": "/etc/cert.pem" }],
+                "minVersion": "1.3",
+                "maxVersion": "1.2"
+            }
+        });
+        let message = validate_stream_settings(&tls).unwrap_err().to_string();
+        assert!(message.contains("tlsSettings.certificates[0]"));
+        assert!(message.contains("tlsSettings.minVersion"));
+
+        let kcp = json!({ "network": "kcp", "kcpSettings": { "mtu": "big" } });
+        let message = validate_stream_settings(&kcp).unwrap_err().to_string();
+        assert!(message.contains("kcpSettings: invalid type"));
+        assert!(validate_stream_settings(&json!({ "network": "h2" })).is_err());
+    }
+
+    #[test]
+    fn test_validate_sniffing() {
+        assert!(validate_sniffing(&json!({})).is_ok());
+        assert!(
+            validate_sniffing(&json!({ "enabled": true, "destOverride": ["http", "tls"] })).is_ok()
+        );
+        assert!(validate_sniffing(&json!({ "enabled": true })).is_err());
+        assert!(validate_sniffing(&json!({ "destOverride": ["ftp"] })).is_err());
+    }
+
+    #[test]
+    fn test_validate_shadowsocks_keys() {
+        let mut generated = json!({ "method": "2022-blake3-aes-256-gcm" });
+        validate_settings("shadowsocks", &mut generated).unwrap();
+        let psk = generated["password"].as_str().unwrap();
+        assert!(client_service::validate_password(Credential::Key(32), psk).is_ok());
+
+        let mut short_key = json!({
+            "method": "2022-blake3-aes-256-gcm",
+            "password": "c2VydmVyLXBzay0xMjM0NQ==",
+        });
+        assert!(validate_settings("shadowsocks", &mut short_key).is_err());
+
+        let mut legacy = json!({ "method": "aes-128-gcm", "network": "tcp,udp" });
+        validate_settings("shadowsocks", &mut legacy).unwrap();
+        assert!(legacy.get("password").is_none());
+
+        assert!(validate_settings("shadowsocks", &mut json!({ "method": "rc4-md5" })).is_err());
+        assert!(validate_settings(
+            "shadowsocks",
+            &mut json!({ "method": "2022-blake3-chacha20-poly1305" })
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clients_live_only_in_the_table() {
+        let pool = crate::db::test_pool().await;
+        let mut conn = pool.acquire().await.unwrap();
+        let (alice, bob) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let req = serde_json::from_value(json!({
+            "remark": "main",
+            "protocol": "vless",
+            "port": 443,
+            "listen": "@vless",
+            "settings": {
+                "decryption": "none",
+                "clients": [{ "id": alice, "email": "alice" }, { "id": bob, "email": "bob" }]
+            }
+        }))
+        .unwrap();
+        let inbound = add_inbound(&mut conn, req).await.unwrap();
+        assert!(!inbound.settings.as_deref().unwrap().contains("clients"));
+        let clients = client_service::get_clients(&mut conn, &inbound.id)
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 2);
+
+        // The list response carries the users from the table for the panel's share links.
+        let listed = get_all_inbounds(&mut conn).await.unwrap();
+        let settings: Value = serde_json::from_str(listed[0].settings.as_deref().unwrap()).unwrap();
+        assert_eq!(settings["decryption"], "none");
+        assert_eq!(settings["clients"][0]["id"], alice.to_string());
+        assert_eq!(settings["clients"][1]["email"], "bob");
+
+        // Deleted clients stay deleted across restarts and settings saves without a user list.
+        let bob_id = clients.iter().find(|c| c.email == "bob").unwrap().id;
+        client_service::delete_client(&mut conn, &inbound.id, bob_id)
+            .await
+            .unwrap();
+        client_service::import_legacy_clients(&mut conn)
+            .await
+            .unwrap();
+        let req = serde_json::from_value(json!({
+            "id": inbound.id,
+            "settings": { "decryption": "none" }
+        }))
+        .unwrap();
+        update_inbound(&mut conn, req).await.unwrap();
+        let clients = client_service::get_clients(&mut conn, &inbound.id)
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 1);
+
+        // A saved user list replaces the clients of the inbound.
+        let carol = uuid::Uuid::new_v4();
+        let req = serde_json::from_value(json!({
+            "id": inbound.id,
+            "settings": { "decryption": "none", "clients": [{ "id": carol, "email": "carol" }] }
+        }))
+        .unwrap();
+        let inbound = update_inbound(&mut conn, req).await.unwrap();
+        assert!(!inbound.settings.as_deref().unwrap().contains("clients"));
+        let clients = client_service::get_clients(&mut conn, &inbound.id)
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].uuid, carol.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_import_legacy_clients_runs_once() {
+        let pool = crate::db::test_pool().await;
+        let mut conn = pool.acquire().await.unwrap();
+        let legacy = json!({
+            "decryption": "none",
+            "clients": [{ "id": uuid::Uuid::new_v4(), "email": "alice" }]
+        });
+        sqlx::query("INSERT INTO inbounds (id, remark, protocol, port, settings) VALUES ('in-1', 'r', 'vless', 1, ?)")
+            .bind(legacy.to_string())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        client_service::import_legacy_clients(&mut conn)
+            .await
+            .unwrap();
+        let inbound = get_inbound(&mut conn, "in-1").await.unwrap();
+        assert!(!inbound.settings.as_deref().unwrap().contains("clients"));
+        assert_eq!(
+            client_service::get_clients(&mut conn, "in-1")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        sqlx::query("DELETE FROM clients")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE inbounds SET settings = ?")
+            .bind(legacy.to_string())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        client_service::import_legacy_clients(&mut conn)
+            .await
+            .unwrap();
+        assert!(client_service::get_clients(&mut conn, "in-1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::client::Client;
+use crate::models::inbound::Inbound;
+use crate::services::system_service::{SharedMonitor, SysStats};
+use std::collections::BTreeMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Upper bounds in seconds of the HTTP request latency buckets.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    method: String,
+    path: String,
+    status: u16,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Non-cumulative count per bucket of [`LATENCY_BUCKETS`].
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+static HTTP_LATENCY: LazyLock<Mutex<BTreeMap<RequestKey, Histogram>>> =
+    LazyLock::new(Default::default);
+
+/// Records the latency of one API request. `path` is the route template, e.g.
+/// `/api/inbound/:id/history`, so ids do not create new series.
+pub fn observe_request(method: &str, path: &str, status: u16, elapsed: Duration) {
+    let key = RequestKey {
+        method: method.to_string(),
+        path: path.to_string(),
+        status,
+    };
+    if let Ok(mut latency) = HTTP_LATENCY.lock() {
+        latency
+            .entry(key)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Checks the bearer token of a scrape against `METRICS_TOKEN`. Metrics stay disabled while
+/// the variable is unset, as they expose every inbound and client.
+pub fn authorize(presented: Option<&str>) -> ApiResult<()> {
+    let expected = std::env::var("METRICS_TOKEN").unwrap_or_default();
+    if expected.is_empty() {
+        return Err(ApiError::Unauthorized(
+            "Metrics are disabled, set METRICS_TOKEN to enable them".to_string(),
+        ));
+    }
+    if !presented.is_some_and(|token| token_matches(&expected, token)) {
+        return Err(ApiError::Unauthorized("Invalid metrics token".to_string()));
+    }
+    Ok(())
+}
+
+/// Compares in constant time for tokens of equal length.
+fn token_matches(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub async fn render(pool: &sqlx::SqlitePool, monitor: SharedMonitor) -> ApiResult<String> {
+    let stats = monitor
+        .lock()
+        .map_err(|e| ApiError::SystemError(format!("Monitor lock poisoned: {}", e)))?
+        .get_system_stats()?;
+    let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds ORDER BY remark ASC")
+        .fetch_all(pool)
+        .await?;
+    let clients =
+        sqlx::query_as::<_, Client>("SELECT * FROM clients ORDER BY inbound_id ASC, email ASC")
+            .fetch_all(pool)
+            .await?;
+
+    let mut out = Exposition::default();
+    write_system(&mut out, &stats);
+    write_traffic(&mut out, &inbounds, &clients);
+    if let Ok(latency) = HTTP_LATENCY.lock() {
+        write_latency(&mut out, &latency);
+    }
+    Ok(out.0)
+}
+
+fn write_system(out: &mut Exposition, stats: &SysStats) {
+    out.gauge("xui_cpu_usage_percent", "CPU usage of the host.", stats.cpu);
+
+    let sizes = [
+        ("memory", "Memory", &stats.mem.current, &stats.mem.total),
+        ("swap", "Swap", &stats.swap.current, &stats.swap.total),
+        ("disk", "Disk space", &stats.disk.current, &stats.disk.total),
+    ];
+    for (name, title, used, total) in sizes {
+        out.gauge(
+            &format!("xui_{}_used_bytes", name),
+            &format!("{} in use.", title),
+            used,
+        );
+        out.gauge(
+            &format!("xui_{}_total_bytes", name),
+            &format!("{} available in total.", title),
+            total,
+        );
+    }
+
+    out.family("xui_load_average", "gauge", "System load average.");
+    for (window, load) in ["1m", "5m", "15m"].iter().zip(&stats.load) {
+        out.sample("xui_load_average", &[("window", window)], load);
+    }
+
+    out.family("xui_connections", "gauge", "Open connections of the host.");
+    out.sample("xui_connections", &[("protocol", "tcp")], stats.tcp_count);
+    out.sample("xui_connections", &[("protocol", "udp")], stats.udp_count);
+
+    out.counter(
+        "xui_network_sent_bytes_total",
+        "Bytes sent on all network interfaces.",
+        stats.net_traffic.sent,
+    );
+    out.counter(
+        "xui_network_received_bytes_total",
+        "Bytes received on all network interfaces.",
+        stats.net_traffic.recv,
+    );
+    out.gauge(
+        "xui_panel_uptime_seconds",
+        "Seconds since the panel started.",
+        stats.uptime,
+    );
+
+    let xray = &stats.xray;
+    out.gauge(
+        "xui_xray_up",
+        "Whether the Xray process is running.",
+        u8::from(xray.state == "running"),
+    );
+    out.family("xui_xray_info", "gauge", "Version and state of Xray.");
+    out.sample(
+        "xui_xray_info",
+        &[("version", &xray.version), ("state", &xray.state)],
+        1,
+    );
+    out.gauge(
+        "xui_xray_uptime_seconds",
+        "Seconds the current Xray process has been running.",
+        xray.uptime,
+    );
+    out.counter(
+        "xui_xray_restarts_total",
+        "Automatic restarts of Xray after it crashed.",
+        xray.restart_count,
+    );
+}
+
+fn write_traffic(out: &mut Exposition, inbounds: &[Inbound], clients: &[Client]) {
+    let remarks: BTreeMap<&str, &str> = inbounds
+        .iter()
+        .map(|i| (i.id.as_str(), i.remark.as_str()))
+        .collect();
+
+    out.family(
+        "xui_inbound_traffic_bytes_total",
+        "counter",
+        "Traffic accounted to an inbound since its last reset.",
+    );
+    for inbound in inbounds {
+        for (direction, bytes) in [("up", inbound.up), ("down", inbound.down)] {
+            out.sample(
+                "xui_inbound_traffic_bytes_total",
+                &[
+                    ("inbound_id", &inbound.id),
+                    ("remark", &inbound.remark),
+                    ("protocol", &inbound.protocol),
+                    ("direction", direction),
+                ],
+                bytes,
+            );
+        }
+    }
+    out.family(
+        "xui_inbound_quota_bytes",
+        "gauge",
+        "Traffic quota of an inbound, 0 if unlimited.",
+    );
+    for inbound in inbounds {
+        let labels = [
+            ("inbound_id", inbound.id.as_str()),
+            ("remark", &inbound.remark),
+        ];
+        out.sample("xui_inbound_quota_bytes", &labels, inbound.total);
+    }
+    out.family(
+        "xui_inbound_enabled",
+        "gauge",
+        "Whether an inbound is enabled.",
+    );
+    for inbound in inbounds {
+        let labels = [
+            ("inbound_id", inbound.id.as_str()),
+            ("remark", &inbound.remark),
+        ];
+        out.sample("xui_inbound_enabled", &labels, u8::from(inbound.enable));
+    }
+
+    out.family(
+        "xui_client_traffic_bytes_total",
+        "counter",
+        "Traffic accounted to a client since its last reset.",
+    );
+    for client in clients {
+        let remark = remarks
+            .get(client.inbound_id.as_str())
+            .copied()
+            .unwrap_or("");
+        for (direction, bytes) in [("up", client.up), ("down", client.down)] {
+            out.sample(
+                "xui_client_traffic_bytes_total",
+                &[
+                    ("inbound_id", &client.inbound_id),
+                    ("remark", remark),
+                    ("email", &client.email),
+                    ("direction", direction),
+                ],
+                bytes,
+            );
+        }
+    }
+    out.family(
+        "xui_client_enabled",
+        "gauge",
+        "Whether a client is enabled.",
+    );
+    for client in clients {
+        let labels = [
+            ("inbound_id", client.inbound_id.as_str()),
+            ("email", &client.email),
+        ];
+        out.sample("xui_client_enabled", &labels, u8::from(client.enable));
+    }
+}
+
+fn write_latency(out: &mut Exposition, latency: &BTreeMap<RequestKey, Histogram>) {
+    let name = "xui_http_request_duration_seconds";
+    out.family(name, "histogram", "Latency of API requests.");
+    for (key, histogram) in latency {
+        let status = key.status.to_string();
+        let labels = [
+            ("method", key.method.as_str()),
+            ("path", &key.path),
+            ("status", &status),
+        ];
+
+        let mut cumulative = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let le = le.to_string();
+            out.sample(
+                &format!("{}_bucket", name),
+                &[&labels[..], &[("le", &le)]].concat(),
+                cumulative,
+            );
+        }
+        out.sample(
+            &format!("{}_bucket", name),
+            &[&labels[..], &[("le", "+Inf")]].concat(),
+            histogram.count,
+        );
+        out.sample(&format!("{}_sum", name), &labels, histogram.sum);
+        out.sample(&format!("{}_count", name), &labels, histogram.count);
+    }
+}
+
+/// A Prometheus text exposition document being written.
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        self.0.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} {}\n",
+            name, help, name, kind
+        ));
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            self.0.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.0.push_str(&format!(" {}\n", value));
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: impl std::fmt::Display) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: impl std::fmt::Display) {
+        self.family(name, "counter", help);
+        self.sample(name, &[], value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("s3cret", "s3cret"));
+        assert!(!token_matches("s3cret", "s3creT"));
+        assert!(!token_matches("s3cret", "s3cret2"));
+        assert!(!token_matches("s3cret", ""));
+    }
+
+    #[test]
+    fn test_sample_escapes_labels() {
+        let mut out = Exposition::default();
+        out.sample("m", &[("remark", "HK \"01\"\\\n")], 5);
+        out.sample("n", &[], 1.5);
+        assert_eq!(out.0, "m{remark=\"HK \\\"01\\\"\\\\\\n\"} 5\nn 1.5\n");
+    }
+
+    #[test]
+    fn test_latency_histogram_is_cumulative() {
+        let key = RequestKey {
+            method: "GET".to_string(),
+            path: "/api/inbound/list".to_string(),
+            status: 200,
+        };
+        let mut histogram = Histogram::default();
+        histogram.observe(0.003);
+        histogram.observe(0.2);
+        histogram.observe(30.0);
+
+        let mut out = Exposition::default();
+        write_latency(&mut out, &BTreeMap::from([(key, histogram)]));
+        let lines: Vec<&str> = out.0.lines().collect();
+
+        let labels = "method=\"GET\",path=\"/api/inbound/list\",status=\"200\"";
+        assert!(lines.contains(
+            &format!(
+                "xui_http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1",
+                labels
+            )
+            .as_str()
+        ));
+        assert!(lines.contains(
+            &format!(
+                "xui_http_request_duration_seconds_bucket{{{},le=\"0.25\"}} 2",
+                labels
+            )
+            .as_str()
+        ));
+        assert!(lines.contains(
+            &format!(
+                "xui_http_request_duration_seconds_bucket{{{},le=\"10\"}} 2",
+                labels
+            )
+            .as_str()
+        ));
+        assert!(lines.contains(
+            &format!(
+                "xui_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3",
+                labels
+            )
+            .as_str()
+        ));
+        assert!(lines.contains(
+            &format!("xui_http_request_duration_seconds_count{{{}}} 3", labels).as_str()
+        ));
+    }
+}
@@ -1,5 +1,20 @@
 pub mod auth_service;
+pub mod balancer_service;
+pub mod client_service;
+pub mod config_revision_service;
+pub mod dns_service;
 pub mod inbound_service;
+pub mod metrics_service;
+pub mod outbound_service;
+pub mod policy_service;
+pub mod port_service;
+pub mod routing_service;
+pub mod subscription_service;
 pub mod system_service;
+pub mod template_service;
+pub mod traffic_history_service;
 pub mod traffic_service;
+pub mod xray_api;
+pub mod xray_process;
 pub mod xray_service;
+pub mod xray_settings_service;
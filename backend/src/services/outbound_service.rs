@@ -0,0 +1,497 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::outbound::{
+    CreateOutboundRequest, Outbound, UpdateOutboundRequest, OUTBOUND_PROTOCOLS,
+    RESERVED_OUTBOUND_TAGS,
+};
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+const DOMAIN_STRATEGIES: &[&str] = &[
+    "AsIs",
+    "UseIP",
+    "UseIPv4",
+    "UseIPv6",
+    "UseIPv4v6",
+    "UseIPv6v4",
+    "ForceIP",
+    "ForceIPv4",
+    "ForceIPv6",
+    "ForceIPv4v6",
+    "ForceIPv6v4",
+];
+
+pub async fn get_all_outbounds(conn: &mut SqliteConnection) -> ApiResult<Vec<Outbound>> {
+    let outbounds = sqlx::query_as::<_, Outbound>("SELECT * FROM outbounds ORDER BY id ASC")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(outbounds)
+}
+
+pub async fn add_outbound(
+    conn: &mut SqliteConnection,
+    req: CreateOutboundRequest,
+) -> ApiResult<Outbound> {
+    let tag = req.tag.trim().to_string();
+    validate_tag(&tag)?;
+    if find_by_tag(conn, &tag).await?.is_some() {
+        return Err(ApiError::BadRequest(format!(
+            "Outbound tag {} is already in use",
+            tag
+        )));
+    }
+
+    let settings = req.settings.unwrap_or_else(|| serde_json::json!({}));
+    validate_settings(&req.protocol, &settings)?;
+    let mux = normalize_mux(req.mux)?;
+    let proxy_tag = non_empty(req.proxy_tag);
+    validate_proxy_chain(conn, &tag, proxy_tag.as_deref()).await?;
+
+    let now = chrono::Local::now().naive_local();
+
+    let outbound = sqlx::query_as::<_, Outbound>(
+        r#"
+        INSERT INTO outbounds (tag, remark, protocol, enable, settings, stream_settings, proxy_tag, mux, send_through, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(tag)
+    .bind(req.remark.unwrap_or_default())
+    .bind(req.protocol)
+    .bind(req.enable.unwrap_or(true))
+    .bind(settings.to_string())
+    .bind(req.stream_settings.map(|v| v.to_string()))
+    .bind(proxy_tag)
+    .bind(mux.map(|v| v.to_string()))
+    .bind(non_empty(req.send_through))
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(outbound)
+}
+
+pub async fn update_outbound(
+    conn: &mut SqliteConnection,
+    req: UpdateOutboundRequest,
+) -> ApiResult<Outbound> {
+    let current = get_outbound(conn, req.id).await?;
+
+    let tag = req
+        .tag
+        .map(|t| t.trim().to_string())
+        .unwrap_or_else(|| current.tag.clone());
+    if tag != current.tag {
+        validate_tag(&tag)?;
+        if find_by_tag(conn, &tag).await?.is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "Outbound tag {} is already in use",
+                tag
+            )));
+        }
+        ensure_not_referenced(conn, &current.tag).await?;
+    }
+
+    let protocol = req.protocol.unwrap_or_else(|| current.protocol.clone());
+    let settings = match req.settings {
+        Some(settings) => settings,
+        None => parse_json(current.settings.as_deref()).unwrap_or_else(|| serde_json::json!({})),
+    };
+    validate_settings(&protocol, &settings)?;
+
+    let mux = match req.mux {
+        Some(mux) => normalize_mux(Some(mux))?,
+        None => parse_json(current.mux.as_deref()),
+    };
+    let proxy_tag = match req.proxy_tag {
+        Some(p) => non_empty(Some(p)),
+        None => current.proxy_tag.clone(),
+    };
+    validate_proxy_chain(conn, &tag, proxy_tag.as_deref()).await?;
+
+    let enable = req.enable.unwrap_or(current.enable);
+    if current.enable && !enable {
+        ensure_not_referenced(conn, &current.tag).await?;
+    }
+
+    let stream_settings = req
+        .stream_settings
+        .map(|v| v.to_string())
+        .or(current.stream_settings);
+    let send_through = match req.send_through {
+        Some(s) => non_empty(Some(s)),
+        None => current.send_through,
+    };
+
+    let now = chrono::Local::now().naive_local();
+
+    let outbound = sqlx::query_as::<_, Outbound>(
+        r#"
+        UPDATE outbounds
+        SET
+            tag = ?,
+            remark = COALESCE(?, remark),
+            protocol = ?,
+            enable = ?,
+            settings = ?,
+            stream_settings = ?,
+            proxy_tag = ?,
+            mux = ?,
+            send_through = ?,
+            updated_at = ?
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(tag)
+    .bind(req.remark)
+    .bind(protocol)
+    .bind(enable)
+    .bind(settings.to_string())
+    .bind(stream_settings)
+    .bind(proxy_tag)
+    .bind(mux.map(|v| v.to_string()))
+    .bind(send_through)
+    .bind(now)
+    .bind(req.id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(outbound)
+}
+
+pub async fn delete_outbound(conn: &mut SqliteConnection, id: i64) -> ApiResult<()> {
+    let outbound = get_outbound(conn, id).await?;
+    ensure_not_referenced(conn, &outbound.tag).await?;
+
+    sqlx::query("DELETE FROM outbounds WHERE id = ?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn get_outbound(conn: &mut SqliteConnection, id: i64) -> ApiResult<Outbound> {
+    sqlx::query_as::<_, Outbound>("SELECT * FROM outbounds WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Outbound not found".to_string()))
+}
+
+async fn find_by_tag(conn: &mut SqliteConnection, tag: &str) -> ApiResult<Option<Outbound>> {
+    let outbound = sqlx::query_as::<_, Outbound>("SELECT * FROM outbounds WHERE tag = ?")
+        .bind(tag)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(outbound)
+}
+
+/// Rejects removing, renaming or disabling an outbound that enabled outbounds chain through,
+/// enabled routing rules send traffic to or a balancer falls back to.
+async fn ensure_not_referenced(conn: &mut SqliteConnection, tag: &str) -> ApiResult<()> {
+    let users: Vec<(String,)> =
+        sqlx::query_as("SELECT tag FROM outbounds WHERE proxy_tag = ? AND enable = 1")
+            .bind(tag)
+            .fetch_all(&mut *conn)
+            .await?;
+    if !users.is_empty() {
+        let names: Vec<String> = users.into_iter().map(|(t,)| t).collect();
+        return Err(ApiError::BadRequest(format!(
+            "Outbound {} is used as proxy by: {}",
+            tag,
+            names.join(", ")
+        )));
+    }
+
+    let rules: Vec<(i64,)> =
+        sqlx::query_as("SELECT id FROM routing_rules WHERE outbound_tag = ? AND enable = 1")
+            .bind(tag)
+            .fetch_all(&mut *conn)
+            .await?;
+    if !rules.is_empty() {
+        let ids: Vec<String> = rules.into_iter().map(|(id,)| format!("#{}", id)).collect();
+        return Err(ApiError::BadRequest(format!(
+            "Outbound {} is used by routing rules: {}",
+            tag,
+            ids.join(", ")
+        )));
+    }
+
+    let balancers: Vec<(String,)> =
+        sqlx::query_as("SELECT tag FROM balancers WHERE fallback_tag = ?")
+            .bind(tag)
+            .fetch_all(&mut *conn)
+            .await?;
+    if !balancers.is_empty() {
+        let names: Vec<String> = balancers.into_iter().map(|(t,)| t).collect();
+        return Err(ApiError::BadRequest(format!(
+            "Outbound {} is the fallback of balancers: {}",
+            tag,
+            names.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that `proxy_tag` names another enabled outbound and that following the
+/// `proxySettings` chain from `tag` never loops back.
+async fn validate_proxy_chain(
+    conn: &mut SqliteConnection,
+    tag: &str,
+    proxy_tag: Option<&str>,
+) -> ApiResult<()> {
+    let Some(proxy_tag) = proxy_tag else {
+        return Ok(());
+    };
+    if proxy_tag == tag {
+        return Err(ApiError::BadRequest(
+            "An outbound cannot use itself as proxy".to_string(),
+        ));
+    }
+
+    let outbounds = get_all_outbounds(conn).await?;
+    let target = outbounds.iter().find(|o| o.tag == proxy_tag);
+    if !target.is_some_and(|o| o.enable) {
+        return Err(ApiError::BadRequest(format!(
+            "Proxy outbound {} does not exist or is disabled",
+            proxy_tag
+        )));
+    }
+
+    let mut next = Some(proxy_tag.to_string());
+    for _ in 0..outbounds.len() {
+        let Some(current) = next else {
+            return Ok(());
+        };
+        if current == tag {
+            return Err(ApiError::BadRequest(format!(
+                "Proxy chain through {} loops back to {}",
+                proxy_tag, tag
+            )));
+        }
+        next = outbounds
+            .iter()
+            .find(|o| o.tag == current)
+            .and_then(|o| o.proxy_tag.clone());
+    }
+    Ok(())
+}
+
+fn validate_tag(tag: &str) -> ApiResult<()> {
+    let valid_chars = tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if tag.is_empty() || tag.len() > 64 || !valid_chars {
+        return Err(ApiError::BadRequest(
+            "Outbound tag must be 1-64 letters, numbers, dots, underscores or hyphens".to_string(),
+        ));
+    }
+    if RESERVED_OUTBOUND_TAGS.contains(&tag) {
+        return Err(ApiError::BadRequest(format!(
+            "Outbound tag {} is reserved",
+            tag
+        )));
+    }
+    Ok(())
+}
+
+/// Checks the parts of `settings` Xray needs to dial out for each protocol.
+fn validate_settings(protocol: &str, settings: &Value) -> ApiResult<()> {
+    if !OUTBOUND_PROTOCOLS.contains(&protocol) {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported outbound protocol: {}",
+            protocol
+        )));
+    }
+    if !settings.is_object() {
+        return Err(ApiError::BadRequest(
+            "Outbound settings must be a JSON object".to_string(),
+        ));
+    }
+
+    match protocol {
+        "freedom" => {
+            if let Some(strategy) = settings.get("domainStrategy") {
+                let valid = strategy
+                    .as_str()
+                    .is_some_and(|s| DOMAIN_STRATEGIES.contains(&s));
+                if !valid {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid freedom domainStrategy: {}",
+                        strategy
+                    )));
+                }
+            }
+        }
+        "socks" | "http" => require_servers(settings, "servers", &[])?,
+        "trojan" | "shadowsocks" => require_servers(settings, "servers", &["password"])?,
+        "vless" | "vmess" => {
+            require_servers(settings, "vnext", &[])?;
+            for server in settings["vnext"].as_array().into_iter().flatten() {
+                let has_users = server
+                    .get("users")
+                    .and_then(|u| u.as_array())
+                    .is_some_and(|u| {
+                        u.iter().all(|user| user.get("id").is_some()) && !u.is_empty()
+                    });
+                if !has_users {
+                    return Err(ApiError::BadRequest(format!(
+                        "Every {} server needs at least one user with an id",
+                        protocol
+                    )));
+                }
+            }
+        }
+        "wireguard" => {
+            if settings.get("secretKey").and_then(|v| v.as_str()).is_none() {
+                return Err(ApiError::BadRequest(
+                    "WireGuard outbound requires secretKey".to_string(),
+                ));
+            }
+            let peers_ok = settings
+                .get("peers")
+                .and_then(|p| p.as_array())
+                .is_some_and(|peers| {
+                    !peers.is_empty()
+                        && peers.iter().all(|peer| {
+                            peer.get("publicKey").and_then(|v| v.as_str()).is_some()
+                                && peer.get("endpoint").and_then(|v| v.as_str()).is_some()
+                        })
+                });
+            if !peers_ok {
+                return Err(ApiError::BadRequest(
+                    "WireGuard outbound requires peers with publicKey and endpoint".to_string(),
+                ));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Requires `settings[key]` to be a non-empty array of servers with `address`, `port` and
+/// every field in `extra`.
+fn require_servers(settings: &Value, key: &str, extra: &[&str]) -> ApiResult<()> {
+    let servers = settings.get(key).and_then(|s| s.as_array());
+    let valid = servers.is_some_and(|servers| {
+        !servers.is_empty()
+            && servers.iter().all(|server| {
+                server.get("address").and_then(|v| v.as_str()).is_some()
+                    && server
+                        .get("port")
+                        .and_then(|v| v.as_u64())
+                        .is_some_and(|p| (1..=65535).contains(&p))
+                    && extra.iter().all(|field| server.get(field).is_some())
+            })
+    });
+    if !valid {
+        let mut fields = vec!["address", "port"];
+        fields.extend_from_slice(extra);
+        return Err(ApiError::BadRequest(format!(
+            "Outbound settings.{} must list servers with {}",
+            key,
+            fields.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Validates the `mux` object; an empty object means no mux settings.
+fn normalize_mux(mux: Option<Value>) -> ApiResult<Option<Value>> {
+    let Some(mux) = mux else {
+        return Ok(None);
+    };
+    let Some(obj) = mux.as_object() else {
+        return Err(ApiError::BadRequest(
+            "mux must be a JSON object".to_string(),
+        ));
+    };
+    if obj.is_empty() {
+        return Ok(None);
+    }
+
+    for (key, value) in obj {
+        let valid = match key.as_str() {
+            "enabled" => value.is_boolean(),
+            "concurrency" | "xudpConcurrency" => {
+                value.as_i64().is_some_and(|n| (-1..=1024).contains(&n))
+            }
+            "xudpProxyUDP443" => value
+                .as_str()
+                .is_some_and(|s| matches!(s, "reject" | "allow" | "skip")),
+            _ => return Err(ApiError::BadRequest(format!("Unknown mux option: {}", key))),
+        };
+        if !valid {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid mux {}: {}",
+                key, value
+            )));
+        }
+    }
+    Ok(Some(mux))
+}
+
+fn parse_json(s: Option<&str>) -> Option<Value> {
+    s.and_then(|s| serde_json::from_str(s).ok())
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_settings() {
+        assert!(validate_settings("freedom", &json!({ "domainStrategy": "UseIPv4" })).is_ok());
+        assert!(validate_settings("freedom", &json!({ "domainStrategy": "Bogus" })).is_err());
+        assert!(validate_settings("tuic", &json!({})).is_err());
+
+        let socks = json!({ "servers": [{ "address": "10.0.0.2", "port": 1080 }] });
+        assert!(validate_settings("socks", &socks).is_ok());
+        assert!(validate_settings("socks", &json!({ "servers": [] })).is_err());
+        assert!(validate_settings("trojan", &socks).is_err());
+
+        let vless = json!({ "vnext": [{
+            "address": "upstream.example.com",
+            "port": 443,
+            "users": [{ "id": "b831381d-6324-4d53-ad4f-8cda48b30811", "encryption": "none" }],
+        }]});
+        assert!(validate_settings("vless", &vless).is_ok());
+        let no_users = json!({ "vnext": [{ "address": "a", "port": 443, "users": [] }] });
+        assert!(validate_settings("vless", &no_users).is_err());
+
+        let wg = json!({
+            "secretKey": "cFf+Qu8Lq3JpE3GFL0Mm5Kk6/GC5c8ZmnJyM1G8rOW0=",
+            "peers": [{ "publicKey": "bmXOC+F1FxEMF9dyiK2H5/1SUtzH0JuVo51h2wPfgyo=", "endpoint": "engage.cloudflareclient.com:2408" }],
+        });
+        assert!(validate_settings("wireguard", &wg).is_ok());
+        assert!(validate_settings("wireguard", &json!({ "peers": [] })).is_err());
+    }
+
+    #[test]
+    fn test_normalize_mux() {
+        assert_eq!(normalize_mux(None).unwrap(), None);
+        assert_eq!(normalize_mux(Some(json!({}))).unwrap(), None);
+
+        let mux = json!({ "enabled": true, "concurrency": 8, "xudpProxyUDP443": "reject" });
+        assert_eq!(normalize_mux(Some(mux.clone())).unwrap(), Some(mux));
+
+        assert!(normalize_mux(Some(json!({ "concurrency": 4096 }))).is_err());
+        assert!(normalize_mux(Some(json!({ "xudpProxyUDP443": "maybe" }))).is_err());
+        assert!(normalize_mux(Some(json!({ "padding": true }))).is_err());
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("warp-out").is_ok());
+        assert!(validate_tag("direct").is_err());
+        assert!(validate_tag("has space").is_err());
+        assert!(validate_tag("").is_err());
+    }
+}
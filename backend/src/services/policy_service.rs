@@ -0,0 +1,182 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::xray_config::{LevelPolicy, PolicyConfig, SystemPolicy};
+use crate::services::xray_settings_service;
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+
+const POLICY_KEY: &str = "policy";
+/// Upper bound for the per-level timeouts, in seconds.
+const MAX_TIMEOUT: u32 = 86_400;
+/// Upper bound for `bufferSize`, in KB.
+const MAX_BUFFER_SIZE: u32 = 102_400;
+
+/// The policy used until an administrator saves their own.
+pub fn default_policy() -> PolicyConfig {
+    let mut levels = HashMap::new();
+    levels.insert(
+        "0".to_string(),
+        LevelPolicy {
+            stats_user_uplink: true,
+            stats_user_downlink: true,
+            handshake: 4,
+            conn_idle: 300,
+            uplink_only: 2,
+            downlink_only: 5,
+            buffer_size: 512,
+        },
+    );
+
+    PolicyConfig {
+        levels,
+        system: Some(SystemPolicy {
+            stats_inbound_uplink: true,
+            stats_inbound_downlink: true,
+            stats_outbound_uplink: true,
+            stats_outbound_downlink: true,
+        }),
+    }
+}
+
+pub async fn get_policy(conn: &mut SqliteConnection) -> ApiResult<PolicyConfig> {
+    let policy = xray_settings_service::get_setting(conn, POLICY_KEY)
+        .await?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_else(default_policy);
+    Ok(policy)
+}
+
+pub async fn update_policy(
+    conn: &mut SqliteConnection,
+    policy: PolicyConfig,
+) -> ApiResult<PolicyConfig> {
+    validate_policy(&policy)?;
+
+    let in_use: Vec<(i64,)> = sqlx::query_as("SELECT DISTINCT level FROM clients")
+        .fetch_all(&mut *conn)
+        .await?;
+    if let Some((level,)) = in_use
+        .into_iter()
+        .find(|(level,)| !policy.levels.contains_key(&level.to_string()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Level {} is still assigned to clients",
+            level
+        )));
+    }
+
+    let value = serde_json::to_string(&policy)
+        .map_err(|e| ApiError::InternalError(format!("Failed to serialize settings: {}", e)))?;
+    xray_settings_service::set_setting(conn, POLICY_KEY, &value).await?;
+    Ok(policy)
+}
+
+/// Rejects assigning a client to a level the policy does not define.
+pub async fn ensure_level_exists(conn: &mut SqliteConnection, level: i64) -> ApiResult<()> {
+    if !get_policy(conn)
+        .await?
+        .levels
+        .contains_key(&level.to_string())
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Policy level {} does not exist",
+            level
+        )));
+    }
+    Ok(())
+}
+
+/// Checks level keys and limits. The user and inbound stats flags must stay on because
+/// traffic quotas are enforced from those counters.
+fn validate_policy(policy: &PolicyConfig) -> ApiResult<()> {
+    if !policy.levels.contains_key("0") {
+        return Err(ApiError::BadRequest(
+            "Policy level 0 is required".to_string(),
+        ));
+    }
+
+    for (key, level) in &policy.levels {
+        if key.parse::<u32>().map(|n| n.to_string()).as_deref() != Ok(key) {
+            return Err(ApiError::BadRequest(format!(
+                "Policy level {:?} must be a non-negative integer",
+                key
+            )));
+        }
+        if !level.stats_user_uplink || !level.stats_user_downlink {
+            return Err(ApiError::BadRequest(format!(
+                "Level {} must keep statsUserUplink and statsUserDownlink enabled for traffic accounting",
+                key
+            )));
+        }
+        for (name, value) in [
+            ("handshake", level.handshake),
+            ("connIdle", level.conn_idle),
+            ("uplinkOnly", level.uplink_only),
+            ("downlinkOnly", level.downlink_only),
+        ] {
+            if value > MAX_TIMEOUT {
+                return Err(ApiError::BadRequest(format!(
+                    "Level {} {} must be at most {} seconds",
+                    key, name, MAX_TIMEOUT
+                )));
+            }
+        }
+        if level.buffer_size > MAX_BUFFER_SIZE {
+            return Err(ApiError::BadRequest(format!(
+                "Level {} bufferSize must be at most {} KB",
+                key, MAX_BUFFER_SIZE
+            )));
+        }
+    }
+
+    let inbound_stats = policy
+        .system
+        .as_ref()
+        .is_some_and(|s| s.stats_inbound_uplink && s.stats_inbound_downlink);
+    if !inbound_stats {
+        return Err(ApiError::BadRequest(
+            "statsInboundUplink and statsInboundDownlink must stay enabled for traffic accounting"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_policy() {
+        assert!(validate_policy(&default_policy()).is_ok());
+
+        let policy: PolicyConfig = serde_json::from_value(json!({
+            "levels": {
+                "0": { "handshake": 4, "connIdle": 600, "bufferSize": 1024 },
+                "1": { "connIdle": 30, "bufferSize": 0 },
+            },
+            "system": { "statsOutboundUplink": false },
+        }))
+        .unwrap();
+        assert!(policy.levels["1"].stats_user_uplink);
+        assert!(validate_policy(&policy).is_ok());
+
+        let mut no_default = default_policy();
+        no_default.levels.remove("0");
+        assert!(validate_policy(&no_default).is_err());
+
+        let mut bad_key = default_policy();
+        bad_key
+            .levels
+            .insert("01".to_string(), LevelPolicy::default());
+        assert!(validate_policy(&bad_key).is_err());
+
+        let mut huge_idle = default_policy();
+        huge_idle.levels.get_mut("0").unwrap().conn_idle = MAX_TIMEOUT + 1;
+        assert!(validate_policy(&huge_idle).is_err());
+
+        let mut no_stats = default_policy();
+        no_stats.system.as_mut().unwrap().stats_inbound_downlink = false;
+        assert!(validate_policy(&no_stats).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::inbound::Inbound;
+use crate::services::xray_api;
+use serde_json::Value;
+use sqlx::SqliteConnection;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// The transport protocols an inbound occupies its port with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transports {
+    pub tcp: bool,
+    pub udp: bool,
+}
+
+impl Transports {
+    pub const TCP: Transports = Transports {
+        tcp: true,
+        udp: false,
+    };
+
+    /// Works out TCP/UDP use from the protocol and its settings: mKCP is UDP only, xhttp adds
+    /// UDP when HTTP/3 is offered, and Shadowsocks, dokodemo-door and SOCKS can relay UDP on
+    /// the same port.
+    pub fn of(protocol: &str, settings: &Value, stream_settings: &Value) -> Self {
+        let network = stream_settings
+            .get("network")
+            .and_then(|n| n.as_str())
+            .unwrap_or("tcp");
+        let mut transports = if network == "kcp" {
+            Transports {
+                tcp: false,
+                udp: true,
+            }
+        } else {
+            Transports::TCP
+        };
+
+        let offers_h3 = stream_settings
+            .pointer("/tlsSettings/alpn")
+            .and_then(|a| a.as_array())
+            .is_some_and(|alpn| alpn.iter().any(|a| a == "h3"));
+        if network == "xhttp" && offers_h3 {
+            transports.udp = true;
+        }
+
+        match protocol {
+            "shadowsocks" | "dokodemo-door" => {
+                let network = settings
+                    .get("network")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("tcp");
+                transports.tcp = network.split(',').any(|n| n.trim() == "tcp");
+                transports.udp = network.split(',').any(|n| n.trim() == "udp");
+            }
+            "socks" if settings.get("udp").and_then(|u| u.as_bool()) == Some(true) => {
+                transports.udp = true;
+            }
+            _ => {}
+        }
+
+        transports
+    }
+
+    fn overlaps(&self, other: &Transports) -> bool {
+        (self.tcp && other.tcp) || (self.udp && other.udp)
+    }
+
+    fn describe(&self) -> &'static str {
+        match (self.tcp, self.udp) {
+            (true, true) => "tcp/udp",
+            (false, true) => "udp",
+            _ => "tcp",
+        }
+    }
+}
+
+/// Where an inbound wants to listen.
+#[derive(Debug, Clone, Copy)]
+pub struct PortRequest<'a> {
+    pub port: i32,
+    pub listen: Option<&'a str>,
+    pub transports: Transports,
+}
+
+/// Rejects `request` if it collides with the Xray API inbound, the panel's own `SERVER_PORT`
+/// or another inbound on an overlapping listen address and transport. With `probe` set the
+/// port is also bound briefly to catch processes outside the panel's knowledge.
+///
+/// `exclude_id` is the inbound being updated, which may keep its own port.
+pub async fn ensure_port_available(
+    conn: &mut SqliteConnection,
+    request: PortRequest<'_>,
+    exclude_id: Option<&str>,
+    probe: bool,
+) -> ApiResult<()> {
+    if is_unix_socket(request.listen) {
+        return Ok(());
+    }
+    if !(1..=65535).contains(&request.port) {
+        return Err(ApiError::BadRequest(format!(
+            "Port must be between 1 and 65535, got {}",
+            request.port
+        )));
+    }
+    let port = request.port;
+
+    if port == xray_api::API_PORT
+        && request.transports.tcp
+        && listens_overlap(request.listen, Some("127.0.0.1"))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Port {} is reserved for the Xray API",
+            port
+        )));
+    }
+    if Some(port) == panel_port() && request.transports.tcp {
+        return Err(ApiError::BadRequest(format!(
+            "Port {} is used by the panel itself (SERVER_PORT)",
+            port
+        )));
+    }
+
+    let others = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE port = ? AND id != ?")
+        .bind(port)
+        .bind(exclude_id.unwrap_or_default())
+        .fetch_all(&mut *conn)
+        .await?;
+    for other in others {
+        let parse = |s: &Option<String>| {
+            s.as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or(Value::Null)
+        };
+        let transports = Transports::of(
+            &other.protocol,
+            &parse(&other.settings),
+            &parse(&other.stream_settings),
+        );
+        if request.transports.overlaps(&transports)
+            && listens_overlap(request.listen, other.listen.as_deref())
+            && !is_unix_socket(other.listen.as_deref())
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Port {}/{} is already used by inbound {}",
+                port,
+                transports.describe(),
+                other.remark
+            )));
+        }
+    }
+
+    if probe {
+        probe_port(request).await?;
+    }
+    Ok(())
+}
+
+/// Binds the port on the requested address to see whether another process holds it. Only
+/// `AddrInUse` counts; errors such as missing privileges for ports below 1024 are left for
+/// Xray to report.
+async fn probe_port(request: PortRequest<'_>) -> ApiResult<()> {
+    let ip = request
+        .listen
+        .and_then(|l| l.trim().parse::<IpAddr>().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let addr = (ip, request.port as u16);
+
+    let in_use = |result: std::io::Result<()>, transport: &str| match result {
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Err(ApiError::BadRequest(format!(
+            "Port {}/{} is already in use by another process",
+            request.port, transport
+        ))),
+        _ => Ok(()),
+    };
+
+    if request.transports.tcp {
+        in_use(tokio::net::TcpListener::bind(addr).await.map(drop), "tcp")?;
+    }
+    if request.transports.udp {
+        in_use(tokio::net::UdpSocket::bind(addr).await.map(drop), "udp")?;
+    }
+    Ok(())
+}
+
+/// The port the panel listens on, from `SERVER_PORT` as read at startup.
+fn panel_port() -> Option<i32> {
+    std::env::var("SERVER_PORT")
+        .unwrap_or_else(|_| "8080".to_string())
+        .parse()
+        .ok()
+}
+
+/// Xray accepts a unix domain socket (a path or `@abstract` name) as `listen`; the port is
+/// then ignored.
+fn is_unix_socket(listen: Option<&str>) -> bool {
+    listen.is_some_and(|l| l.starts_with('/') || l.starts_with('@'))
+}
+
+/// Whether two listen addresses can clash: an empty or wildcard address covers every
+/// other, otherwise the addresses must be equal.
+fn listens_overlap(a: Option<&str>, b: Option<&str>) -> bool {
+    let ip = |l: Option<&str>| {
+        l.map(str::trim)
+            .filter(|l| !l.is_empty())
+            .and_then(|l| l.parse::<IpAddr>().ok())
+    };
+    match (ip(a), ip(b)) {
+        (Some(a), Some(b)) => a.is_unspecified() || b.is_unspecified() || a == b,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_transports() {
+        let none = json!({});
+        assert_eq!(Transports::of("vless", &none, &none), Transports::TCP);
+        assert_eq!(
+            Transports::of("vless", &none, &json!({ "network": "kcp" })),
+            Transports {
+                tcp: false,
+                udp: true
+            }
+        );
+        assert_eq!(
+            Transports::of("shadowsocks", &json!({ "network": "tcp,udp" }), &none),
+            Transports {
+                tcp: true,
+                udp: true
+            }
+        );
+        assert_eq!(
+            Transports::of("dokodemo-door", &json!({ "network": "udp" }), &none),
+            Transports {
+                tcp: false,
+                udp: true
+            }
+        );
+        assert!(Transports::of("socks", &json!({ "udp": true }), &none).udp);
+    }
+
+    #[test]
+    fn test_listens_overlap() {
+        assert!(listens_overlap(None, Some("127.0.0.1")));
+        assert!(listens_overlap(Some("0.0.0.0"), Some("10.0.0.1")));
+        assert!(listens_overlap(Some("::"), Some("127.0.0.1")));
+        assert!(listens_overlap(Some("10.0.0.1"), Some("10.0.0.1")));
+        assert!(!listens_overlap(Some("10.0.0.1"), Some("10.0.0.2")));
+        assert!(!listens_overlap(Some("127.0.0.1"), Some("::1")));
+    }
+
+    #[tokio::test]
+    async fn test_probe_detects_bound_port() {
+        let holder = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = holder.local_addr().unwrap().port() as i32;
+
+        let request = PortRequest {
+            port,
+            listen: Some("127.0.0.1"),
+            transports: Transports::TCP,
+        };
+        assert!(probe_port(request).await.is_err());
+
+        drop(holder);
+        assert!(probe_port(request).await.is_ok());
+    }
+}
@@ -0,0 +1,459 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::routing_rule::{
+    CreateRouteRuleRequest, ReorderRouteRulesRequest, RouteRule, RoutingSettings,
+    UpdateRouteRuleRequest, ROUTING_DOMAIN_STRATEGIES,
+};
+use crate::services::{balancer_service, xray_settings_service};
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{BTreeMap, HashSet};
+
+const DOMAIN_STRATEGY_KEY: &str = "routing.domainStrategy";
+const DEFAULT_DOMAIN_STRATEGY: &str = "IPIfNonMatch";
+const SNIFFED_PROTOCOLS: &[&str] = &["http", "tls", "quic", "bittorrent"];
+
+pub async fn get_all_rules(conn: &mut SqliteConnection) -> ApiResult<Vec<RouteRule>> {
+    let rules =
+        sqlx::query_as::<_, RouteRule>("SELECT * FROM routing_rules ORDER BY priority ASC, id ASC")
+            .fetch_all(&mut *conn)
+            .await?;
+    Ok(rules)
+}
+
+pub async fn add_rule(
+    conn: &mut SqliteConnection,
+    req: CreateRouteRuleRequest,
+) -> ApiResult<RouteRule> {
+    let fields = RuleFields::default().merge(&req);
+    fields.validate()?;
+    validate_references(conn, &fields).await?;
+
+    let (max_priority,): (Option<i64>,) = sqlx::query_as("SELECT MAX(priority) FROM routing_rules")
+        .fetch_one(&mut *conn)
+        .await?;
+    let now = chrono::Local::now().naive_local();
+
+    let rule = sqlx::query_as::<_, RouteRule>(
+        r#"
+        INSERT INTO routing_rules (priority, remark, enable, domain, ip, port, source_port, source, user, inbound_tag, network, protocol, attrs, outbound_tag, balancer_tag, created_at, updated_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#,
+    )
+    .bind(max_priority.map_or(0, |p| p + 1))
+    .bind(req.remark.unwrap_or_default())
+    .bind(req.enable.unwrap_or(true))
+    .bind(to_json(&fields.domain))
+    .bind(to_json(&fields.ip))
+    .bind(fields.port)
+    .bind(fields.source_port)
+    .bind(to_json(&fields.source))
+    .bind(to_json(&fields.user))
+    .bind(to_json(&fields.inbound_tag))
+    .bind(fields.network)
+    .bind(to_json(&fields.protocol))
+    .bind(attrs_json(&fields.attrs))
+    .bind(fields.outbound_tag)
+    .bind(fields.balancer_tag)
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(rule)
+}
+
+pub async fn update_rule(
+    conn: &mut SqliteConnection,
+    req: UpdateRouteRuleRequest,
+) -> ApiResult<RouteRule> {
+    let current = get_rule(conn, req.id).await?;
+    let fields = RuleFields::from_rule(&current).merge(&req.rule);
+    fields.validate()?;
+    validate_references(conn, &fields).await?;
+
+    let now = chrono::Local::now().naive_local();
+
+    let rule = sqlx::query_as::<_, RouteRule>(
+        r#"
+        UPDATE routing_rules
+        SET
+            remark = COALESCE(?, remark),
+            enable = COALESCE(?, enable),
+            domain = ?,
+            ip = ?,
+            port = ?,
+            source_port = ?,
+            source = ?,
+            user = ?,
+            inbound_tag = ?,
+            network = ?,
+            protocol = ?,
+            attrs = ?,
+            outbound_tag = ?,
+            balancer_tag = ?,
+            updated_at = ?
+        WHERE id = ?
+        RETURNING *
+        "#,
+    )
+    .bind(req.rule.remark)
+    .bind(req.rule.enable)
+    .bind(to_json(&fields.domain))
+    .bind(to_json(&fields.ip))
+    .bind(fields.port)
+    .bind(fields.source_port)
+    .bind(to_json(&fields.source))
+    .bind(to_json(&fields.user))
+    .bind(to_json(&fields.inbound_tag))
+    .bind(fields.network)
+    .bind(to_json(&fields.protocol))
+    .bind(attrs_json(&fields.attrs))
+    .bind(fields.outbound_tag)
+    .bind(fields.balancer_tag)
+    .bind(now)
+    .bind(req.id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(rule)
+}
+
+pub async fn delete_rule(conn: &mut SqliteConnection, id: i64) -> ApiResult<()> {
+    let result = sqlx::query("DELETE FROM routing_rules WHERE id = ?")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::BadRequest("Routing rule not found".to_string()));
+    }
+    Ok(())
+}
+
+/// Rewrites every rule's priority to its index in `req.ids`, which must list each rule once.
+pub async fn reorder_rules(
+    conn: &mut SqliteConnection,
+    req: ReorderRouteRulesRequest,
+) -> ApiResult<Vec<RouteRule>> {
+    let existing: HashSet<i64> = get_all_rules(conn).await?.iter().map(|r| r.id).collect();
+    let requested: HashSet<i64> = req.ids.iter().copied().collect();
+    if requested.len() != req.ids.len() || requested != existing {
+        return Err(ApiError::BadRequest(
+            "Reorder must list every routing rule id exactly once".to_string(),
+        ));
+    }
+
+    let mut tx = conn.begin().await?;
+    for (priority, id) in req.ids.iter().enumerate() {
+        sqlx::query("UPDATE routing_rules SET priority = ? WHERE id = ?")
+            .bind(priority as i64)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    get_all_rules(conn).await
+}
+
+pub async fn get_settings(conn: &mut SqliteConnection) -> ApiResult<RoutingSettings> {
+    let domain_strategy = xray_settings_service::get_setting(conn, DOMAIN_STRATEGY_KEY)
+        .await?
+        .unwrap_or_else(|| DEFAULT_DOMAIN_STRATEGY.to_string());
+    Ok(RoutingSettings { domain_strategy })
+}
+
+pub async fn update_settings(
+    conn: &mut SqliteConnection,
+    settings: RoutingSettings,
+) -> ApiResult<RoutingSettings> {
+    if !ROUTING_DOMAIN_STRATEGIES.contains(&settings.domain_strategy.as_str()) {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid routing domainStrategy: {}",
+            settings.domain_strategy
+        )));
+    }
+    xray_settings_service::set_setting(conn, DOMAIN_STRATEGY_KEY, &settings.domain_strategy)
+        .await?;
+    Ok(settings)
+}
+
+async fn get_rule(conn: &mut SqliteConnection, id: i64) -> ApiResult<RouteRule> {
+    sqlx::query_as::<_, RouteRule>("SELECT * FROM routing_rules WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Routing rule not found".to_string()))
+}
+
+/// Checks that the inbound and outbound tags a rule names exist.
+async fn validate_references(conn: &mut SqliteConnection, fields: &RuleFields) -> ApiResult<()> {
+    if !fields.inbound_tag.is_empty() {
+        let known: Vec<(String,)> =
+            sqlx::query_as("SELECT COALESCE(tag, 'inbound-' || id) FROM inbounds")
+                .fetch_all(&mut *conn)
+                .await?;
+        let known: HashSet<String> = known.into_iter().map(|(t,)| t).collect();
+        if let Some(missing) = fields.inbound_tag.iter().find(|t| !known.contains(*t)) {
+            return Err(ApiError::BadRequest(format!(
+                "Inbound {} does not exist",
+                missing
+            )));
+        }
+    }
+
+    if let Some(tag) = &fields.outbound_tag {
+        let builtin = matches!(tag.as_str(), "direct" | "blocked");
+        let (enabled,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM outbounds WHERE tag = ? AND enable = 1")
+                .bind(tag)
+                .fetch_one(&mut *conn)
+                .await?;
+        if !builtin && enabled == 0 {
+            return Err(ApiError::BadRequest(format!(
+                "Outbound {} does not exist or is disabled",
+                tag
+            )));
+        }
+    }
+
+    if let Some(tag) = &fields.balancer_tag {
+        if !balancer_service::balancer_exists(conn, tag).await? {
+            return Err(ApiError::BadRequest(format!(
+                "Balancer {} does not exist",
+                tag
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The matchers and target of a rule, decoded from storage so updates can be merged and
+/// validated as a whole.
+#[derive(Debug, Default)]
+struct RuleFields {
+    domain: Vec<String>,
+    ip: Vec<String>,
+    port: Option<String>,
+    source_port: Option<String>,
+    source: Vec<String>,
+    user: Vec<String>,
+    inbound_tag: Vec<String>,
+    network: Option<String>,
+    protocol: Vec<String>,
+    attrs: BTreeMap<String, String>,
+    outbound_tag: Option<String>,
+    balancer_tag: Option<String>,
+}
+
+impl RuleFields {
+    fn from_rule(rule: &RouteRule) -> Self {
+        let list = |s: &Option<String>| -> Vec<String> {
+            s.as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or_default()
+        };
+        Self {
+            domain: list(&rule.domain),
+            ip: list(&rule.ip),
+            port: rule.port.clone(),
+            source_port: rule.source_port.clone(),
+            source: list(&rule.source),
+            user: list(&rule.user),
+            inbound_tag: list(&rule.inbound_tag),
+            network: rule.network.clone(),
+            protocol: list(&rule.protocol),
+            attrs: rule
+                .attrs
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or_default(),
+            outbound_tag: rule.outbound_tag.clone(),
+            balancer_tag: rule.balancer_tag.clone(),
+        }
+    }
+
+    fn merge(mut self, req: &CreateRouteRuleRequest) -> Self {
+        let list = |v: &Option<Vec<String>>, current: &mut Vec<String>| {
+            if let Some(v) = v {
+                *current = v
+                    .iter()
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+            }
+        };
+        let text = |v: &Option<String>, current: &mut Option<String>| {
+            if let Some(v) = v {
+                *current = Some(v.trim().to_string()).filter(|s| !s.is_empty());
+            }
+        };
+
+        list(&req.domain, &mut self.domain);
+        list(&req.ip, &mut self.ip);
+        text(&req.port, &mut self.port);
+        text(&req.source_port, &mut self.source_port);
+        list(&req.source, &mut self.source);
+        list(&req.user, &mut self.user);
+        list(&req.inbound_tag, &mut self.inbound_tag);
+        text(&req.network, &mut self.network);
+        list(&req.protocol, &mut self.protocol);
+        if let Some(attrs) = &req.attrs {
+            self.attrs = attrs.clone();
+        }
+        text(&req.outbound_tag, &mut self.outbound_tag);
+        text(&req.balancer_tag, &mut self.balancer_tag);
+        self
+    }
+
+    fn validate(&self) -> ApiResult<()> {
+        let has_matcher = !self.domain.is_empty()
+            || !self.ip.is_empty()
+            || self.port.is_some()
+            || self.source_port.is_some()
+            || !self.source.is_empty()
+            || !self.user.is_empty()
+            || !self.inbound_tag.is_empty()
+            || self.network.is_some()
+            || !self.protocol.is_empty()
+            || !self.attrs.is_empty();
+        if !has_matcher {
+            return Err(ApiError::BadRequest(
+                "A routing rule needs at least one condition".to_string(),
+            ));
+        }
+
+        match (&self.outbound_tag, &self.balancer_tag) {
+            (Some(_), None) | (None, Some(_)) => {}
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "A routing rule needs exactly one of outboundTag or balancerTag".to_string(),
+                ))
+            }
+        }
+
+        for (name, value) in [("port", &self.port), ("sourcePort", &self.source_port)] {
+            if let Some(value) = value {
+                if !is_port_list(value) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid {}: {} (expected e.g. 53,443,1000-2000)",
+                        name, value
+                    )));
+                }
+            }
+        }
+
+        if let Some(network) = &self.network {
+            if !matches!(network.as_str(), "tcp" | "udp" | "tcp,udp") {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid network: {} (expected tcp, udp or tcp,udp)",
+                    network
+                )));
+            }
+        }
+
+        if let Some(p) = self
+            .protocol
+            .iter()
+            .find(|p| !SNIFFED_PROTOCOLS.contains(&p.as_str()))
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid protocol matcher: {} (expected one of {})",
+                p,
+                SNIFFED_PROTOCOLS.join(", ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Accepts Xray port lists such as `443`, `1000-2000` or `53,443,8000-9000`.
+fn is_port_list(value: &str) -> bool {
+    let port = |s: &str| s.trim().parse::<u16>().ok().filter(|p| *p > 0);
+    value.split(',').all(|part| match part.split_once('-') {
+        Some((from, to)) => matches!((port(from), port(to)), (Some(from), Some(to)) if from <= to),
+        None => port(part).is_some(),
+    })
+}
+
+fn to_json(list: &[String]) -> Option<String> {
+    (!list.is_empty()).then(|| serde_json::to_string(list).unwrap_or_default())
+}
+
+fn attrs_json(attrs: &BTreeMap<String, String>) -> Option<String> {
+    (!attrs.is_empty()).then(|| serde_json::to_string(attrs).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(outbound_tag: Option<&str>) -> CreateRouteRuleRequest {
+        CreateRouteRuleRequest {
+            remark: None,
+            enable: None,
+            domain: Some(vec!["geosite:category-ads-all".to_string()]),
+            ip: None,
+            port: None,
+            source_port: None,
+            source: None,
+            user: None,
+            inbound_tag: None,
+            network: None,
+            protocol: None,
+            attrs: None,
+            outbound_tag: outbound_tag.map(str::to_string),
+            balancer_tag: None,
+        }
+    }
+
+    #[test]
+    fn test_port_list() {
+        assert!(is_port_list("443"));
+        assert!(is_port_list("53,443,1000-2000"));
+        assert!(!is_port_list("0"));
+        assert!(!is_port_list("2000-1000"));
+        assert!(!is_port_list("http"));
+        assert!(!is_port_list("443,"));
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        assert!(RuleFields::default()
+            .merge(&request(Some("blocked")))
+            .validate()
+            .is_ok());
+        assert!(RuleFields::default()
+            .merge(&request(None))
+            .validate()
+            .is_err());
+
+        let mut no_condition = request(Some("direct"));
+        no_condition.domain = Some(vec![" ".to_string()]);
+        assert!(RuleFields::default()
+            .merge(&no_condition)
+            .validate()
+            .is_err());
+
+        let mut bad_network = request(Some("direct"));
+        bad_network.network = Some("icmp".to_string());
+        assert!(RuleFields::default()
+            .merge(&bad_network)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_merge_clears_empty_fields() {
+        let mut req = request(Some("direct"));
+        req.ip = Some(vec!["geoip:cn".to_string()]);
+        let fields = RuleFields::default().merge(&req);
+
+        let mut update = request(None);
+        update.domain = Some(vec![]);
+        update.outbound_tag = None;
+        let merged = fields.merge(&update);
+        assert!(merged.domain.is_empty());
+        assert_eq!(merged.ip, vec!["geoip:cn"]);
+        assert_eq!(merged.outbound_tag.as_deref(), Some("direct"));
+    }
+}
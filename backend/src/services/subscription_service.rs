@@ -0,0 +1,288 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::inbound::Inbound;
+use crate::services::client_service;
+use crate::utils::share_link::ProxyNode;
+use base64::Engine;
+use sqlx::SqlitePool;
+
+/// Output format of a subscription response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionFormat {
+    /// Base64 encoded share links (v2rayN, Shadowrocket, ...).
+    Base64,
+    /// Clash Meta / Mihomo YAML profile.
+    Clash,
+    /// sing-box JSON outbounds.
+    SingBox,
+}
+
+impl SubscriptionFormat {
+    /// Picks the format from the `format` query parameter if present, otherwise from the
+    /// client's User-Agent, falling back to base64 links.
+    pub fn detect(format: Option<&str>, user_agent: Option<&str>) -> Self {
+        if let Some(format) = format {
+            return match format.to_ascii_lowercase().as_str() {
+                "clash" | "clash-meta" | "mihomo" => SubscriptionFormat::Clash,
+                "singbox" | "sing-box" => SubscriptionFormat::SingBox,
+                _ => SubscriptionFormat::Base64,
+            };
+        }
+
+        let ua = user_agent.unwrap_or_default().to_ascii_lowercase();
+        if ua.contains("clash") || ua.contains("mihomo") || ua.contains("stash") {
+            SubscriptionFormat::Clash
+        } else if ua.contains("sing-box") || ua.contains("singbox") {
+            SubscriptionFormat::SingBox
+        } else {
+            SubscriptionFormat::Base64
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SubscriptionFormat::Base64 => "text/plain; charset=utf-8",
+            SubscriptionFormat::Clash => "text/yaml; charset=utf-8",
+            SubscriptionFormat::SingBox => "application/json; charset=utf-8",
+        }
+    }
+}
+
+/// The nodes and traffic totals served for one subscription ID.
+#[derive(Debug)]
+pub struct Subscription {
+    pub nodes: Vec<ProxyNode>,
+    pub upload: i64,
+    pub download: i64,
+    /// Sum of the client quotas in bytes, 0 when any of them is unlimited.
+    pub total: i64,
+    /// Earliest client expiry as a unix timestamp in seconds, 0 when none expires.
+    pub expire: i64,
+}
+
+impl Subscription {
+    /// Value of the `Subscription-Userinfo` header understood by v2rayN, Clash and friends.
+    pub fn userinfo_header(&self) -> String {
+        format!(
+            "upload={}; download={}; total={}; expire={}",
+            self.upload, self.download, self.total, self.expire
+        )
+    }
+
+    /// Newline separated share links, base64 encoded as v2rayN expects.
+    pub fn to_base64_links(&self) -> String {
+        let links = self
+            .nodes
+            .iter()
+            .map(|n| n.to_link())
+            .collect::<Vec<_>>()
+            .join("\n");
+        base64::engine::general_purpose::STANDARD.encode(links)
+    }
+
+    /// A minimal Clash Meta profile: all nodes behind one selector group.
+    pub fn to_clash_yaml(&self) -> ApiResult<String> {
+        let proxies: Vec<serde_json::Value> =
+            self.nodes.iter().map(|n| n.to_clash_proxy()).collect();
+        let mut names: Vec<&str> = self.nodes.iter().map(|n| n.remark.as_str()).collect();
+        names.push("DIRECT");
+
+        let profile = serde_json::json!({
+            "mixed-port": 7890,
+            "allow-lan": false,
+            "mode": "rule",
+            "log-level": "info",
+            "proxies": proxies,
+            "proxy-groups": [{
+                "name": "Proxy",
+                "type": "select",
+                "proxies": names,
+            }],
+            "rules": ["MATCH,Proxy"],
+        });
+
+        serde_yaml::to_string(&profile)
+            .map_err(|e| ApiError::InternalError(format!("Failed to render Clash profile: {}", e)))
+    }
+
+    /// sing-box outbounds: one per supported node, a selector over them and `direct`.
+    pub fn to_singbox_json(&self) -> ApiResult<String> {
+        let nodes: Vec<serde_json::Value> = self
+            .nodes
+            .iter()
+            .filter_map(|n| n.to_singbox_outbound())
+            .collect();
+        let mut tags: Vec<serde_json::Value> = nodes.iter().map(|n| n["tag"].clone()).collect();
+        tags.push(serde_json::json!("direct"));
+
+        let mut outbounds = vec![serde_json::json!({
+            "type": "selector",
+            "tag": "proxy",
+            "outbounds": tags,
+        })];
+        outbounds.extend(nodes);
+        outbounds.push(serde_json::json!({ "type": "direct", "tag": "direct" }));
+
+        serde_json::to_string_pretty(&serde_json::json!({ "outbounds": outbounds })).map_err(|e| {
+            ApiError::InternalError(format!("Failed to render sing-box outbounds: {}", e))
+        })
+    }
+
+    pub fn render(&self, format: SubscriptionFormat) -> ApiResult<String> {
+        match format {
+            SubscriptionFormat::Base64 => Ok(self.to_base64_links()),
+            SubscriptionFormat::Clash => self.to_clash_yaml(),
+            SubscriptionFormat::SingBox => self.to_singbox_json(),
+        }
+    }
+}
+
+/// Loads the subscription for `sub_id`, or `None` if no client uses that ID.
+///
+/// Traffic totals cover every client of the subscription, while only enabled clients
+/// of enabled inbounds that are within quota and not expired contribute nodes.
+pub async fn get_subscription(
+    pool: &SqlitePool,
+    sub_id: &str,
+    address: &str,
+) -> ApiResult<Option<Subscription>> {
+    let clients =
+        client_service::get_clients_by_sub_id(&mut *pool.acquire().await?, sub_id).await?;
+    if clients.is_empty() {
+        return Ok(None);
+    }
+
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let mut subscription = Subscription {
+        nodes: Vec::new(),
+        upload: 0,
+        download: 0,
+        total: 0,
+        expire: 0,
+    };
+    let mut unlimited = false;
+
+    for client in &clients {
+        subscription.upload += client.up;
+        subscription.download += client.down;
+        subscription.total += client.total;
+        unlimited |= client.total == 0;
+        if client.expiry > 0 {
+            let expire = client.expiry / 1000;
+            if subscription.expire == 0 || expire < subscription.expire {
+                subscription.expire = expire;
+            }
+        }
+
+        if !client.is_active(now_ms) {
+            continue;
+        }
+
+        let inbound = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE id = ?")
+            .bind(&client.inbound_id)
+            .fetch_optional(pool)
+            .await?;
+        let Some(inbound) = inbound.filter(|i| i.enable) else {
+            continue;
+        };
+
+        if let Some(node) = ProxyNode::from_inbound(&inbound, client, address) {
+            subscription.nodes.push(node);
+        }
+    }
+
+    if unlimited {
+        subscription.total = 0;
+    }
+
+    Ok(Some(subscription))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            SubscriptionFormat::detect(Some("clash"), Some("v2rayN/6.0")),
+            SubscriptionFormat::Clash
+        );
+        assert_eq!(
+            SubscriptionFormat::detect(Some("sing-box"), None),
+            SubscriptionFormat::SingBox
+        );
+        assert_eq!(
+            SubscriptionFormat::detect(Some("base64"), Some("clash-verge/v1.3.8")),
+            SubscriptionFormat::Base64
+        );
+        assert_eq!(
+            SubscriptionFormat::detect(None, Some("clash.meta/v1.18.0")),
+            SubscriptionFormat::Clash
+        );
+        assert_eq!(
+            SubscriptionFormat::detect(None, Some("SFA/1.9.0 (sing-box 1.9.0)")),
+            SubscriptionFormat::SingBox
+        );
+        assert_eq!(
+            SubscriptionFormat::detect(None, Some("v2rayN/6.42")),
+            SubscriptionFormat::Base64
+        );
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let subscription = Subscription {
+            nodes: vec![
+                ProxyNode {
+                    remark: "HK-alice".to_string(),
+                    address: "example.com".to_string(),
+                    port: 443,
+                    uuid: "1b5a2d4e-6b7e-4b43-9d1b-4b3c5a6d7e8f".to_string(),
+                    flow: "xtls-rprx-vision".to_string(),
+                    network: "raw".to_string(),
+                    security: "reality".to_string(),
+                    sni: "www.microsoft.com".to_string(),
+                    fingerprint: "chrome".to_string(),
+                    public_key: "PUBKEY".to_string(),
+                    short_id: "ab12".to_string(),
+                    ..Default::default()
+                },
+                ProxyNode {
+                    remark: "JP-alice".to_string(),
+                    address: "example.com".to_string(),
+                    port: 8443,
+                    uuid: "1b5a2d4e-6b7e-4b43-9d1b-4b3c5a6d7e8f".to_string(),
+                    network: "xhttp".to_string(),
+                    security: "none".to_string(),
+                    path: "/xh".to_string(),
+                    ..Default::default()
+                },
+            ],
+            upload: 0,
+            download: 0,
+            total: 0,
+            expire: 0,
+        };
+
+        let clash: serde_yaml::Value =
+            serde_yaml::from_str(&subscription.to_clash_yaml().unwrap()).unwrap();
+        let proxies = clash["proxies"].as_sequence().unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0]["network"], "tcp");
+        assert_eq!(proxies[0]["reality-opts"]["public-key"], "PUBKEY");
+        assert_eq!(proxies[0]["client-fingerprint"], "chrome");
+        assert_eq!(proxies[1]["xhttp-opts"]["path"], "/xh");
+
+        let singbox: serde_json::Value =
+            serde_json::from_str(&subscription.to_singbox_json().unwrap()).unwrap();
+        let outbounds = singbox["outbounds"].as_array().unwrap();
+        // selector + the reality node + direct; the xhttp node is not supported by sing-box
+        assert_eq!(outbounds.len(), 3);
+        assert_eq!(
+            outbounds[0]["outbounds"],
+            serde_json::json!(["HK-alice", "direct"])
+        );
+        assert_eq!(outbounds[1]["tls"]["reality"]["short_id"], "ab12");
+        assert_eq!(outbounds[1]["server_port"], 443);
+    }
+}
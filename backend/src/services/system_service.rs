@@ -1,4 +1,5 @@
 use crate::errors::ApiResult;
+use crate::services::xray_process::XraySupervisor;
 use chrono;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
@@ -10,7 +11,7 @@ pub struct SystemMonitor {
     sys: System,
     disks: Disks,
     networks: Networks,
-    mock_running: bool,
+    xray: XraySupervisor,
     start_time: std::time::Instant,
 }
 
@@ -27,7 +28,7 @@ impl SystemMonitor {
             sys,
             disks,
             networks,
-            mock_running: true,
+            xray: XraySupervisor::xray(),
             start_time: std::time::Instant::now(),
         }
     }
@@ -66,17 +67,17 @@ impl SystemMonitor {
         let load_avg = System::load_average();
         let load = vec![load_avg.one, load_avg.five, load_avg.fifteen];
 
-        let xray_state_str = if self.is_xray_running() {
-            "running"
-        } else {
-            "stopped"
-        };
-
+        let process = self.xray.status();
         let xray_version = get_xray_version().unwrap_or_else(|| "Unknown".to_string());
 
         let xray = XrayStatus {
-            state: xray_state_str.to_string(),
+            state: process.state.as_str().to_string(),
             version: xray_version,
+            pid: process.pid,
+            exit_code: process.exit_code,
+            uptime: process.uptime(),
+            restart_count: process.restart_count,
+            error: process.last_error,
         };
 
         let (tcp_count, udp_count) = get_connection_counts();
@@ -123,17 +124,8 @@ impl SystemMonitor {
         })
     }
 
-    fn is_xray_running(&self) -> bool {
-        #[cfg(target_os = "linux")]
-        {
-            return self.mock_running;
-        }
-        #[cfg(not(target_os = "linux"))]
-        return self.mock_running;
-    }
-
-    pub fn set_mock_running(&mut self, running: bool) {
-        self.mock_running = running;
+    pub fn xray(&self) -> XraySupervisor {
+        self.xray.clone()
     }
 }
 
@@ -185,6 +177,13 @@ pub struct DiskStats {
 pub struct XrayStatus {
     pub state: String,
     pub version: String,
+    pub pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    /// Seconds the current Xray process has been running.
+    pub uptime: u64,
+    pub restart_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -284,91 +283,25 @@ fn get_fallback_logs() -> ApiResult<Vec<String>> {
     ])
 }
 
+fn supervisor(monitor: &SharedMonitor) -> ApiResult<XraySupervisor> {
+    let m = monitor.lock().map_err(|e| {
+        crate::errors::ApiError::SystemError(format!("Monitor lock poisoned: {}", e))
+    })?;
+    Ok(m.xray())
+}
+
 pub async fn stop_xray(monitor: SharedMonitor) -> ApiResult<()> {
     tracing::info!("Received request to stop Xray service...");
-
-    {
-        let mut m = monitor.lock().map_err(|e| {
-            crate::errors::ApiError::SystemError(format!("Monitor lock poisoned: {}", e))
-        })?;
-        m.set_mock_running(false);
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        let _ = std::process::Command::new("pkill")
-            .arg("-f")
-            .arg("xray")
-            .output();
-
-        let _ = std::process::Command::new("killall").arg("xray").output();
-    }
-
-    Ok(())
+    supervisor(&monitor)?.stop().await
 }
 
 pub async fn start_xray(monitor: SharedMonitor) -> ApiResult<()> {
     tracing::info!("Received request to start Xray service...");
-
-    {
-        let mut m = monitor.lock().map_err(|e| {
-            crate::errors::ApiError::SystemError(format!("Monitor lock poisoned: {}", e))
-        })?;
-        m.set_mock_running(true);
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        let bin_path_str =
-            std::env::var("XRAY_BIN_PATH").unwrap_or("/usr/local/bin/xray".to_string());
-        let config_path_str =
-            std::env::var("XRAY_CONFIG_PATH").unwrap_or("/etc/x-ui/xray.json".to_string());
-
-        let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-        let log_dir = cwd.join("logs");
-        if !log_dir.exists() {
-            let _ = std::fs::create_dir_all(&log_dir);
-        }
-
-        let stdout_file = std::fs::File::create(log_dir.join("access.log")).map_err(|e| {
-            crate::errors::ApiError::SystemError(format!("Failed to create stdout log: {}", e))
-        })?;
-        let stderr_file = std::fs::File::create(log_dir.join("error.log")).map_err(|e| {
-            crate::errors::ApiError::SystemError(format!("Failed to create stderr log: {}", e))
-        })?;
-
-        let child = std::process::Command::new(&bin_path_str)
-            .arg("-c")
-            .arg(&config_path_str)
-            .env("GOMEMLIMIT", "150MiB")
-            .env("GOGC", "50")
-            .stdout(stdout_file)
-            .stderr(stderr_file)
-            .spawn();
-
-        match child {
-            Ok(_) => tracing::info!(
-                "Xray process started directly: {} -c {}",
-                bin_path_str,
-                config_path_str
-            ),
-            Err(e) => {
-                tracing::error!("Failed to start xray process: {}", e);
-                return Err(crate::errors::ApiError::SystemError(format!(
-                    "Failed to start xray: {}",
-                    e
-                )));
-            }
-        }
-    }
-
-    Ok(())
+    supervisor(&monitor)?.start().await
 }
 
 pub async fn restart_xray(monitor: SharedMonitor) -> ApiResult<()> {
-    stop_xray(monitor.clone()).await?;
-    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-    start_xray(monitor).await
+    supervisor(&monitor)?.restart().await
 }
 
 pub async fn restart_panel() -> ApiResult<()> {
@@ -445,8 +378,7 @@ pub async fn update_xray(monitor: SharedMonitor, version: String) -> ApiResult<(
             crate::errors::ApiError::SystemError("xray binary not found in zip".to_string())
         })?;
 
-        let bin_path_str =
-            std::env::var("XRAY_BIN_PATH").unwrap_or("/usr/local/bin/xray".to_string());
+        let bin_path_str = crate::services::xray_process::bin_path();
         let bin_path = std::path::Path::new(&bin_path_str);
         tracing::info!("Xray binary will be updated at: {}", bin_path.display());
 
@@ -545,7 +477,7 @@ fn get_connection_counts() -> (usize, usize) {
 }
 
 fn get_xray_version() -> Option<String> {
-    let bin_path_str = std::env::var("XRAY_BIN_PATH").unwrap_or("/usr/local/bin/xray".to_string());
+    let bin_path_str = crate::services::xray_process::bin_path();
 
     let output = std::process::Command::new(bin_path_str)
         .arg("-version")
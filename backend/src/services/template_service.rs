@@ -0,0 +1,36 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::services::xray_settings_service;
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+const TEMPLATE_KEY: &str = "template";
+const MAX_TEMPLATE_SIZE: usize = 1024 * 1024;
+
+/// Returns the stored config template, an empty object when none was saved.
+pub async fn get_template(conn: &mut SqliteConnection) -> ApiResult<Value> {
+    let template = xray_settings_service::get_setting(conn, TEMPLATE_KEY)
+        .await?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_else(|| Value::Object(Default::default()));
+    Ok(template)
+}
+
+pub async fn save_template(conn: &mut SqliteConnection, template: &Value) -> ApiResult<()> {
+    validate_template(template)?;
+    xray_settings_service::set_setting(conn, TEMPLATE_KEY, &template.to_string()).await
+}
+
+pub fn validate_template(template: &Value) -> ApiResult<()> {
+    if !template.is_object() {
+        return Err(ApiError::BadRequest(
+            "Config template must be a JSON object".to_string(),
+        ));
+    }
+    if template.to_string().len() > MAX_TEMPLATE_SIZE {
+        return Err(ApiError::BadRequest(format!(
+            "Config template must be smaller than {} KB",
+            MAX_TEMPLATE_SIZE / 1024
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,370 @@
+use crate::errors::{ApiError, ApiResult};
+use crate::models::traffic_history::{
+    HistoryPeriod, HistoryQuery, HistorySubject, TrafficDelta, TrafficPoint, TrafficSeries,
+};
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+use sqlx::{SqliteConnection, SqlitePool};
+use tokio::time::{interval, Duration};
+
+const HOUR_MS: i64 = 3_600_000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+/// Upper bound on the buckets one query may return.
+const MAX_POINTS: i64 = 2_000;
+/// Default retention of hourly buckets, overridable with `TRAFFIC_HISTORY_HOURLY_DAYS`.
+const DEFAULT_HOURLY_DAYS: i64 = 7;
+/// Default retention of daily buckets, overridable with `TRAFFIC_HISTORY_DAILY_DAYS`.
+const DEFAULT_DAILY_DAYS: i64 = 365;
+
+/// Prunes expired buckets once at startup and then every hour.
+pub fn start_pruning_task(pool: SqlitePool) {
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(3600));
+        loop {
+            ticker.tick().await;
+            let now_ms = chrono::Utc::now().timestamp_millis();
+            if let Err(e) = prune(&pool, now_ms).await {
+                tracing::warn!("Failed to prune traffic history: {:?}", e);
+            }
+        }
+    });
+    tracing::info!(
+        "Traffic history pruning task started (hourly kept {} days, daily kept {} days)",
+        retention_days("TRAFFIC_HISTORY_HOURLY_DAYS", DEFAULT_HOURLY_DAYS),
+        retention_days("TRAFFIC_HISTORY_DAILY_DAYS", DEFAULT_DAILY_DAYS)
+    );
+}
+
+/// Adds the deltas of one stats poll to their hourly and daily buckets. Rolling up at write
+/// time keeps both resolutions current without a separate aggregation pass. Runs on the
+/// caller's connection so it can share the transaction that updates the counters.
+pub async fn record(
+    conn: &mut SqliteConnection,
+    deltas: &[TrafficDelta],
+    now_ms: i64,
+) -> ApiResult<()> {
+    for delta in deltas {
+        for period in [HistoryPeriod::Hour, HistoryPeriod::Day] {
+            sqlx::query(
+                r#"
+                INSERT INTO traffic_history (subject, subject_id, period, bucket, up, down)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT (subject, subject_id, period, bucket)
+                DO UPDATE SET up = up + excluded.up, down = down + excluded.down
+                "#,
+            )
+            .bind(delta.subject.as_str())
+            .bind(&delta.subject_id)
+            .bind(period.as_str())
+            .bind(bucket_start(period, now_ms))
+            .bind(delta.up)
+            .bind(delta.down)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// The traffic of one inbound or client over the range in `query`, one point per bucket.
+pub async fn get_series(
+    pool: &SqlitePool,
+    subject: HistorySubject,
+    subject_id: &str,
+    query: &HistoryQuery,
+) -> ApiResult<TrafficSeries> {
+    let to = query
+        .to
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    if query.from.is_some_and(|from| from < 0) {
+        return Err(ApiError::BadRequest(
+            "History range cannot start before 1970".to_string(),
+        ));
+    }
+    let period = match (query.period, query.from) {
+        (Some(period), _) => period,
+        (None, Some(from)) if to.saturating_sub(from) > 2 * DAY_MS => HistoryPeriod::Day,
+        _ => HistoryPeriod::Hour,
+    };
+    let from = query.from.unwrap_or(match period {
+        HistoryPeriod::Hour => to.saturating_sub(DAY_MS).max(0),
+        HistoryPeriod::Day => to.saturating_sub(30 * DAY_MS).max(0),
+    });
+
+    if from >= to {
+        return Err(ApiError::BadRequest(
+            "History range start must be before its end".to_string(),
+        ));
+    }
+    let bucket_ms = match period {
+        HistoryPeriod::Hour => HOUR_MS,
+        HistoryPeriod::Day => DAY_MS,
+    };
+    if (to - from) / bucket_ms > MAX_POINTS {
+        return Err(ApiError::BadRequest(format!(
+            "History range spans more than {} {} buckets; use a shorter range or a larger period",
+            MAX_POINTS,
+            period.as_str()
+        )));
+    }
+
+    let first = bucket_start(period, from);
+    let rows: Vec<(i64, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT bucket, up, down FROM traffic_history
+        WHERE subject = ? AND subject_id = ? AND period = ? AND bucket >= ? AND bucket <= ?
+        ORDER BY bucket ASC
+        "#,
+    )
+    .bind(subject.as_str())
+    .bind(subject_id)
+    .bind(period.as_str())
+    .bind(first)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let mut rows = rows.into_iter().peekable();
+    let mut points = Vec::new();
+    let mut next = Some(first);
+    while let Some(time) = next.filter(|time| *time <= to) {
+        let mut point = TrafficPoint {
+            time,
+            up: 0,
+            down: 0,
+        };
+        while let Some(&(bucket, up, down)) = rows.peek() {
+            if bucket > time {
+                break;
+            }
+            point.up += up;
+            point.down += down;
+            rows.next();
+        }
+        points.push(point);
+        next = next_bucket(period, time);
+    }
+
+    Ok(TrafficSeries {
+        period,
+        from,
+        to,
+        up: points.iter().map(|p| p.up).sum(),
+        down: points.iter().map(|p| p.down).sum(),
+        points,
+    })
+}
+
+/// Drops hourly and daily buckets past their retention, and buckets of inbounds or clients
+/// that no longer exist.
+pub async fn prune(pool: &SqlitePool, now_ms: i64) -> ApiResult<()> {
+    let hourly_cutoff =
+        now_ms - retention_days("TRAFFIC_HISTORY_HOURLY_DAYS", DEFAULT_HOURLY_DAYS) * DAY_MS;
+    let daily_cutoff =
+        now_ms - retention_days("TRAFFIC_HISTORY_DAILY_DAYS", DEFAULT_DAILY_DAYS) * DAY_MS;
+
+    let expired = sqlx::query(
+        "DELETE FROM traffic_history WHERE (period = 'hour' AND bucket < ?) OR (period = 'day' AND bucket < ?)",
+    )
+    .bind(hourly_cutoff)
+    .bind(daily_cutoff)
+    .execute(pool)
+    .await?;
+
+    let orphaned = sqlx::query(
+        r#"
+        DELETE FROM traffic_history
+        WHERE (subject = 'inbound' AND subject_id NOT IN (SELECT id FROM inbounds))
+           OR (subject = 'client' AND subject_id NOT IN (SELECT CAST(id AS TEXT) FROM clients))
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let removed = expired.rows_affected() + orphaned.rows_affected();
+    if removed > 0 {
+        tracing::info!("Pruned {} traffic history buckets", removed);
+    }
+    Ok(())
+}
+
+fn retention_days(var: &str, default: i64) -> i64 {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(default)
+}
+
+/// Start of the bucket containing `ts_ms`.
+fn bucket_start(period: HistoryPeriod, ts_ms: i64) -> i64 {
+    match period {
+        HistoryPeriod::Hour => ts_ms - ts_ms.rem_euclid(HOUR_MS),
+        HistoryPeriod::Day => match Local.timestamp_millis_opt(ts_ms).single() {
+            Some(local) => local_midnight(local.date_naive()),
+            None => ts_ms - ts_ms.rem_euclid(DAY_MS),
+        },
+    }
+}
+
+/// Start of the bucket after the one starting at `bucket`, `None` past the end of time. Days
+/// are stepped by date so daylight saving changes yield 23 or 25 hour buckets.
+fn next_bucket(period: HistoryPeriod, bucket: i64) -> Option<i64> {
+    match period {
+        HistoryPeriod::Hour => bucket.checked_add(HOUR_MS),
+        HistoryPeriod::Day => Local
+            .timestamp_millis_opt(bucket)
+            .single()
+            .and_then(|local| local.date_naive().succ_opt())
+            .map(local_midnight)
+            .filter(|next| *next > bucket)
+            .or_else(|| bucket.checked_add(DAY_MS)),
+    }
+}
+
+fn local_midnight(date: NaiveDate) -> i64 {
+    let midnight = date.and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|d| d.timestamp_millis())
+        .unwrap_or_else(|| midnight.and_utc().timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    async fn record_at(pool: &SqlitePool, deltas: &[TrafficDelta], now_ms: i64) {
+        let mut conn = pool.acquire().await.unwrap();
+        record(&mut conn, deltas, now_ms).await.unwrap();
+    }
+
+    fn delta(subject_id: &str, up: i64, down: i64) -> TrafficDelta {
+        TrafficDelta {
+            subject: HistorySubject::Inbound,
+            subject_id: subject_id.to_string(),
+            up,
+            down,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_query_hourly_series() {
+        let pool = test_pool().await;
+        let hour = 480_000 * HOUR_MS;
+
+        record_at(&pool, &[delta("in-1", 10, 20)], hour + 60_000).await;
+        record_at(
+            &pool,
+            &[delta("in-1", 5, 5), delta("in-2", 1, 1)],
+            hour + 120_000,
+        )
+        .await;
+        record_at(&pool, &[delta("in-1", 7, 0)], hour + 2 * HOUR_MS + 1).await;
+
+        let query = HistoryQuery {
+            from: Some(hour),
+            to: Some(hour + 3 * HOUR_MS - 1),
+            period: None,
+        };
+        let series = get_series(&pool, HistorySubject::Inbound, "in-1", &query)
+            .await
+            .unwrap();
+
+        assert_eq!(series.period, HistoryPeriod::Hour);
+        assert_eq!(
+            series.points,
+            vec![
+                TrafficPoint {
+                    time: hour,
+                    up: 15,
+                    down: 25
+                },
+                TrafficPoint {
+                    time: hour + HOUR_MS,
+                    up: 0,
+                    down: 0
+                },
+                TrafficPoint {
+                    time: hour + 2 * HOUR_MS,
+                    up: 7,
+                    down: 0
+                },
+            ]
+        );
+        assert_eq!((series.up, series.down), (22, 25));
+
+        let daily = HistoryQuery {
+            from: Some(hour),
+            to: Some(hour + 3 * HOUR_MS - 1),
+            period: Some(HistoryPeriod::Day),
+        };
+        let series = get_series(&pool, HistorySubject::Inbound, "in-1", &daily)
+            .await
+            .unwrap();
+        assert_eq!((series.up, series.down), (22, 25));
+    }
+
+    #[tokio::test]
+    async fn test_range_validation_and_pruning() {
+        let pool = test_pool().await;
+        let now = 480_000 * HOUR_MS;
+
+        let backwards = HistoryQuery {
+            from: Some(now),
+            to: Some(now - 1),
+            period: None,
+        };
+        assert!(get_series(&pool, HistorySubject::Client, "1", &backwards)
+            .await
+            .is_err());
+        let too_long = HistoryQuery {
+            from: Some(now - 365 * DAY_MS),
+            to: Some(now),
+            period: Some(HistoryPeriod::Hour),
+        };
+        assert!(get_series(&pool, HistorySubject::Client, "1", &too_long)
+            .await
+            .is_err());
+        for (from, to) in [
+            (Some(0), Some(i64::MIN)),
+            (Some(-1), None),
+            (None, Some(i64::MAX)),
+        ] {
+            let extreme = HistoryQuery {
+                from,
+                to,
+                period: None,
+            };
+            let series = get_series(&pool, HistorySubject::Client, "1", &extreme).await;
+            assert_eq!(series.is_ok(), from.is_none());
+        }
+
+        sqlx::query(
+            "INSERT INTO inbounds (id, remark, protocol, port) VALUES ('in-1', 'r', 'vless', 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        record_at(&pool, &[delta("in-1", 1, 1)], now - 30 * DAY_MS).await;
+        record_at(&pool, &[delta("in-1", 1, 1), delta("gone", 1, 1)], now).await;
+
+        prune(&pool, now).await.unwrap();
+
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT subject_id, period FROM traffic_history ORDER BY bucket, period",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("in-1".to_string(), "day".to_string()),
+                ("in-1".to_string(), "day".to_string()),
+                ("in-1".to_string(), "hour".to_string()),
+            ]
+        );
+    }
+}
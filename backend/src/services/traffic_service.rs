@@ -1,9 +1,16 @@
 use crate::errors::ApiResult;
-use crate::models::inbound::Inbound;
+use crate::models::client::Client;
+use crate::models::config_revision::ConfigTrigger;
+use crate::models::inbound::{DisabledReason, Inbound, TrafficReset};
+use crate::models::traffic_history::{HistorySubject, TrafficDelta};
+use crate::services::inbound_service;
 use crate::services::system_service::SharedMonitor;
+use crate::services::traffic_history_service;
+use crate::services::xray_api::XrayApiClient;
 use crate::services::xray_service;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
 use sqlx::SqlitePool;
-use tokio::process::Command;
+use std::collections::HashMap;
 use tokio::time::{interval, Duration};
 
 pub fn start_traffic_stats_task(pool: SqlitePool, monitor: SharedMonitor) {
@@ -20,155 +27,660 @@ pub fn start_traffic_stats_task(pool: SqlitePool, monitor: SharedMonitor) {
 }
 
 async fn update_traffic_stats(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResult<()> {
-    let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE enable = 1")
-        .fetch_all(pool)
+    if reset_scheduled(pool).await? {
+        reapply_config(pool, monitor.clone(), "Scheduled traffic reset").await;
+    }
+
+    let mut needs_reapply = disable_expired(pool).await?;
+
+    let (enabled,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM inbounds WHERE enable = 1")
+        .fetch_one(pool)
         .await?;
 
-    if inbounds.is_empty() {
-        return Ok(());
+    // A failed poll is simply skipped: Xray keeps counting, so the traffic is picked up by
+    // the next successful one.
+    if enabled > 0 {
+        match query_xray_counters().await {
+            Ok(counters) => {
+                tracing::debug!(
+                    "Traffic stats query returned {} entries",
+                    counters.values.len()
+                );
+                if counters.values.is_empty() {
+                    tracing::warn!(
+                        "No stats retrieved from Xray API despite having {} enabled inbounds",
+                        enabled
+                    );
+                }
+                needs_reapply |= account_traffic(pool, &counters).await?;
+            }
+            Err(e) => tracing::warn!("Failed to query Xray stats: {}", e),
+        }
     }
 
-    let xray_bin = std::env::var("XRAY_BIN_PATH").unwrap_or_else(|_| "./bin/xray".to_string());
-    let mut needs_reapply = false;
+    if needs_reapply {
+        reapply_config(pool, monitor, "Disable expired or exhausted entries").await;
+    }
 
-    let stats_map = query_all_xray_stats(&xray_bin).await.unwrap_or_default();
+    Ok(())
+}
 
-    tracing::info!("Traffic stats query returned {} entries", stats_map.len());
-    if stats_map.is_empty() && !inbounds.is_empty() {
-        tracing::warn!(
-            "No stats retrieved from Xray API despite having {} enabled inbounds",
-            inbounds.len()
-        );
-    }
+/// Adds the traffic counted since the previous poll to the inbounds and clients, disables
+/// those that crossed their quota, records the history and advances the cursors, all in one
+/// transaction. If any write fails the cursors stay put and the next poll counts the same
+/// bytes again. Returns whether something was disabled.
+async fn account_traffic(pool: &SqlitePool, counters: &XrayCounters) -> ApiResult<bool> {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let quota = DisabledReason::QuotaExceeded.as_str();
+    let mut tx = pool.begin().await?;
+
+    let cursors: HashMap<String, Cursor> = sqlx::query_as::<_, (String, i64, Option<i64>)>(
+        "SELECT name, value, xray_started FROM traffic_cursors",
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|(name, value, xray_started)| {
+        (
+            name,
+            Cursor {
+                value,
+                xray_started,
+            },
+        )
+    })
+    .collect();
+
+    let deltas: HashMap<String, i64> = counters
+        .values
+        .iter()
+        .map(|(name, &value)| {
+            let delta = counter_delta(cursors.get(name).copied(), value, counters.started);
+            (name.clone(), delta)
+        })
+        .collect();
+
+    let mut disabled = false;
+    let mut history = Vec::new();
 
+    let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds")
+        .fetch_all(&mut *tx)
+        .await?;
     for inbound in inbounds {
         let tag = inbound
             .tag
             .clone()
             .unwrap_or_else(|| format!("inbound-{}", inbound.id));
+        let counter = |direction: &str| {
+            let name = format!("inbound>>>{}>>>traffic>>>{}", tag, direction);
+            deltas.get(&name).copied().unwrap_or(0)
+        };
+        let (uplink, downlink) = (counter("uplink"), counter("downlink"));
+        if uplink == 0 && downlink == 0 {
+            continue;
+        }
 
-        let up_key = format!("inbound>>>{}>>>traffic>>>uplink", tag);
-        let down_key = format!("inbound>>>{}>>>traffic>>>downlink", tag);
+        let exceeded = inbound.enable
+            && inbound.total > 0
+            && inbound.up + inbound.down + uplink + downlink >= inbound.total;
+        if exceeded {
+            disabled = true;
+            tracing::info!("Node {} reached traffic quota, disabling.", inbound.remark);
+        }
 
-        let uplink = stats_map.get(&up_key).cloned().unwrap_or(0);
-        let downlink = stats_map.get(&down_key).cloned().unwrap_or(0);
+        sqlx::query(
+            r#"
+            UPDATE inbounds
+            SET up = up + ?, down = down + ?,
+                enable = CASE WHEN ? THEN 0 ELSE enable END,
+                disabled_reason = CASE WHEN ? THEN ? ELSE disabled_reason END
+            WHERE id = ?
+            "#,
+        )
+        .bind(uplink)
+        .bind(downlink)
+        .bind(exceeded)
+        .bind(exceeded)
+        .bind(quota)
+        .bind(&inbound.id)
+        .execute(&mut *tx)
+        .await?;
 
-        tracing::info!(
-            "Node '{}' (tag={}): looking for keys '{}' and '{}', found uplink={}, downlink={}",
+        tracing::debug!(
+            "Node {} ({}): +{} up, +{} down",
             inbound.remark,
             tag,
-            up_key,
-            down_key,
             uplink,
             downlink
         );
+        history.push(TrafficDelta {
+            subject: HistorySubject::Inbound,
+            subject_id: inbound.id,
+            up: uplink,
+            down: downlink,
+        });
+    }
 
-        if uplink > 0 || downlink > 0 {
-            let new_up = inbound.up + uplink;
-            let new_down = inbound.down + downlink;
-            let mut enable = 1;
+    let user_traffic = collect_user_traffic(&deltas);
+    if !user_traffic.is_empty() {
+        let clients = sqlx::query_as::<_, Client>("SELECT * FROM clients")
+            .fetch_all(&mut *tx)
+            .await?;
 
-            if inbound.total > 0 && (new_up + new_down) >= inbound.total {
-                enable = 0;
-                needs_reapply = true;
-                tracing::info!("Node {} reached traffic quota, disabling.", inbound.remark);
+        for client in clients {
+            let Some(&(uplink, downlink)) = user_traffic.get(&client.email) else {
+                continue;
+            };
+            if uplink == 0 && downlink == 0 {
+                continue;
             }
 
-            sqlx::query("UPDATE inbounds SET up = ?, down = ?, enable = ? WHERE id = ?")
-                .bind(new_up)
-                .bind(new_down)
-                .bind(enable)
-                .bind(&inbound.id)
-                .execute(pool)
-                .await?;
+            let exceeded = client.enable
+                && client.total > 0
+                && client.up + client.down + uplink + downlink >= client.total;
+            if exceeded {
+                disabled = true;
+                tracing::info!("Client {} reached traffic quota, disabling.", client.email);
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE clients
+                SET up = up + ?, down = down + ?,
+                    enable = CASE WHEN ? THEN 0 ELSE enable END,
+                    disabled_reason = CASE WHEN ? THEN ? ELSE disabled_reason END
+                WHERE id = ?
+                "#,
+            )
+            .bind(uplink)
+            .bind(downlink)
+            .bind(exceeded)
+            .bind(exceeded)
+            .bind(quota)
+            .bind(client.id)
+            .execute(&mut *tx)
+            .await?;
 
             tracing::debug!(
-                "Node {} ({}): up={}, down={}, total={}",
-                inbound.remark,
-                tag,
-                new_up,
-                new_down,
-                inbound.total
+                "Client {}: +{} up, +{} down",
+                client.email,
+                uplink,
+                downlink
             );
+            history.push(TrafficDelta {
+                subject: HistorySubject::Client,
+                subject_id: client.id.to_string(),
+                up: uplink,
+                down: downlink,
+            });
         }
     }
 
-    if needs_reapply {
-        if let Err(e) = xray_service::apply_config(pool, monitor).await {
-            tracing::error!("Failed to reapply config after quota reached: {}", e);
+    traffic_history_service::record(&mut tx, &history, now_ms).await?;
+
+    for (name, &value) in &counters.values {
+        let cursor = Cursor {
+            value,
+            xray_started: counters.started,
+        };
+        if cursors.get(name) == Some(&cursor) {
+            continue;
         }
+        sqlx::query(
+            r#"
+            INSERT INTO traffic_cursors (name, value, xray_started) VALUES (?, ?, ?)
+            ON CONFLICT (name) DO UPDATE SET value = excluded.value, xray_started = excluded.xray_started
+            "#,
+        )
+        .bind(name)
+        .bind(cursor.value)
+        .bind(cursor.xray_started)
+        .execute(&mut *tx)
+        .await?;
+    }
+    // Counters of removed inbounds and users are no longer reported.
+    for name in cursors.keys().filter(|n| !counters.values.contains_key(*n)) {
+        sqlx::query("DELETE FROM traffic_cursors WHERE name = ?")
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
     }
 
-    Ok(())
+    tx.commit().await?;
+    Ok(disabled)
 }
 
-async fn query_all_xray_stats(xray_bin: &str) -> ApiResult<std::collections::HashMap<String, i64>> {
-    let output = Command::new(xray_bin)
-        .arg("api")
-        .arg("statsquery")
-        .arg("--server=127.0.0.1:10085")
-        .arg("--pattern=")
-        .arg("--reset=true")
-        .output()
-        .await
-        .map_err(|e| {
-            crate::errors::ApiError::SystemError(format!("Xray API call failed: {}", e))
-        })?;
-
-    if !output.status.success() {
-        let err = String::from_utf8_lossy(&output.stderr);
-        tracing::error!("Xray API error: {}", err);
-        return Ok(std::collections::HashMap::new());
+async fn reapply_config(pool: &SqlitePool, monitor: SharedMonitor, reason: &str) {
+    let trigger = ConfigTrigger::system(reason);
+    if let Err(e) = xray_service::apply_config(pool, monitor, trigger).await {
+        tracing::error!("Failed to reapply config ({}): {}", reason, e);
     }
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    let preview = if stdout.len() > 500 {
-        &stdout[..500]
-    } else {
-        &stdout[..]
-    };
-    tracing::info!("Xray API raw output preview: {}", preview);
+/// Resets the traffic of every inbound whose schedule came due since its last reset.
+/// Returns whether an inbound or client was re-enabled, so the caller can reapply the config.
+async fn reset_scheduled(pool: &SqlitePool) -> ApiResult<bool> {
+    let inbounds =
+        sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE traffic_reset != 'never'")
+            .fetch_all(pool)
+            .await?;
+    let now = Local::now().naive_local();
 
-    let mut stats = std::collections::HashMap::new();
-    let mut current_name: Option<String> = None;
-    let mut current_value: Option<i64> = None;
+    let mut reenabled = false;
+    for inbound in inbounds {
+        let Some(schedule) = TrafficReset::parse(&inbound.traffic_reset) else {
+            continue;
+        };
+        let due = latest_reset(schedule, inbound.traffic_reset_day, now)
+            .and_then(|due| due.and_local_timezone(Local).earliest())
+            .map(|due| due.timestamp_millis());
+        if due.is_none_or(|due| inbound.last_traffic_reset >= due) {
+            continue;
+        }
 
-    for line in stdout.lines() {
-        let line = line.trim();
+        tracing::info!(
+            "Resetting traffic of inbound {} on its {} schedule (up={}, down={})",
+            inbound.remark,
+            schedule.as_str(),
+            inbound.up,
+            inbound.down
+        );
+        reenabled |=
+            inbound_service::reset_inbound_traffic(&mut *pool.acquire().await?, &inbound.id)
+                .await?;
+    }
+    Ok(reenabled)
+}
 
-        if line.starts_with("}") {
-            if let (Some(name), Some(value)) = (&current_name, current_value) {
-                stats.insert(name.clone(), value);
+/// The most recent local midnight at or before `now` on which `schedule` resets. A monthly
+/// reset on a day the month does not have runs on its last day instead.
+fn latest_reset(schedule: TrafficReset, day: i64, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let today = now.date();
+    let date = match schedule {
+        TrafficReset::Never => return None,
+        TrafficReset::Daily => today,
+        TrafficReset::Weekly => {
+            let target = day.clamp(1, 7) - 1;
+            let current = i64::from(today.weekday().num_days_from_monday());
+            today - chrono::Duration::days((current - target).rem_euclid(7))
+        }
+        TrafficReset::Monthly => {
+            let this_month = reset_date(today.year(), today.month(), day)?;
+            if this_month <= today {
+                this_month
+            } else if today.month() == 1 {
+                reset_date(today.year() - 1, 12, day)?
+            } else {
+                reset_date(today.year(), today.month() - 1, day)?
             }
-            current_name = None;
-            current_value = None;
         }
+    };
+    Some(date.and_time(NaiveTime::MIN))
+}
+
+/// Day `day` of the given month, or its last day when the month is shorter.
+fn reset_date(year: i32, month: u32, day: i64) -> Option<NaiveDate> {
+    let day = day.clamp(1, 31) as u32;
+    (1..=day)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+}
+
+/// Disables every enabled inbound and client whose expiry time has passed.
+/// Returns whether anything changed, so the caller can reapply the config once per tick.
+async fn disable_expired(pool: &SqlitePool) -> ApiResult<bool> {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let reason = DisabledReason::Expired.as_str();
+
+    let inbounds: Vec<(String,)> = sqlx::query_as(
+        "UPDATE inbounds SET enable = 0, disabled_reason = ? WHERE enable = 1 AND expiry > 0 AND expiry <= ? RETURNING remark",
+    )
+    .bind(reason)
+    .bind(now_ms)
+    .fetch_all(pool)
+    .await?;
+
+    for (remark,) in &inbounds {
+        tracing::info!("Node {} expired, disabling.", remark);
+    }
+
+    let clients: Vec<(String,)> = sqlx::query_as(
+        "UPDATE clients SET enable = 0, disabled_reason = ? WHERE enable = 1 AND expiry > 0 AND expiry <= ? RETURNING email",
+    )
+    .bind(reason)
+    .bind(now_ms)
+    .fetch_all(pool)
+    .await?;
+
+    for (email,) in &clients {
+        tracing::info!("Client {} expired, disabling.", email);
+    }
+
+    Ok(!inbounds.is_empty() || !clients.is_empty())
+}
+
+/// A parsed Xray stats counter name, e.g. `user>>>alice@example.com>>>traffic>>>uplink`.
+#[derive(Debug, PartialEq, Eq)]
+enum StatName<'a> {
+    Inbound { tag: &'a str, uplink: bool },
+    User { email: &'a str, uplink: bool },
+    Outbound { tag: &'a str, uplink: bool },
+}
+
+fn parse_stat_name(name: &str) -> Option<StatName<'_>> {
+    let mut parts = name.split(">>>");
+    let kind = parts.next()?;
+    let subject = parts.next()?;
+    if parts.next()? != "traffic" {
+        return None;
+    }
+    let uplink = match parts.next()? {
+        "uplink" => true,
+        "downlink" => false,
+        _ => return None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
 
-        if line.contains("name") {
-            let parts: Vec<&str> = line.split('"').collect();
-            if parts.len() >= 4 {
-                current_name = Some(parts[3].to_string());
+    match kind {
+        "inbound" => Some(StatName::Inbound {
+            tag: subject,
+            uplink,
+        }),
+        "user" => Some(StatName::User {
+            email: subject,
+            uplink,
+        }),
+        "outbound" => Some(StatName::Outbound {
+            tag: subject,
+            uplink,
+        }),
+        _ => None,
+    }
+}
+
+/// Groups the `user>>>...` counters into `(uplink, downlink)` per client email.
+fn collect_user_traffic(stats: &HashMap<String, i64>) -> HashMap<String, (i64, i64)> {
+    let mut traffic = HashMap::new();
+    for (name, value) in stats {
+        if let Some(StatName::User { email, uplink }) = parse_stat_name(name) {
+            let entry: &mut (i64, i64) = traffic.entry(email.to_string()).or_default();
+            if uplink {
+                entry.0 += value;
+            } else {
+                entry.1 += value;
             }
         }
+    }
+    traffic
+}
 
-        if line.contains("value") {
-            if let Some(part) = line.split("value").nth(1) {
-                let num_str: String = part.chars().filter(|c| c.is_ascii_digit()).collect();
-                if let Ok(value) = num_str.parse::<i64>() {
-                    current_value = Some(value);
-                }
-            }
+/// Counter values read from Xray in one poll.
+struct XrayCounters {
+    values: HashMap<String, i64>,
+    /// Unix time in seconds the Xray process started, `None` if its uptime is unavailable.
+    started: Option<i64>,
+}
+
+/// The last value read of one Xray counter. Cursors are stored in the database so traffic
+/// counted by Xray while the panel was down is not lost either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    value: i64,
+    xray_started: Option<i64>,
+}
+
+/// Slack when comparing start times derived from uptimes read at different polls.
+const START_TOLERANCE_SECS: i64 = 10;
+
+/// Traffic counted since `cursor`. A counter that went backwards, or that belongs to a
+/// different Xray process, was restarted from zero, so its whole value is new traffic.
+fn counter_delta(cursor: Option<Cursor>, value: i64, started: Option<i64>) -> i64 {
+    let Some(cursor) = cursor else {
+        return value;
+    };
+    let same_process = match (cursor.xray_started, started) {
+        (Some(previous), Some(current)) => (previous - current).abs() <= START_TOLERANCE_SECS,
+        _ => true,
+    };
+    if same_process && value >= cursor.value {
+        value - cursor.value
+    } else {
+        value
+    }
+}
+
+/// Reads every traffic counter through the Xray StatsService without resetting it, along
+/// with the start time of the Xray process.
+async fn query_xray_counters() -> ApiResult<XrayCounters> {
+    let mut client = XrayApiClient::connect_local().await?;
+    let stats = client.query_stats("", false).await?;
+    let started = match client.uptime().await {
+        Ok(uptime) => Some(chrono::Utc::now().timestamp() - i64::from(uptime)),
+        Err(e) => {
+            tracing::debug!("Xray uptime unavailable, relying on counter values: {}", e);
+            None
         }
+    };
+
+    Ok(XrayCounters {
+        values: stats
+            .into_iter()
+            .filter(|stat| parse_stat_name(&stat.name).is_some())
+            .map(|stat| (stat.name, stat.value))
+            .collect(),
+        started,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat_name() {
+        assert_eq!(
+            parse_stat_name("inbound>>>inbound-1a2b>>>traffic>>>uplink"),
+            Some(StatName::Inbound {
+                tag: "inbound-1a2b",
+                uplink: true
+            })
+        );
+        assert_eq!(
+            parse_stat_name("user>>>alice@example.com>>>traffic>>>downlink"),
+            Some(StatName::User {
+                email: "alice@example.com",
+                uplink: false
+            })
+        );
+        assert_eq!(
+            parse_stat_name("outbound>>>direct>>>traffic>>>uplink"),
+            Some(StatName::Outbound {
+                tag: "direct",
+                uplink: true
+            })
+        );
+
+        assert_eq!(parse_stat_name("user>>>bob>>>online"), None);
+        assert_eq!(parse_stat_name("user>>>bob>>>traffic>>>sideways"), None);
+        assert_eq!(parse_stat_name("user>>>bob>>>traffic>>>uplink>>>x"), None);
+        assert_eq!(parse_stat_name("garbage"), None);
+    }
+
+    #[test]
+    fn test_latest_reset() {
+        let at = |y, m, d, h| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, 0, 0)
+                .unwrap()
+        };
+        // Wednesday 2025-01-15, 10:00.
+        let now = at(2025, 1, 15, 10);
+
+        assert_eq!(latest_reset(TrafficReset::Never, 1, now), None);
+        assert_eq!(
+            latest_reset(TrafficReset::Daily, 1, now),
+            Some(at(2025, 1, 15, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Weekly, 1, now),
+            Some(at(2025, 1, 13, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Weekly, 3, now),
+            Some(at(2025, 1, 15, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Weekly, 4, now),
+            Some(at(2025, 1, 9, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Monthly, 1, now),
+            Some(at(2025, 1, 1, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Monthly, 20, now),
+            Some(at(2024, 12, 20, 0))
+        );
+        // The 31st falls back to the last day of shorter months.
+        assert_eq!(
+            latest_reset(TrafficReset::Monthly, 31, at(2025, 3, 10, 0)),
+            Some(at(2025, 2, 28, 0))
+        );
+        assert_eq!(
+            latest_reset(TrafficReset::Monthly, 30, at(2024, 2, 29, 0)),
+            Some(at(2024, 2, 29, 0))
+        );
+    }
+
+    #[test]
+    fn test_counter_delta() {
+        let cursor = |value, xray_started| {
+            Some(Cursor {
+                value,
+                xray_started,
+            })
+        };
+
+        assert_eq!(counter_delta(None, 500, Some(1000)), 500);
+        assert_eq!(counter_delta(cursor(300, Some(1000)), 500, Some(1001)), 200);
+        // Went backwards: Xray restarted and counted 40 bytes since.
+        assert_eq!(counter_delta(cursor(300, Some(1000)), 40, None), 40);
+        // A newer Xray process already counted past the old value.
+        assert_eq!(counter_delta(cursor(300, Some(1000)), 500, Some(2000)), 500);
+        // Without an uptime only a decreasing counter reveals a restart.
+        assert_eq!(counter_delta(cursor(300, None), 500, Some(2000)), 200);
     }
 
-    tracing::info!(
-        "Parser completed. Total unique keys in map: {}, checking for duplicates...",
-        stats.len()
-    );
-    for (key, val) in stats.iter().take(5) {
-        tracing::info!("Sample entry: {} = {}", key, val);
+    #[tokio::test]
+    async fn test_account_traffic_survives_restarts() {
+        let pool = crate::db::test_pool().await;
+        sqlx::query(
+            "INSERT INTO inbounds (id, remark, protocol, port, tag) VALUES ('in-1', 'r', 'vless', 1, 'in-1')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let poll = |uplink: i64, started: i64| XrayCounters {
+            values: HashMap::from([("inbound>>>in-1>>>traffic>>>uplink".to_string(), uplink)]),
+            started: Some(started),
+        };
+        let up = || async {
+            let (up,): (i64,) = sqlx::query_as("SELECT up FROM inbounds")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            up
+        };
+
+        account_traffic(&pool, &poll(100, 1000)).await.unwrap();
+        assert_eq!(up().await, 100);
+        account_traffic(&pool, &poll(100, 1000)).await.unwrap();
+        assert_eq!(up().await, 100);
+        account_traffic(&pool, &poll(150, 1001)).await.unwrap();
+        assert_eq!(up().await, 150);
+        // Xray restarted and has counted 30 bytes since.
+        account_traffic(&pool, &poll(30, 5000)).await.unwrap();
+        assert_eq!(up().await, 180);
     }
 
-    Ok(stats)
+    #[tokio::test]
+    async fn test_disable_expired() {
+        let pool = crate::db::test_pool().await;
+        sqlx::query(
+            "INSERT INTO inbounds (id, remark, protocol, port, settings, expiry) VALUES \
+             ('old', 'old', 'vless', 1, '{\"decryption\":\"none\"}', 1), \
+             ('forever', 'forever', 'vless', 2, '{\"decryption\":\"none\"}', 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO clients (inbound_id, email, uuid, expiry) VALUES \
+             ('forever', 'old', 'b831381d-6324-4d53-ad4f-8cda48b30811', 1), \
+             ('forever', 'forever', 'b831381d-6324-4d53-ad4f-8cda48b30812', 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let inbounds = "SELECT remark, enable, disabled_reason FROM inbounds ORDER BY remark";
+        let clients = "SELECT email, enable, disabled_reason FROM clients ORDER BY email";
+        let state = |sql: &'static str| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_as::<_, (String, bool, Option<String>)>(sql)
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        assert!(disable_expired(&pool).await.unwrap());
+        assert!(!disable_expired(&pool).await.unwrap());
+        let expected = vec![
+            ("forever".to_string(), true, None),
+            ("old".to_string(), false, Some("expired".to_string())),
+        ];
+        assert_eq!(state(inbounds).await, expected);
+        assert_eq!(state(clients).await, expected);
+
+        let mut conn = pool.acquire().await.unwrap();
+        let req = serde_json::from_value(serde_json::json!({ "id": "old", "enable": true }));
+        inbound_service::update_inbound(&mut conn, req.unwrap())
+            .await
+            .unwrap();
+        let client: Client = sqlx::query_as("SELECT * FROM clients WHERE email = 'old'")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        let req = serde_json::from_value(serde_json::json!({ "enable": true })).unwrap();
+        crate::services::client_service::update_client(&mut conn, "forever", client.id, req)
+            .await
+            .unwrap();
+        drop(conn);
+        let enabled = vec![
+            ("forever".to_string(), true, None),
+            ("old".to_string(), true, None),
+        ];
+        assert_eq!(state(inbounds).await, enabled);
+        assert_eq!(state(clients).await, enabled);
+    }
+
+    #[test]
+    fn test_collect_user_traffic() {
+        let mut stats = HashMap::new();
+        stats.insert("user>>>alice>>>traffic>>>uplink".to_string(), 100);
+        stats.insert("user>>>alice>>>traffic>>>downlink".to_string(), 250);
+        stats.insert("user>>>bob>>>traffic>>>downlink".to_string(), 7);
+        stats.insert("inbound>>>in-1>>>traffic>>>uplink".to_string(), 999);
+
+        let traffic = collect_user_traffic(&stats);
+
+        assert_eq!(traffic.len(), 2);
+        assert_eq!(traffic["alice"], (100, 250));
+        assert_eq!(traffic["bob"], (0, 7));
+    }
 }
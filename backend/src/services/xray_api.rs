@@ -0,0 +1,739 @@
+//! Native gRPC client for the Xray API inbound.
+//!
+//! The message types mirror `app/stats/command/command.proto`,
+//! `app/proxyman/command/command.proto` and `app/observatory/command/command.proto` from
+//! Xray-core. They are written by hand with `prost`
+//! derives so the build does not need `protoc`.
+
+use crate::errors::{ApiError, ApiResult};
+use tokio::time::Duration;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+
+/// Port of the `api` dokodemo-door inbound written into every generated config.
+pub const API_PORT: i32 = 10085;
+
+const STATS_SERVICE: &str = "xray.app.stats.command.StatsService";
+const HANDLER_SERVICE: &str = "xray.app.proxyman.command.HandlerService";
+const OBSERVATORY_SERVICE: &str = "xray.core.app.observatory.command.ObservatoryService";
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueryStatsRequest {
+    #[prost(string, tag = "1")]
+    pub pattern: String,
+    #[prost(bool, tag = "2")]
+    pub reset: bool,
+    #[prost(string, repeated, tag = "3")]
+    pub patterns: Vec<String>,
+    #[prost(bool, tag = "4")]
+    pub regexp: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Stat {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(int64, tag = "2")]
+    pub value: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueryStatsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub stat: Vec<Stat>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SysStatsRequest {}
+
+/// Only the uptime of `SysStatsResponse` is modelled; the Go runtime figures are skipped.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SysStatsResponse {
+    #[prost(uint32, tag = "10")]
+    pub uptime: u32,
+}
+
+/// `xray.common.serial.TypedMessage`: a protobuf message tagged with its full type name.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TypedMessage {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+impl TypedMessage {
+    pub fn new<M: prost::Message>(type_name: &str, message: &M) -> Self {
+        Self {
+            r#type: type_name.to_string(),
+            value: message.encode_to_vec(),
+        }
+    }
+}
+
+/// `xray.common.protocol.User`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct User {
+    #[prost(uint32, tag = "1")]
+    pub level: u32,
+    #[prost(string, tag = "2")]
+    pub email: String,
+    #[prost(message, optional, tag = "3")]
+    pub account: Option<TypedMessage>,
+}
+
+/// `xray.proxy.vless.Account`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VlessAccount {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub flow: String,
+    #[prost(string, tag = "3")]
+    pub encryption: String,
+}
+
+/// `xray.proxy.vmess.Account`. The server accepts whatever security the client picks, so
+/// `security_settings` (tag 3) is left unset.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VmessAccount {
+    #[prost(string, tag = "1")]
+    pub id: String,
+}
+
+/// `xray.proxy.trojan.Account`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TrojanAccount {
+    #[prost(string, tag = "1")]
+    pub password: String,
+}
+
+/// `xray.proxy.shadowsocks.Account`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ShadowsocksAccount {
+    #[prost(string, tag = "1")]
+    pub password: String,
+    /// `xray.proxy.shadowsocks.CipherType`, see [`shadowsocks_cipher`].
+    #[prost(int32, tag = "2")]
+    pub cipher_type: i32,
+}
+
+/// `xray.proxy.shadowsocks_2022.Account`: the base64 user PSK.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Shadowsocks2022Account {
+    #[prost(string, tag = "1")]
+    pub key: String,
+}
+
+/// The `CipherType` enum value of a legacy Shadowsocks method name.
+fn shadowsocks_cipher(method: &str) -> i32 {
+    match method {
+        "aes-128-gcm" => 5,
+        "aes-256-gcm" => 6,
+        "chacha20-poly1305" | "chacha20-ietf-poly1305" => 7,
+        "xchacha20-poly1305" | "xchacha20-ietf-poly1305" => 8,
+        "none" | "plain" => 9,
+        _ => 0,
+    }
+}
+
+impl User {
+    pub fn vless(email: &str, level: u32, id: &str, flow: &str) -> Self {
+        let account = VlessAccount {
+            id: id.to_string(),
+            flow: flow.to_string(),
+            encryption: "none".to_string(),
+        };
+        Self::with_account(email, level, "xray.proxy.vless.Account", &account)
+    }
+
+    pub fn vmess(email: &str, level: u32, id: &str) -> Self {
+        let account = VmessAccount { id: id.to_string() };
+        Self::with_account(email, level, "xray.proxy.vmess.Account", &account)
+    }
+
+    pub fn trojan(email: &str, level: u32, password: &str) -> Self {
+        let account = TrojanAccount {
+            password: password.to_string(),
+        };
+        Self::with_account(email, level, "xray.proxy.trojan.Account", &account)
+    }
+
+    pub fn shadowsocks(email: &str, level: u32, password: &str, method: &str) -> Self {
+        let account = ShadowsocksAccount {
+            password: password.to_string(),
+            cipher_type: shadowsocks_cipher(method),
+        };
+        Self::with_account(email, level, "xray.proxy.shadowsocks.Account", &account)
+    }
+
+    pub fn shadowsocks_2022(email: &str, level: u32, key: &str) -> Self {
+        let account = Shadowsocks2022Account {
+            key: key.to_string(),
+        };
+        Self::with_account(
+            email,
+            level,
+            "xray.proxy.shadowsocks_2022.Account",
+            &account,
+        )
+    }
+
+    fn with_account<M: prost::Message>(
+        email: &str,
+        level: u32,
+        type_name: &str,
+        account: &M,
+    ) -> Self {
+        Self {
+            level,
+            email: email.to_string(),
+            account: Some(TypedMessage::new(type_name, account)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AddUserOperation {
+    #[prost(message, optional, tag = "1")]
+    pub user: Option<User>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RemoveUserOperation {
+    #[prost(string, tag = "1")]
+    pub email: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AlterInboundRequest {
+    #[prost(string, tag = "1")]
+    pub tag: String,
+    #[prost(message, optional, tag = "2")]
+    pub operation: Option<TypedMessage>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RemoveInboundRequest {
+    #[prost(string, tag = "1")]
+    pub tag: String,
+}
+
+/// Shared empty response of the HandlerService mutations.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Empty {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetOutboundStatusRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetOutboundStatusResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status: Option<ObservationResult>,
+}
+
+/// `xray.core.app.observatory.ObservationResult`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ObservationResult {
+    #[prost(message, repeated, tag = "1")]
+    pub status: Vec<OutboundStatus>,
+}
+
+/// `xray.core.app.observatory.OutboundStatus`. `delay` is in milliseconds, the times are Unix
+/// seconds.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OutboundStatus {
+    #[prost(bool, tag = "1")]
+    pub alive: bool,
+    #[prost(int64, tag = "2")]
+    pub delay: i64,
+    #[prost(string, tag = "3")]
+    pub last_error_reason: String,
+    #[prost(string, tag = "4")]
+    pub outbound_tag: String,
+    #[prost(int64, tag = "5")]
+    pub last_seen_time: i64,
+    #[prost(int64, tag = "6")]
+    pub last_try_time: i64,
+    #[prost(message, optional, tag = "7")]
+    pub health_ping: Option<HealthPingMeasurementResult>,
+}
+
+/// Burst observatory statistics; everything except the counts is a duration in nanoseconds.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HealthPingMeasurementResult {
+    #[prost(int64, tag = "1")]
+    pub all: i64,
+    #[prost(int64, tag = "2")]
+    pub fail: i64,
+    #[prost(int64, tag = "3")]
+    pub deviation: i64,
+    #[prost(int64, tag = "4")]
+    pub average: i64,
+    #[prost(int64, tag = "5")]
+    pub max: i64,
+    #[prost(int64, tag = "6")]
+    pub min: i64,
+}
+
+pub struct XrayApiClient {
+    grpc: tonic::client::Grpc<Channel>,
+}
+
+impl XrayApiClient {
+    /// Connects to the API inbound of the locally running Xray.
+    pub async fn connect_local() -> ApiResult<Self> {
+        Self::connect(&format!("127.0.0.1:{}", API_PORT)).await
+    }
+
+    pub async fn connect(addr: &str) -> ApiResult<Self> {
+        let channel = Endpoint::from_shared(format!("http://{}", addr))
+            .map_err(|e| ApiError::SystemError(format!("Invalid Xray API address: {}", e)))?
+            .connect_timeout(Duration::from_secs(2))
+            .timeout(Duration::from_secs(5))
+            .connect()
+            .await
+            .map_err(|e| {
+                ApiError::SystemError(format!("Failed to connect to Xray API at {}: {}", addr, e))
+            })?;
+
+        Ok(Self {
+            grpc: tonic::client::Grpc::new(channel),
+        })
+    }
+
+    /// Returns every counter whose name contains `pattern`, optionally resetting them to zero.
+    pub async fn query_stats(&mut self, pattern: &str, reset: bool) -> ApiResult<Vec<Stat>> {
+        let request = QueryStatsRequest {
+            pattern: pattern.to_string(),
+            reset,
+            ..Default::default()
+        };
+        let response: QueryStatsResponse = self.unary(STATS_SERVICE, "QueryStats", request).await?;
+        Ok(response.stat)
+    }
+
+    /// Seconds since the running Xray process started.
+    pub async fn uptime(&mut self) -> ApiResult<u32> {
+        let response: SysStatsResponse = self
+            .unary(STATS_SERVICE, "GetSysStats", SysStatsRequest {})
+            .await?;
+        Ok(response.uptime)
+    }
+
+    /// Removes a running inbound and closes its listener.
+    pub async fn remove_inbound(&mut self, tag: &str) -> ApiResult<()> {
+        let request = RemoveInboundRequest {
+            tag: tag.to_string(),
+        };
+        let _: Empty = self
+            .unary(HANDLER_SERVICE, "RemoveInbound", request)
+            .await?;
+        Ok(())
+    }
+
+    /// Adds a user to a running inbound without touching existing connections.
+    pub async fn add_user(&mut self, tag: &str, user: User) -> ApiResult<()> {
+        let operation = AddUserOperation { user: Some(user) };
+        self.alter_inbound(
+            tag,
+            TypedMessage::new("xray.app.proxyman.command.AddUserOperation", &operation),
+        )
+        .await
+    }
+
+    /// Removes a user, identified by email, from a running inbound.
+    pub async fn remove_user(&mut self, tag: &str, email: &str) -> ApiResult<()> {
+        let operation = RemoveUserOperation {
+            email: email.to_string(),
+        };
+        self.alter_inbound(
+            tag,
+            TypedMessage::new("xray.app.proxyman.command.RemoveUserOperation", &operation),
+        )
+        .await
+    }
+
+    /// Returns the latest observatory probe result of every observed outbound.
+    pub async fn get_outbound_status(&mut self) -> ApiResult<Vec<OutboundStatus>> {
+        let response: GetOutboundStatusResponse = self
+            .unary(
+                OBSERVATORY_SERVICE,
+                "GetOutboundStatus",
+                GetOutboundStatusRequest {},
+            )
+            .await?;
+        Ok(response.status.map(|s| s.status).unwrap_or_default())
+    }
+
+    async fn alter_inbound(&mut self, tag: &str, operation: TypedMessage) -> ApiResult<()> {
+        let request = AlterInboundRequest {
+            tag: tag.to_string(),
+            operation: Some(operation),
+        };
+        let _: Empty = self.unary(HANDLER_SERVICE, "AlterInbound", request).await?;
+        Ok(())
+    }
+
+    async fn unary<Req, Resp>(
+        &mut self,
+        service: &str,
+        method: &str,
+        request: Req,
+    ) -> ApiResult<Resp>
+    where
+        Req: prost::Message + Send + Sync + 'static,
+        Resp: prost::Message + Default + Send + Sync + 'static,
+    {
+        self.grpc
+            .ready()
+            .await
+            .map_err(|e| ApiError::SystemError(format!("Xray API is not ready: {}", e)))?;
+
+        let path = PathAndQuery::try_from(format!("/{}/{}", service, method))
+            .map_err(|e| ApiError::InternalError(format!("Invalid gRPC path: {}", e)))?;
+
+        let response = self
+            .grpc
+            .unary(
+                tonic::Request::new(request),
+                path,
+                ProstCodec::<Req, Resp>::default(),
+            )
+            .await
+            .map_err(|status| {
+                ApiError::SystemError(format!(
+                    "Xray API {}/{} failed ({:?}): {}",
+                    service,
+                    method,
+                    status.code(),
+                    status.message()
+                ))
+            })?;
+
+        Ok(response.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+    use tonic::codegen::{empty_body, http, BoxFuture, Context, Poll, Service};
+    use tonic::server::{NamedService, UnaryService};
+
+    /// Minimal stand-in for Xray's StatsService that records the requests it receives.
+    #[derive(Clone, Default)]
+    struct StubStats {
+        stats: Vec<Stat>,
+        requests: Arc<Mutex<Vec<QueryStatsRequest>>>,
+    }
+
+    impl UnaryService<QueryStatsRequest> for StubStats {
+        type Response = QueryStatsResponse;
+        type Future = BoxFuture<tonic::Response<QueryStatsResponse>, tonic::Status>;
+
+        fn call(&mut self, request: tonic::Request<QueryStatsRequest>) -> Self::Future {
+            let request = request.into_inner();
+            let stat = self
+                .stats
+                .iter()
+                .filter(|s| s.name.contains(&request.pattern))
+                .cloned()
+                .collect();
+            self.requests.lock().unwrap().push(request);
+            Box::pin(async move { Ok(tonic::Response::new(QueryStatsResponse { stat })) })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for StubStats
+    where
+        B: tonic::codegen::Body + Send + 'static,
+        B::Error: Into<tonic::codegen::StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let stub = self.clone();
+            match req.uri().path() {
+                "/xray.app.stats.command.StatsService/QueryStats" => Box::pin(async move {
+                    let mut grpc = tonic::server::Grpc::new(ProstCodec::default());
+                    Ok(grpc.unary(stub, req).await)
+                }),
+                _ => Box::pin(async move {
+                    let mut response = http::Response::new(empty_body());
+                    let headers = response.headers_mut();
+                    headers.insert("grpc-status", (tonic::Code::Unimplemented as i32).into());
+                    headers.insert(
+                        http::header::CONTENT_TYPE,
+                        http::HeaderValue::from_static("application/grpc"),
+                    );
+                    Ok(response)
+                }),
+            }
+        }
+    }
+
+    impl NamedService for StubStats {
+        const NAME: &'static str = STATS_SERVICE;
+    }
+
+    /// Stand-in for the HandlerService that records AlterInbound and RemoveInbound calls.
+    #[derive(Clone, Default)]
+    struct StubHandler {
+        alters: Arc<Mutex<Vec<AlterInboundRequest>>>,
+        removes: Arc<Mutex<Vec<RemoveInboundRequest>>>,
+    }
+
+    impl UnaryService<AlterInboundRequest> for StubHandler {
+        type Response = Empty;
+        type Future = BoxFuture<tonic::Response<Empty>, tonic::Status>;
+
+        fn call(&mut self, request: tonic::Request<AlterInboundRequest>) -> Self::Future {
+            self.alters.lock().unwrap().push(request.into_inner());
+            Box::pin(async move { Ok(tonic::Response::new(Empty {})) })
+        }
+    }
+
+    impl UnaryService<RemoveInboundRequest> for StubHandler {
+        type Response = Empty;
+        type Future = BoxFuture<tonic::Response<Empty>, tonic::Status>;
+
+        fn call(&mut self, request: tonic::Request<RemoveInboundRequest>) -> Self::Future {
+            let request = request.into_inner();
+            if request.tag == "missing" {
+                return Box::pin(async move { Err(tonic::Status::not_found("handler not found")) });
+            }
+            self.removes.lock().unwrap().push(request);
+            Box::pin(async move { Ok(tonic::Response::new(Empty {})) })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for StubHandler
+    where
+        B: tonic::codegen::Body + Send + 'static,
+        B::Error: Into<tonic::codegen::StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let stub = self.clone();
+            let path = req.uri().path().to_string();
+            Box::pin(async move {
+                match path.as_str() {
+                    "/xray.app.proxyman.command.HandlerService/AlterInbound" => {
+                        let codec = ProstCodec::<Empty, AlterInboundRequest>::default();
+                        Ok(tonic::server::Grpc::new(codec).unary(stub, req).await)
+                    }
+                    _ => {
+                        let codec = ProstCodec::<Empty, RemoveInboundRequest>::default();
+                        Ok(tonic::server::Grpc::new(codec).unary(stub, req).await)
+                    }
+                }
+            })
+        }
+    }
+
+    impl NamedService for StubHandler {
+        const NAME: &'static str = HANDLER_SERVICE;
+    }
+
+    /// Stand-in for the ObservatoryService that reports a fixed set of probe results.
+    #[derive(Clone, Default)]
+    struct StubObservatory {
+        status: Vec<OutboundStatus>,
+    }
+
+    impl UnaryService<GetOutboundStatusRequest> for StubObservatory {
+        type Response = GetOutboundStatusResponse;
+        type Future = BoxFuture<tonic::Response<GetOutboundStatusResponse>, tonic::Status>;
+
+        fn call(&mut self, _request: tonic::Request<GetOutboundStatusRequest>) -> Self::Future {
+            let status = Some(ObservationResult {
+                status: self.status.clone(),
+            });
+            Box::pin(async move { Ok(tonic::Response::new(GetOutboundStatusResponse { status })) })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for StubObservatory
+    where
+        B: tonic::codegen::Body + Send + 'static,
+        B::Error: Into<tonic::codegen::StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let stub = self.clone();
+            Box::pin(async move {
+                let mut grpc = tonic::server::Grpc::new(ProstCodec::default());
+                Ok(grpc.unary(stub, req).await)
+            })
+        }
+    }
+
+    impl NamedService for StubObservatory {
+        const NAME: &'static str = OBSERVATORY_SERVICE;
+    }
+
+    async fn spawn_stub<S>(stub: S) -> String
+    where
+        S: Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<tonic::body::BoxBody>,
+                Error = Infallible,
+            > + NamedService
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let incoming =
+            tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(stub)
+                .serve_with_incoming(incoming),
+        );
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_query_stats_against_stub() {
+        let stub = StubStats {
+            stats: vec![
+                Stat {
+                    name: "inbound>>>inbound-1>>>traffic>>>uplink".to_string(),
+                    value: 1024,
+                },
+                Stat {
+                    name: "user>>>alice>>>traffic>>>downlink".to_string(),
+                    value: 1 << 40,
+                },
+            ],
+            ..Default::default()
+        };
+        let requests = stub.requests.clone();
+        let addr = spawn_stub(stub).await;
+
+        let mut client = XrayApiClient::connect(&addr).await.unwrap();
+
+        let all = client.query_stats("", true).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].value, 1 << 40);
+
+        let users = client.query_stats("user>>>", false).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "user>>>alice>>>traffic>>>downlink");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].reset);
+        assert!(!requests[1].reset);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_reported() {
+        let addr = spawn_stub(StubStats::default()).await;
+        let mut client = XrayApiClient::connect(&addr).await.unwrap();
+
+        let err = client
+            .unary::<QueryStatsRequest, QueryStatsResponse>(
+                "xray.app.stats.command.StatsService",
+                "GetSysStats",
+                QueryStatsRequest::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unimplemented"), "{}", err);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert!(XrayApiClient::connect(&closed).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handler_service_against_stub() {
+        let stub = StubHandler::default();
+        let (alters, removes) = (stub.alters.clone(), stub.removes.clone());
+        let addr = spawn_stub(stub).await;
+        let mut client = XrayApiClient::connect(&addr).await.unwrap();
+
+        let user = User::vless(
+            "alice",
+            0,
+            "b831381d-6324-4d53-ad4f-8cda48b30811",
+            "xtls-rprx-vision",
+        );
+        client.add_user("inbound-1", user.clone()).await.unwrap();
+        client.remove_user("inbound-1", "bob").await.unwrap();
+        client.remove_inbound("inbound-2").await.unwrap();
+
+        let err = client.remove_inbound("missing").await.unwrap_err();
+        assert!(err.to_string().contains("handler not found"), "{}", err);
+
+        let alters = alters.lock().unwrap();
+        assert_eq!(alters.len(), 2);
+        assert_eq!(alters[0].tag, "inbound-1");
+        let add = alters[0].operation.as_ref().unwrap();
+        assert_eq!(add.r#type, "xray.app.proxyman.command.AddUserOperation");
+        let decoded = <AddUserOperation as prost::Message>::decode(add.value.as_slice()).unwrap();
+        assert_eq!(decoded.user, Some(user));
+
+        let remove = alters[1].operation.as_ref().unwrap();
+        assert_eq!(
+            remove.r#type,
+            "xray.app.proxyman.command.RemoveUserOperation"
+        );
+        let decoded =
+            <RemoveUserOperation as prost::Message>::decode(remove.value.as_slice()).unwrap();
+        assert_eq!(decoded.email, "bob");
+
+        assert_eq!(removes.lock().unwrap()[0].tag, "inbound-2");
+    }
+
+    #[tokio::test]
+    async fn test_observatory_service_against_stub() {
+        let stub = StubObservatory {
+            status: vec![OutboundStatus {
+                alive: true,
+                delay: 87,
+                outbound_tag: "warp".to_string(),
+                last_seen_time: 1_700_000_000,
+                last_try_time: 1_700_000_000,
+                ..Default::default()
+            }],
+        };
+        let addr = spawn_stub(stub).await;
+        let mut client = XrayApiClient::connect(&addr).await.unwrap();
+
+        let status = client.get_outbound_status().await.unwrap();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].outbound_tag, "warp");
+        assert_eq!(status[0].delay, 87);
+        assert!(status[0].alive);
+    }
+}
@@ -0,0 +1,490 @@
+//! Supervisor for the Xray child process.
+//!
+//! A single background task owns the `Child`, so the panel always knows the real PID and exit
+//! status, restarts Xray with exponential backoff when it crashes, and stops it with SIGTERM
+//! before falling back to SIGKILL.
+
+use crate::errors::{ApiError, ApiResult};
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, timeout, Duration};
+
+/// Builds the command used to launch the supervised process.
+pub type CommandFactory = Arc<dyn Fn() -> ApiResult<Command> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    Stopped,
+    /// Exited on its own while it should be running; a restart is pending.
+    Error,
+}
+
+impl ProcessState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessState::Running => "running",
+            ProcessState::Stopped => "stopped",
+            ProcessState::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessStatus {
+    pub state: ProcessState,
+    pub pid: Option<u32>,
+    /// Exit code of the last run, `None` if it has not exited or was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub started_at: Option<Instant>,
+    /// Number of automatic restarts after crashes.
+    pub restart_count: u32,
+    pub last_error: Option<String>,
+}
+
+impl ProcessStatus {
+    /// Seconds since the current process was started, 0 when it is not running.
+    pub fn uptime(&self) -> u64 {
+        match (self.state, self.started_at) {
+            (ProcessState::Running, Some(started)) => started.elapsed().as_secs(),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// How long to wait after SIGTERM before sending SIGKILL.
+    pub stop_timeout: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// A process that stays up this long resets the backoff to `min_backoff`.
+    pub stable_after: Duration,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            stop_timeout: Duration::from_secs(5),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(60),
+        }
+    }
+}
+
+enum Request {
+    Start(oneshot::Sender<ApiResult<()>>),
+    Stop(oneshot::Sender<ApiResult<()>>),
+    Restart(oneshot::Sender<ApiResult<()>>),
+}
+
+/// Handle to the supervisor task. Cheap to clone; the task stops the process once every
+/// handle is dropped.
+#[derive(Clone)]
+pub struct XraySupervisor {
+    requests: mpsc::UnboundedSender<Request>,
+    status: Arc<Mutex<ProcessStatus>>,
+}
+
+impl XraySupervisor {
+    /// Supervises the Xray binary from `XRAY_BIN_PATH`, run with `XRAY_CONFIG_PATH`.
+    pub fn xray() -> Self {
+        Self::spawn(Arc::new(xray_command), SupervisorConfig::default())
+    }
+
+    pub fn spawn(factory: CommandFactory, config: SupervisorConfig) -> Self {
+        let (requests, rx) = mpsc::unbounded_channel();
+        let status = Arc::new(Mutex::new(ProcessStatus {
+            state: ProcessState::Stopped,
+            pid: None,
+            exit_code: None,
+            started_at: None,
+            restart_count: 0,
+            last_error: None,
+        }));
+
+        let task = Supervisor {
+            factory,
+            config,
+            status: status.clone(),
+            child: None,
+            want_running: false,
+            backoff: Duration::ZERO,
+            restart_at: None,
+        };
+        tokio::spawn(task.run(rx));
+
+        Self { requests, status }
+    }
+
+    pub fn status(&self) -> ProcessStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Starts the process unless it is already running.
+    pub async fn start(&self) -> ApiResult<()> {
+        self.request(Request::Start).await
+    }
+
+    /// Stops the process and disables automatic restarts until the next start.
+    pub async fn stop(&self) -> ApiResult<()> {
+        self.request(Request::Stop).await
+    }
+
+    pub async fn restart(&self) -> ApiResult<()> {
+        self.request(Request::Restart).await
+    }
+
+    async fn request(
+        &self,
+        make: impl FnOnce(oneshot::Sender<ApiResult<()>>) -> Request,
+    ) -> ApiResult<()> {
+        let (reply, rx) = oneshot::channel();
+        self.requests
+            .send(make(reply))
+            .map_err(|_| ApiError::SystemError("Xray supervisor is not running".to_string()))?;
+        rx.await
+            .map_err(|_| ApiError::SystemError("Xray supervisor stopped".to_string()))?
+    }
+}
+
+/// The Xray binary: `XRAY_BIN_PATH`, or the standard install location.
+pub fn bin_path() -> String {
+    std::env::var("XRAY_BIN_PATH").unwrap_or_else(|_| "/usr/local/bin/xray".to_string())
+}
+
+/// The config file Xray runs with: `XRAY_CONFIG_PATH`, or the panel's default.
+pub fn config_path() -> String {
+    std::env::var("XRAY_CONFIG_PATH").unwrap_or_else(|_| "/etc/x-ui/xray.json".to_string())
+}
+
+fn xray_command() -> ApiResult<Command> {
+    let bin_path = bin_path();
+    let config_path = config_path();
+
+    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+    let log_dir = cwd.join("logs");
+    if !log_dir.exists() {
+        let _ = std::fs::create_dir_all(&log_dir);
+    }
+
+    let stdout_file = std::fs::File::create(log_dir.join("access.log"))
+        .map_err(|e| ApiError::SystemError(format!("Failed to create stdout log: {}", e)))?;
+    let stderr_file = std::fs::File::create(log_dir.join("error.log"))
+        .map_err(|e| ApiError::SystemError(format!("Failed to create stderr log: {}", e)))?;
+
+    let mut command = Command::new(bin_path);
+    command
+        .arg("-c")
+        .arg(config_path)
+        .env("GOMEMLIMIT", "150MiB")
+        .env("GOGC", "50")
+        .stdout(stdout_file)
+        .stderr(stderr_file);
+    Ok(command)
+}
+
+struct Supervisor {
+    factory: CommandFactory,
+    config: SupervisorConfig,
+    status: Arc<Mutex<ProcessStatus>>,
+    child: Option<Child>,
+    want_running: bool,
+    backoff: Duration,
+    restart_at: Option<tokio::time::Instant>,
+}
+
+impl Supervisor {
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<Request>) {
+        loop {
+            let restart_at = self.restart_at;
+            tokio::select! {
+                request = rx.recv() => {
+                    let Some(request) = request else {
+                        let _ = self.terminate().await;
+                        return;
+                    };
+                    self.handle(request).await;
+                }
+                exit = wait_child(&mut self.child) => self.on_exit(exit),
+                _ = sleep_until(restart_at.unwrap_or_else(tokio::time::Instant::now)), if restart_at.is_some() => {
+                    self.restart_at = None;
+                    self.update(|s| s.restart_count += 1);
+                    if let Err(e) = self.launch() {
+                        tracing::error!("Failed to restart Xray: {}", e);
+                        self.schedule_restart();
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle(&mut self, request: Request) {
+        match request {
+            Request::Start(reply) => {
+                self.want_running = true;
+                self.restart_at = None;
+                self.backoff = Duration::ZERO;
+                let result = if self.child.is_some() {
+                    Ok(())
+                } else {
+                    self.launch()
+                };
+                let _ = reply.send(result);
+            }
+            Request::Stop(reply) => {
+                self.want_running = false;
+                self.restart_at = None;
+                let _ = reply.send(self.terminate().await);
+            }
+            Request::Restart(reply) => {
+                self.want_running = true;
+                self.restart_at = None;
+                self.backoff = Duration::ZERO;
+                let result = match self.terminate().await {
+                    Ok(()) => self.launch(),
+                    Err(e) => Err(e),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    fn launch(&mut self) -> ApiResult<()> {
+        let spawned = (self.factory)().and_then(|mut command| {
+            command
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| ApiError::SystemError(format!("Failed to start xray: {}", e)))
+        });
+
+        match spawned {
+            Ok(child) => {
+                let pid = child.id();
+                tracing::info!("Xray process started with PID {:?}", pid);
+                self.child = Some(child);
+                self.update(|s| {
+                    s.state = ProcessState::Running;
+                    s.pid = pid;
+                    s.exit_code = None;
+                    s.started_at = Some(Instant::now());
+                    s.last_error = None;
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.update(|s| {
+                    s.state = ProcessState::Error;
+                    s.pid = None;
+                    s.last_error = Some(e.to_string());
+                });
+                Err(e)
+            }
+        }
+    }
+
+    fn on_exit(&mut self, exit: std::io::Result<ExitStatus>) {
+        self.child = None;
+        let uptime = self.status().started_at.map(|t| t.elapsed());
+        let (exit_code, description) = match exit {
+            Ok(status) => (status.code(), describe_exit(status)),
+            Err(e) => (None, format!("wait failed: {}", e)),
+        };
+
+        if !self.want_running {
+            self.update(|s| {
+                s.state = ProcessState::Stopped;
+                s.pid = None;
+                s.exit_code = exit_code;
+            });
+            return;
+        }
+
+        if uptime.is_some_and(|t| t >= self.config.stable_after) {
+            self.backoff = Duration::ZERO;
+        }
+        tracing::warn!("Xray exited unexpectedly ({})", description);
+        self.update(|s| {
+            s.state = ProcessState::Error;
+            s.pid = None;
+            s.exit_code = exit_code;
+            s.last_error = Some(format!("Xray {}", description));
+        });
+        self.schedule_restart();
+    }
+
+    fn schedule_restart(&mut self) {
+        self.backoff = if self.backoff.is_zero() {
+            self.config.min_backoff
+        } else {
+            (self.backoff * 2).min(self.config.max_backoff)
+        };
+        tracing::info!("Restarting Xray in {:?}", self.backoff);
+        self.restart_at = Some(tokio::time::Instant::now() + self.backoff);
+    }
+
+    /// Sends SIGTERM and waits up to `stop_timeout` before killing the process.
+    async fn terminate(&mut self) -> ApiResult<()> {
+        let Some(mut child) = self.child.take() else {
+            self.update(|s| s.state = ProcessState::Stopped);
+            return Ok(());
+        };
+
+        #[cfg(unix)]
+        if let Some(pid) = child.id() {
+            // SAFETY: `pid` belongs to a child we own and have not reaped yet.
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+
+        let status = match timeout(self.config.stop_timeout, child.wait()).await {
+            Ok(status) => status,
+            Err(_) => {
+                tracing::warn!(
+                    "Xray did not exit within {:?} after SIGTERM, killing it",
+                    self.config.stop_timeout
+                );
+                let _ = child.start_kill();
+                child.wait().await
+            }
+        }
+        .map_err(|e| ApiError::SystemError(format!("Failed to stop xray: {}", e)))?;
+
+        tracing::info!("Xray process stopped ({})", describe_exit(status));
+        self.update(|s| {
+            s.state = ProcessState::Stopped;
+            s.pid = None;
+            s.exit_code = status.code();
+        });
+        Ok(())
+    }
+
+    fn status(&self) -> ProcessStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ProcessStatus)) {
+        f(&mut self.status.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+}
+
+async fn wait_child(child: &mut Option<Child>) -> std::io::Result<ExitStatus> {
+    match child {
+        Some(child) => child.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn describe_exit(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("killed by signal {}", signal);
+        }
+    }
+    "exited".to_string()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(script: &'static str) -> CommandFactory {
+        Arc::new(move || {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(script);
+            Ok(command)
+        })
+    }
+
+    fn fast_config() -> SupervisorConfig {
+        SupervisorConfig {
+            stop_timeout: Duration::from_millis(300),
+            min_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(200),
+            stable_after: Duration::from_secs(60),
+        }
+    }
+
+    async fn wait_for(supervisor: &XraySupervisor, pred: impl Fn(&ProcessStatus) -> bool) {
+        for _ in 0..100 {
+            if pred(&supervisor.status()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("condition not reached: {:?}", supervisor.status());
+    }
+
+    #[tokio::test]
+    async fn test_start_and_stop() {
+        let supervisor = XraySupervisor::spawn(shell("exec sleep 30"), fast_config());
+        assert_eq!(supervisor.status().state, ProcessState::Stopped);
+
+        supervisor.start().await.unwrap();
+        let status = supervisor.status();
+        assert_eq!(status.state, ProcessState::Running);
+        assert!(status.pid.is_some());
+
+        supervisor.stop().await.unwrap();
+        let status = supervisor.status();
+        assert_eq!(status.state, ProcessState::Stopped);
+        assert_eq!(status.pid, None);
+        assert_eq!(status.restart_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_crash_restarts_with_backoff() {
+        let supervisor = XraySupervisor::spawn(shell("exit 3"), fast_config());
+        supervisor.start().await.unwrap();
+
+        wait_for(&supervisor, |s| s.restart_count >= 2).await;
+        let status = supervisor.status();
+        assert_eq!(status.exit_code, Some(3));
+        assert!(status.last_error.unwrap().contains("exit code 3"));
+
+        supervisor.stop().await.unwrap();
+        let count = supervisor.status().restart_count;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(supervisor.status().restart_count, count);
+        assert_eq!(supervisor.status().state, ProcessState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_stop_kills_after_timeout() {
+        let supervisor = XraySupervisor::spawn(shell("trap '' TERM; exec sleep 30"), fast_config());
+        supervisor.start().await.unwrap();
+        // Give the shell time to install the trap before it is signalled.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let started = Instant::now();
+        supervisor.stop().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(supervisor.status().state, ProcessState::Stopped);
+        assert_eq!(supervisor.status().exit_code, None);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_failure_is_reported() {
+        let factory: CommandFactory = Arc::new(|| Ok(Command::new("/nonexistent/xray-binary")));
+        let supervisor = XraySupervisor::spawn(factory, fast_config());
+
+        assert!(supervisor.start().await.is_err());
+        assert_eq!(supervisor.status().state, ProcessState::Error);
+    }
+}
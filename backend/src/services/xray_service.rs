@@ -1,25 +1,186 @@
-use crate::errors::ApiResult;
+use crate::errors::{ApiError, ApiResult};
+use crate::models::balancer::{Balancer, ObservatoryMode};
+use crate::models::client::Client;
+use crate::models::config_revision::ConfigTrigger;
 use crate::models::inbound::Inbound;
+use crate::models::outbound::Outbound;
+use crate::models::protocol_settings::users_key;
+use crate::models::routing_rule::RouteRule;
 use crate::models::xray_config::*;
-use crate::services::system_service;
 use crate::services::system_service::SharedMonitor;
-use sqlx::SqlitePool;
+use crate::services::xray_api::{self, XrayApiClient};
+use crate::services::{
+    balancer_service, client_service, config_revision_service, dns_service, outbound_service,
+    policy_service, routing_service, system_service, template_service, xray_process,
+};
+use crate::utils::json_merge;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool, Transaction};
+use std::collections::HashMap;
 use std::env;
+use tokio::sync::{Mutex, MutexGuard};
 
-pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResult<()> {
+/// The config the running Xray was last brought in line with, either by a restart or by live
+/// HandlerService calls. `None` until the first apply, which always restarts.
+static APPLIED_CONFIG: Mutex<Option<Value>> = Mutex::const_new(None);
+
+/// Regenerates the Xray config from the database and brings the running Xray in line with it.
+pub async fn apply_config(
+    pool: &SqlitePool,
+    monitor: SharedMonitor,
+    trigger: ConfigTrigger,
+) -> ApiResult<()> {
+    // Rendered under the lock so an apply cannot overwrite a newer config with older data.
+    let applied = APPLIED_CONFIG.lock().await;
+    let config = render_config(&mut *pool.acquire().await?, None).await?;
+    stage_config(
+        config,
+        applied,
+        &xray_process::bin_path(),
+        &xray_process::config_path(),
+    )
+    .await?
+    .install(pool, monitor, trigger, false)
+    .await
+}
+
+/// A database transaction for a config change, kept only if the config rendered from it
+/// passes `xray run -test`. The config lock is taken before the transaction begins, so an
+/// apply waiting for the lock never holds a write the transaction needs, or the reverse.
+pub struct ConfigChange {
+    tx: Transaction<'static, Sqlite>,
+    applied: MutexGuard<'static, Option<Value>>,
+}
+
+/// Starts a change to be finished with [`commit_config`].
+pub async fn begin_change(pool: &SqlitePool) -> ApiResult<ConfigChange> {
+    let applied = APPLIED_CONFIG.lock().await;
+    Ok(ConfigChange {
+        tx: pool.begin().await?,
+        applied,
+    })
+}
+
+impl ConfigChange {
+    /// Commits the change without touching the config, for changes that leave it as it is.
+    pub async fn commit(self) -> ApiResult<()> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    /// Renders and tests the config with the uncommitted change, then commits the change. A
+    /// config Xray rejects rolls it back.
+    async fn stage(self, xray_bin: &str, config_path: &str) -> ApiResult<StagedConfig> {
+        let ConfigChange { mut tx, applied } = self;
+        let config = render_config(&mut tx, None).await?;
+        let staged = stage_config(config, applied, xray_bin, config_path).await?;
+        if let Err(e) = tx.commit().await {
+            staged.discard().await;
+            return Err(e.into());
+        }
+        Ok(staged)
+    }
+}
+
+impl std::ops::Deref for ConfigChange {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        &self.tx
+    }
+}
+
+impl std::ops::DerefMut for ConfigChange {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        &mut self.tx
+    }
+}
+
+/// Commits `change` and applies it like [`apply_config`].
+pub async fn commit_config(
+    pool: &SqlitePool,
+    monitor: SharedMonitor,
+    change: ConfigChange,
+    trigger: ConfigTrigger,
+) -> ApiResult<()> {
+    change
+        .stage(&xray_process::bin_path(), &xray_process::config_path())
+        .await?
+        .install(pool, monitor, trigger, false)
+        .await
+}
+
+/// Builds the config from the database and merges it over the config template, the stored
+/// one unless `template` is given. See [`json_merge::merge_template`] for the precedence.
+pub async fn render_config(
+    conn: &mut SqliteConnection,
+    template: Option<&Value>,
+) -> ApiResult<Value> {
+    let config = build_config(conn).await?;
+    let config = serde_json::to_value(&config).map_err(|e| {
+        crate::errors::ApiError::InternalError(format!("Failed to serialize config: {}", e))
+    })?;
+    let template = match template {
+        Some(template) => template.clone(),
+        None => template_service::get_template(conn).await?,
+    };
+    json_merge::merge_template(template, config).map_err(ApiError::BadRequest)
+}
+
+/// Keys whose values are credentials: user ids, passwords, private and pre-shared keys, the
+/// REALITY short ids and mKCP seeds.
+const SECRET_KEYS: &[&str] = &[
+    "id",
+    "password",
+    "pass",
+    "privateKey",
+    "secretKey",
+    "preSharedKey",
+    "shortIds",
+    "seed",
+];
+
+/// Replaces every credential in `config` with a placeholder so it can be shared for debugging.
+/// Inline TLS certificate keys are redacted as well.
+pub fn redact_secrets(config: &mut Value) {
+    match config {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) || (key == "key" && value.is_array()) {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// Builds the Xray config from the database. Reads only; nothing is written to disk and the
+/// running Xray is not touched.
+pub async fn build_config(conn: &mut SqliteConnection) -> ApiResult<XrayConfig> {
     let inbounds = sqlx::query_as::<_, Inbound>("SELECT * FROM inbounds WHERE enable = 1")
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
 
+    let mut clients_by_inbound: std::collections::HashMap<String, Vec<Client>> =
+        std::collections::HashMap::new();
+    for client in client_service::get_all_clients(conn).await? {
+        clients_by_inbound
+            .entry(client.inbound_id.clone())
+            .or_default()
+            .push(client);
+    }
+    let now_ms = chrono::Utc::now().timestamp_millis();
+
     let mut config = XrayConfig::default();
 
     config.log.loglevel = "error".to_string();
     let cwd = env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
     let log_dir = cwd.join("logs");
-    if !log_dir.exists() {
-        let _ = std::fs::create_dir_all(&log_dir);
-    }
-
     config.log.access = Some(log_dir.join("access.log").to_string_lossy().to_string());
     config.log.error = Some(log_dir.join("error.log").to_string_lossy().to_string());
 
@@ -34,7 +195,7 @@ pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResul
 
     config.inbounds.push(InboundConfig {
         tag: "api".to_string(),
-        port: 10085,
+        port: xray_api::API_PORT,
         protocol: "dokodemo-door".to_string(),
         listen: Some("127.0.0.1".to_string()),
         settings: Some(serde_json::json!({
@@ -51,10 +212,35 @@ pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResul
             .clone()
             .unwrap_or_else(|| format!("inbound-{}", inbound.id));
 
-        let allocate = inbound
-            .allocate
-            .as_ref()
-            .and_then(|s| serde_json::from_str(s).ok());
+        let owner = format!("Inbound {}", inbound.remark);
+        let allocate = json_column(&owner, "allocate", &inbound.allocate)?;
+        let mut settings: Option<Value> = json_column(&owner, "settings", &inbound.settings)?;
+
+        // The clients table is authoritative: an inbound without rows gets an empty user
+        // list rather than whatever stale users its stored settings still carry.
+        if client_service::supports_clients(&inbound.protocol) {
+            let clients = clients_by_inbound
+                .get(&inbound.id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let method = settings
+                .as_ref()
+                .and_then(|s| s.get("method"))
+                .and_then(|m| m.as_str())
+                .map(str::to_string);
+            let entries: Vec<serde_json::Value> = clients
+                .iter()
+                .filter(|c| c.is_active(now_ms))
+                .map(|c| client_service::settings_entry(&inbound.protocol, method.as_deref(), c))
+                .collect();
+            let settings = settings.get_or_insert_with(|| serde_json::json!({}));
+            if let Some(obj) = settings.as_object_mut() {
+                obj.insert(
+                    users_key(&inbound.protocol).to_string(),
+                    serde_json::Value::Array(entries),
+                );
+            }
+        }
 
         let inbound_config = InboundConfig {
             tag,
@@ -62,85 +248,137 @@ pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResul
             protocol: inbound.protocol.clone(),
             listen: inbound.listen.clone(),
             allocate,
-            settings: inbound
-                .settings
-                .as_ref()
-                .and_then(|s| serde_json::from_str(s).ok()),
-            stream_settings: inbound
-                .stream_settings
-                .as_ref()
-                .and_then(|s| serde_json::from_str(s).ok()),
-            sniffing: inbound
-                .sniffing
-                .as_ref()
-                .and_then(|s| serde_json::from_str(s).ok()),
+            settings,
+            stream_settings: json_column(&owner, "streamSettings", &inbound.stream_settings)?,
+            sniffing: json_column(&owner, "sniffing", &inbound.sniffing)?,
         };
 
         config.inbounds.push(inbound_config);
     }
 
+    config.dns = dns_service::dns_config(conn).await?;
     config.stats = Some(StatsConfig {});
 
-    let mut levels = std::collections::HashMap::new();
-    levels.insert(
-        "0".to_string(),
-        LevelPolicy {
-            stats_user_uplink: true,
-            stats_user_downlink: true,
-            handshake: 4,
-            conn_idle: 300,
-            uplink_only: 2,
-            downlink_only: 5,
-            buffer_size: 512,
-        },
-    );
-
-    config.policy = Some(PolicyConfig {
-        levels,
-        system: Some(SystemPolicy {
-            stats_inbound_uplink: true,
-            stats_inbound_downlink: true,
-            stats_outbound_uplink: true,
-            stats_outbound_downlink: true,
-        }),
-    });
+    config.policy = Some(policy_service::get_policy(conn).await?);
 
     config.outbounds.push(OutboundConfig {
         tag: "direct".to_string(),
         protocol: "freedom".to_string(),
+        send_through: None,
         settings: None,
         stream_settings: None,
+        proxy_settings: None,
+        mux: None,
     });
 
     config.outbounds.push(OutboundConfig {
         tag: "blocked".to_string(),
         protocol: "blackhole".to_string(),
+        send_through: None,
         settings: None,
         stream_settings: None,
+        proxy_settings: None,
+        mux: None,
     });
 
-    let mut rules = vec![];
+    for outbound in outbound_service::get_all_outbounds(conn).await? {
+        if outbound.enable {
+            config.outbounds.push(outbound_config(&outbound)?);
+        }
+    }
 
-    rules.push(RoutingRule {
+    let mut rules = vec![RoutingRule {
         rule_type: "field".to_string(),
         inbound_tag: Some(vec!["api".to_string()]),
         outbound_tag: Some("api".to_string()),
         ..Default::default()
-    });
+    }];
+    for rule in routing_service::get_all_rules(conn).await? {
+        if rule.enable {
+            rules.push(routing_rule_config(&rule)?);
+        }
+    }
+
+    let balancers = balancer_service::get_all_balancers(conn)
+        .await?
+        .iter()
+        .map(balancer_config)
+        .collect::<ApiResult<_>>()?;
 
     config.routing = Some(RoutingConfig {
-        domain_strategy: "IPIfNonMatch".to_string(),
+        domain_strategy: routing_service::get_settings(conn).await?.domain_strategy,
         rules,
+        balancers,
     });
 
-    let config_json = serde_json::to_string_pretty(&config).map_err(|e| {
+    let observatory = balancer_service::get_observatory_settings(conn).await?;
+    match observatory.mode {
+        ObservatoryMode::None => {}
+        ObservatoryMode::Observatory => {
+            config.observatory = Some(ObservatoryConfig {
+                subject_selector: observatory.subject_selector,
+                probe_url: observatory.probe_url,
+                probe_interval: observatory.probe_interval,
+                enable_concurrency: observatory.enable_concurrency,
+            });
+        }
+        ObservatoryMode::BurstObservatory => {
+            config.burst_observatory = Some(BurstObservatoryConfig {
+                subject_selector: observatory.subject_selector,
+                ping_config: observatory.ping_config,
+            });
+        }
+    }
+    if observatory.mode != ObservatoryMode::None {
+        config.api.services.push("ObservatoryService".to_string());
+    }
+
+    Ok(config)
+}
+
+/// Validates `new_config`, atomically swaps it into `XRAY_CONFIG_PATH`, records it as a
+/// revision and applies it, live where possible unless `force_restart` is set.
+pub async fn install_config(
+    pool: &SqlitePool,
+    monitor: SharedMonitor,
+    new_config: Value,
+    trigger: ConfigTrigger,
+    force_restart: bool,
+) -> ApiResult<()> {
+    let applied = APPLIED_CONFIG.lock().await;
+    stage_config(
+        new_config,
+        applied,
+        &xray_process::bin_path(),
+        &xray_process::config_path(),
+    )
+    .await?
+    .install(pool, monitor, trigger, force_restart)
+    .await
+}
+
+/// A config that passed `xray run -test` and waits next to the live config to be installed.
+struct StagedConfig {
+    config: Value,
+    config_path: String,
+    tmp_path: std::path::PathBuf,
+    /// Held until the running Xray matches this config so concurrent applies cannot interleave.
+    applied: MutexGuard<'static, Option<Value>>,
+}
+
+/// Writes `new_config` next to the live config at `config_path` and runs Xray's config test
+/// on it with `xray_bin`.
+async fn stage_config(
+    new_config: Value,
+    applied: MutexGuard<'static, Option<Value>>,
+    xray_bin: &str,
+    config_path: &str,
+) -> ApiResult<StagedConfig> {
+    let config_json = serde_json::to_string_pretty(&new_config).map_err(|e| {
         crate::errors::ApiError::InternalError(format!("Failed to serialize config: {}", e))
     })?;
 
-    let config_path =
-        env::var("XRAY_CONFIG_PATH").unwrap_or_else(|_| "/etc/x-ui/xray.json".to_string());
-
-    if let Some(parent) = std::path::Path::new(&config_path).parent() {
+    if let Some(parent) = std::path::Path::new(config_path).parent() {
         if !parent.exists() {
             tokio::fs::create_dir_all(parent).await.map_err(|e| {
                 crate::errors::ApiError::SystemError(format!(
@@ -151,14 +389,146 @@ pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResul
         }
     }
 
-    tokio::fs::write(&config_path, config_json)
+    for log in ["access", "error"] {
+        let path = new_config["log"][log].as_str().map(std::path::Path::new);
+        if let Some(parent) = path.and_then(std::path::Path::parent) {
+            let _ = tokio::fs::create_dir_all(parent).await;
+        }
+    }
+
+    // Written next to the live config so the final rename stays on one filesystem. The
+    // `.json` extension lets Xray detect the format.
+    let tmp_path = std::path::Path::new(config_path).with_extension("tmp.json");
+    tokio::fs::write(&tmp_path, config_json)
         .await
         .map_err(|e| {
             crate::errors::ApiError::SystemError(format!("Failed to write config file: {}", e))
         })?;
 
-    tracing::info!("Xray config generated at: {}", config_path);
+    if let Err(e) = test_config(xray_bin, &tmp_path.to_string_lossy()).await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(e);
+    }
+
+    Ok(StagedConfig {
+        config: new_config,
+        config_path: config_path.to_string(),
+        tmp_path,
+        applied,
+    })
+}
+
+impl StagedConfig {
+    /// Swaps the staged file into place, records the revision and brings Xray in line.
+    async fn install(
+        mut self,
+        pool: &SqlitePool,
+        monitor: SharedMonitor,
+        trigger: ConfigTrigger,
+        force_restart: bool,
+    ) -> ApiResult<()> {
+        tokio::fs::rename(&self.tmp_path, &self.config_path)
+            .await
+            .map_err(|e| {
+                crate::errors::ApiError::SystemError(format!(
+                    "Failed to replace config file: {}",
+                    e
+                ))
+            })?;
+
+        tracing::info!("Xray config generated at: {}", self.config_path);
+
+        if let Err(e) = config_revision_service::record_revision(pool, &self.config, &trigger).await
+        {
+            tracing::warn!("Failed to record config revision: {}", e);
+        }
+
+        let plan = match self.applied.as_ref() {
+            Some(old) if !force_restart => plan_live_update(old, &self.config),
+            _ => None,
+        };
+        match plan {
+            Some(changes) if changes.is_empty() => {
+                tracing::info!("Xray config unchanged, nothing to apply");
+            }
+            Some(changes) => match apply_live(&changes).await {
+                Ok(()) => tracing::info!(
+                    "Applied {} change(s) to Xray without restart",
+                    changes.len()
+                ),
+                Err(e) => {
+                    tracing::warn!("Live Xray update failed, falling back to restart: {}", e);
+                    spawn_restart(monitor);
+                }
+            },
+            None => spawn_restart(monitor),
+        }
+
+        *self.applied = Some(self.config);
 
+        Ok(())
+    }
+
+    /// Removes the staged file when the change it was rendered from is not kept.
+    async fn discard(self) {
+        let _ = tokio::fs::remove_file(&self.tmp_path).await;
+    }
+}
+
+/// Runs Xray's config-test mode on `path`. A rejected config is reported with Xray's own
+/// error text; a binary that cannot be run fails the check, as nothing was validated.
+async fn test_config(xray_bin: &str, path: &str) -> ApiResult<()> {
+    let output = tokio::time::timeout(
+        std::time::Duration::from_secs(15),
+        tokio::process::Command::new(xray_bin)
+            .arg("run")
+            .arg("-test")
+            .arg("-c")
+            .arg(path)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    let output = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Err(ApiError::SystemError(format!(
+                "Failed to run {} for the config test: {}",
+                xray_bin, e
+            )))
+        }
+        Err(_) => {
+            return Err(ApiError::SystemError(
+                "Xray config test timed out".to_string(),
+            ))
+        }
+    };
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Drop the version banner Xray prints before any error.
+    let detail: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("Xray "))
+        .collect();
+
+    tracing::warn!("Xray rejected the generated config: {}", detail.join(" "));
+    Err(ApiError::BadRequest(format!(
+        "Xray rejected the config: {}",
+        detail.join("\n")
+    )))
+}
+
+fn spawn_restart(monitor: SharedMonitor) {
     tokio::spawn(async move {
         if let Err(e) = system_service::restart_xray(monitor).await {
             tracing::error!("Background Xray restart failed: {:?}", e);
@@ -166,20 +536,650 @@ pub async fn apply_config(pool: &SqlitePool, monitor: SharedMonitor) -> ApiResul
             tracing::info!("Background Xray restart successful");
         }
     });
+}
+
+/// A change that can be pushed to a running Xray through the HandlerService.
+#[derive(Debug, Clone, PartialEq)]
+enum LiveChange {
+    RemoveInbound(String),
+    RemoveUser {
+        tag: String,
+        email: String,
+    },
+    AddUser {
+        tag: String,
+        protocol: String,
+        user: Value,
+    },
+    AddInbound(Value),
+}
+
+/// Works out how to move a running Xray from `old` to `new` without a restart.
+///
+/// Returns `None` when anything outside `inbounds` changed, since only inbounds can be altered
+/// at runtime. Inbounds whose only difference is their client list are patched user by user;
+/// any other inbound change is a remove followed by an add.
+fn plan_live_update(old: &Value, new: &Value) -> Option<Vec<LiveChange>> {
+    let (old_obj, new_obj) = (old.as_object()?, new.as_object()?);
+    let sections_changed = old_obj
+        .keys()
+        .chain(new_obj.keys())
+        .filter(|key| *key != "inbounds")
+        .any(|key| old_obj.get(key) != new_obj.get(key));
+    if sections_changed {
+        return None;
+    }
+
+    let old_inbounds = inbounds_by_tag(old)?;
+    let new_inbounds = inbounds_by_tag(new)?;
+
+    let mut removes = Vec::new();
+    let mut user_changes = Vec::new();
+    let mut adds = Vec::new();
+
+    for tag in old_inbounds.keys() {
+        if !new_inbounds.contains_key(tag) {
+            removes.push(LiveChange::RemoveInbound(tag.to_string()));
+        }
+    }
+
+    for inbound in new.get("inbounds")?.as_array()? {
+        let tag = inbound.get("tag")?.as_str()?;
+        match old_inbounds.get(tag) {
+            None => adds.push(LiveChange::AddInbound(inbound.clone())),
+            Some(prev) if *prev == inbound => {}
+            Some(prev) => match diff_users(tag, prev, inbound) {
+                Some(changes) => user_changes.extend(changes),
+                None => {
+                    removes.push(LiveChange::RemoveInbound(tag.to_string()));
+                    adds.push(LiveChange::AddInbound(inbound.clone()));
+                }
+            },
+        }
+    }
+
+    removes.extend(user_changes);
+    removes.extend(adds);
+    Some(removes)
+}
+
+fn inbounds_by_tag(config: &Value) -> Option<HashMap<&str, &Value>> {
+    config
+        .get("inbounds")?
+        .as_array()?
+        .iter()
+        .map(|inbound| Some((inbound.get("tag")?.as_str()?, inbound)))
+        .collect()
+}
+
+/// User operations turning `old` into `new`, or `None` if the inbounds differ in more than
+/// their `settings.clients`. Users are keyed by email, which is how Xray removes them. SOCKS
+/// and HTTP accounts cannot be altered at runtime.
+fn diff_users(tag: &str, old: &Value, new: &Value) -> Option<Vec<LiveChange>> {
+    let protocol = new.get("protocol")?.as_str()?;
+    let live_users = matches!(protocol, "vless" | "vmess" | "trojan" | "shadowsocks");
+    if old.get("protocol")?.as_str()? != protocol || !live_users {
+        return None;
+    }
+
+    let (old_users, old_rest) = split_users(old)?;
+    let (new_users, new_rest) = split_users(new)?;
+    if old_rest != new_rest {
+        return None;
+    }
+
+    let mut changes = Vec::new();
+    for (email, user) in &old_users {
+        if new_users.get(email) != Some(user) {
+            changes.push(LiveChange::RemoveUser {
+                tag: tag.to_string(),
+                email: email.clone(),
+            });
+        }
+    }
+    for (email, user) in &new_users {
+        if old_users.get(email) != Some(user) {
+            changes.push(LiveChange::AddUser {
+                tag: tag.to_string(),
+                protocol: protocol.to_string(),
+                user: user.clone(),
+            });
+        }
+    }
+    Some(changes)
+}
+
+/// Splits an inbound into its clients keyed by email and the inbound without them.
+fn split_users(inbound: &Value) -> Option<(std::collections::BTreeMap<String, Value>, Value)> {
+    let mut rest = inbound.clone();
+    let clients = match rest
+        .get_mut("settings")
+        .and_then(|s| s.as_object_mut())
+        .and_then(|s| s.remove("clients"))
+    {
+        Some(Value::Array(clients)) => clients,
+        Some(_) => return None,
+        None => Vec::new(),
+    };
+
+    let mut users = std::collections::BTreeMap::new();
+    for client in clients {
+        let email = client.get("email")?.as_str()?.to_string();
+        if users.insert(email, client).is_some() {
+            return None;
+        }
+    }
+    Some((users, rest))
+}
+
+async fn apply_live(changes: &[LiveChange]) -> ApiResult<()> {
+    let mut client = XrayApiClient::connect_local().await?;
+
+    for change in changes {
+        match change {
+            LiveChange::RemoveInbound(tag) => client.remove_inbound(tag).await?,
+            LiveChange::RemoveUser { tag, email } => client.remove_user(tag, email).await?,
+            LiveChange::AddUser {
+                tag,
+                protocol,
+                user,
+            } => {
+                let str_field = |key: &str| user.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                let level = user.get("level").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                let email = str_field("email");
+                let user = match protocol.as_str() {
+                    "vmess" => xray_api::User::vmess(email, level, str_field("id")),
+                    "trojan" => xray_api::User::trojan(email, level, str_field("password")),
+                    "shadowsocks" if user.get("method").is_some() => xray_api::User::shadowsocks(
+                        email,
+                        level,
+                        str_field("password"),
+                        str_field("method"),
+                    ),
+                    "shadowsocks" => {
+                        xray_api::User::shadowsocks_2022(email, level, str_field("password"))
+                    }
+                    _ => xray_api::User::vless(email, level, str_field("id"), str_field("flow")),
+                };
+                client.add_user(tag, user).await?
+            }
+            LiveChange::AddInbound(inbound) => add_inbound(inbound).await?,
+        }
+        tracing::debug!("Applied live Xray change: {:?}", change);
+    }
 
     Ok(())
 }
 
+/// Adds an inbound through `xray api adi`, which converts the JSON inbound into the protobuf
+/// `InboundHandlerConfig` with Xray's own config loader before calling `AddInbound`.
+async fn add_inbound(inbound: &Value) -> ApiResult<()> {
+    let xray_bin = xray_process::bin_path();
+    let path = env::temp_dir().join(format!("x-ui-inbound-{}.json", uuid::Uuid::new_v4()));
+    let body = serde_json::json!({ "inbounds": [inbound] });
+
+    tokio::fs::write(&path, body.to_string())
+        .await
+        .map_err(|e| ApiError::SystemError(format!("Failed to write inbound file: {}", e)))?;
+
+    let output = tokio::process::Command::new(&xray_bin)
+        .arg("api")
+        .arg("adi")
+        .arg(format!("--server=127.0.0.1:{}", xray_api::API_PORT))
+        .arg(&path)
+        .output()
+        .await;
+    let _ = tokio::fs::remove_file(&path).await;
+
+    let output =
+        output.map_err(|e| ApiError::SystemError(format!("Failed to run xray api adi: {}", e)))?;
+    if !output.status.success() {
+        let detail = format!(
+            "{} {}",
+            String::from_utf8_lossy(&output.stdout).trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(ApiError::SystemError(format!(
+            "Xray rejected inbound: {}",
+            detail.trim()
+        )));
+    }
+    Ok(())
+}
+
+fn outbound_config(outbound: &Outbound) -> ApiResult<OutboundConfig> {
+    let owner = format!("Outbound {}", outbound.tag);
+    Ok(OutboundConfig {
+        tag: outbound.tag.clone(),
+        protocol: outbound.protocol.clone(),
+        send_through: outbound.send_through.clone(),
+        settings: json_column(&owner, "settings", &outbound.settings)?,
+        stream_settings: json_column(&owner, "streamSettings", &outbound.stream_settings)?,
+        proxy_settings: outbound.proxy_tag.clone().map(|tag| ProxySettings { tag }),
+        mux: json_column(&owner, "mux", &outbound.mux)?,
+    })
+}
+
+fn balancer_config(balancer: &Balancer) -> ApiResult<BalancerConfig> {
+    let owner = format!("Balancer {}", balancer.tag);
+    Ok(BalancerConfig {
+        tag: balancer.tag.clone(),
+        selector: json_column(&owner, "selector", &Some(balancer.selector.clone()))?
+            .unwrap_or_default(),
+        strategy: BalancerStrategy {
+            strategy_type: balancer.strategy.clone(),
+            settings: json_column(&owner, "strategy settings", &balancer.strategy_settings)?,
+        },
+        fallback_tag: balancer.fallback_tag.clone(),
+    })
+}
+
+fn routing_rule_config(rule: &RouteRule) -> ApiResult<RoutingRule> {
+    let owner = if rule.remark.is_empty() {
+        format!("Routing rule #{}", rule.id)
+    } else {
+        format!("Routing rule {}", rule.remark)
+    };
+    Ok(RoutingRule {
+        rule_type: "field".to_string(),
+        domain: json_column(&owner, "domain", &rule.domain)?,
+        ip: json_column(&owner, "ip", &rule.ip)?,
+        port: rule.port.clone(),
+        source_port: rule.source_port.clone(),
+        source: json_column(&owner, "source", &rule.source)?,
+        user: json_column(&owner, "user", &rule.user)?,
+        inbound_tag: json_column(&owner, "inboundTag", &rule.inbound_tag)?,
+        network: rule.network.clone(),
+        protocol: json_column(&owner, "protocol", &rule.protocol)?,
+        attrs: json_column(&owner, "attrs", &rule.attrs)?,
+        outbound_tag: rule.outbound_tag.clone(),
+        balancer_tag: rule.balancer_tag.clone(),
+    })
+}
+
+/// Parses one JSON column of the inbound, outbound, balancer or routing rule named by `owner`.
+/// Text that does not parse fails the build rather than being dropped, which would hand Xray
+/// an entry silently missing its settings.
+fn json_column<T: DeserializeOwned>(
+    owner: &str,
+    column: &str,
+    value: &Option<String>,
+) -> ApiResult<Option<T>> {
+    value
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            serde_json::from_str(s).map_err(|e| {
+                ApiError::BadRequest(format!("{} has invalid {}: {}", owner, column, e))
+            })
+        })
+        .transpose()
+}
+
 impl Default for RoutingRule {
     fn default() -> Self {
         Self {
             rule_type: "field".to_string(),
+            domain: None,
+            ip: None,
             port: None,
+            source_port: None,
+            source: None,
+            user: None,
             inbound_tag: None,
-            outbound_tag: None,
-            ip: None,
-            domain: None,
+            network: None,
             protocol: None,
+            attrs: None,
+            outbound_tag: None,
+            balancer_tag: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use serde_json::json;
+
+    fn config(inbounds: Vec<Value>) -> Value {
+        json!({
+            "log": { "loglevel": "error" },
+            "inbounds": inbounds,
+            "outbounds": [{ "tag": "direct", "protocol": "freedom" }],
+        })
+    }
+
+    fn vless(tag: &str, port: i32, clients: Vec<Value>) -> Value {
+        json!({
+            "tag": tag,
+            "port": port,
+            "protocol": "vless",
+            "settings": { "clients": clients, "decryption": "none" },
+        })
+    }
+
+    fn user(email: &str, id: &str) -> Value {
+        json!({ "id": id, "email": email, "level": 0 })
+    }
+
+    #[test]
+    fn test_plan_user_changes() {
+        let old = config(vec![vless(
+            "in-1",
+            443,
+            vec![user("alice", "a1"), user("bob", "b1")],
+        )]);
+        let new = config(vec![vless(
+            "in-1",
+            443,
+            vec![user("alice", "a2"), user("carol", "c1")],
+        )]);
+
+        let changes = plan_live_update(&old, &new).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                LiveChange::RemoveUser {
+                    tag: "in-1".into(),
+                    email: "alice".into()
+                },
+                LiveChange::RemoveUser {
+                    tag: "in-1".into(),
+                    email: "bob".into()
+                },
+                LiveChange::AddUser {
+                    tag: "in-1".into(),
+                    protocol: "vless".into(),
+                    user: user("alice", "a2")
+                },
+                LiveChange::AddUser {
+                    tag: "in-1".into(),
+                    protocol: "vless".into(),
+                    user: user("carol", "c1")
+                },
+            ]
+        );
+
+        assert_eq!(plan_live_update(&new, &new), Some(vec![]));
+    }
+
+    #[test]
+    fn test_plan_inbound_changes() {
+        let old = config(vec![
+            vless("in-1", 443, vec![]),
+            vless("in-2", 8443, vec![]),
+        ]);
+        let new = config(vec![
+            vless("in-1", 444, vec![]),
+            vless("in-3", 2053, vec![]),
+        ]);
+
+        let changes = plan_live_update(&old, &new).unwrap();
+        assert_eq!(changes.len(), 4);
+        assert!(changes[..2].contains(&LiveChange::RemoveInbound("in-1".into())));
+        assert!(changes[..2].contains(&LiveChange::RemoveInbound("in-2".into())));
+        assert_eq!(
+            changes[2],
+            LiveChange::AddInbound(vless("in-1", 444, vec![]))
+        );
+        assert_eq!(
+            changes[3],
+            LiveChange::AddInbound(vless("in-3", 2053, vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_test_reports_xray_error() {
+        let dir = env::temp_dir().join(format!("x-ui-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake_xray = dir.join("xray");
+        std::fs::write(
+            &fake_xray,
+            "#!/bin/sh\necho 'Xray 25.1.1 (Xray, Penetrates Everything.)'\ngrep -q bad \"$4\" || exit 0\necho 'Failed to start: infra/conf: unknown transport protocol: bad'\nexit 23\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&fake_xray, std::fs::Permissions::from_mode(0o755)).unwrap();
         }
+        let bin = fake_xray.to_str().unwrap();
+
+        let good = dir.join("good.json");
+        std::fs::write(&good, r#"{"inbounds":[]}"#).unwrap();
+        test_config(bin, good.to_str().unwrap()).await.unwrap();
+
+        let bad = dir.join("bad.json");
+        std::fs::write(&bad, r#"{"network":"bad"}"#).unwrap();
+        let err = test_config(bin, bad.to_str().unwrap()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid input: Xray rejected the config: Failed to start: infra/conf: unknown transport protocol: bad"
+        );
+
+        let err = test_config("/nonexistent/xray", good.to_str().unwrap())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::SystemError(_)), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_requires_restart() {
+        let old = config(vec![vless("in-1", 443, vec![])]);
+
+        let mut new = old.clone();
+        new["outbounds"] = json!([]);
+        assert_eq!(plan_live_update(&old, &new), None);
+
+        let mut new = old.clone();
+        new["routing"] = json!({ "rules": [] });
+        assert_eq!(plan_live_update(&old, &new), None);
+    }
+
+    async fn seed(pool: &SqlitePool) {
+        sqlx::query(
+            "INSERT INTO inbounds (id, remark, protocol, port, enable, settings, tag) \
+             VALUES ('ib1', 'main', 'vless', 443, 1, '{\"decryption\":\"none\"}', 'vless-443'), \
+                    ('ib2', 'off', 'vless', 8443, 0, '{}', NULL)",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO clients (inbound_id, email, uuid, flow, enable, sub_id) VALUES \
+             ('ib1', 'alice', 'b831381d-6324-4d53-ad4f-8cda48b30811', 'xtls-rprx-vision', 1, 'a'), \
+             ('ib1', 'bob', '2d0b2f59-4b8e-4d47-a3a1-3c4e3f2b8c11', '', 0, 'b')",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO outbounds (tag, protocol, settings, proxy_tag) VALUES \
+             ('warp', 'socks', '{\"servers\":[{\"address\":\"127.0.0.1\",\"port\":40000,\"users\":[{\"user\":\"u\",\"pass\":\"p\"}]}]}', NULL)",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO routing_rules (priority, domain, outbound_tag) \
+             VALUES (0, '[\"geosite:category-ads-all\"]', 'blocked')",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_build_config_from_db() {
+        let pool = test_pool().await;
+        seed(&pool).await;
+
+        let config = serde_json::to_value(
+            build_config(&mut pool.acquire().await.unwrap())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+
+        let tags: Vec<&str> = config["inbounds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| i["tag"].as_str().unwrap())
+            .collect();
+        assert_eq!(tags, vec!["api", "vless-443"]);
+        assert_eq!(
+            config["inbounds"][1]["settings"],
+            json!({
+                "decryption": "none",
+                "clients": [{
+                    "id": "b831381d-6324-4d53-ad4f-8cda48b30811",
+                    "email": "alice",
+                    "level": 0,
+                    "flow": "xtls-rprx-vision",
+                }],
+            })
+        );
+
+        let outbounds: Vec<&str> = config["outbounds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["tag"].as_str().unwrap())
+            .collect();
+        assert_eq!(outbounds, vec!["direct", "blocked", "warp"]);
+
+        assert_eq!(config["routing"]["domainStrategy"], "IPIfNonMatch");
+        assert_eq!(config["routing"]["rules"][0]["outboundTag"], "api");
+        assert_eq!(
+            config["routing"]["rules"][1],
+            json!({ "type": "field", "domain": ["geosite:category-ads-all"], "outboundTag": "blocked" })
+        );
+        assert_eq!(config["policy"]["levels"]["0"]["connIdle"], 300);
+        assert!(config.get("dns").is_none());
+        assert!(config.get("observatory").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_build_config_drops_stale_clients() {
+        let pool = test_pool().await;
+        sqlx::query(
+            "INSERT INTO inbounds (id, remark, protocol, port, enable, settings, tag) \
+             VALUES ('ib1', 'main', 'vless', 443, 1, \
+                     '{\"decryption\":\"none\",\"clients\":[{\"id\":\"x\",\"email\":\"gone\"}]}', 'vless-443')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let config = serde_json::to_value(
+            build_config(&mut pool.acquire().await.unwrap())
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config["inbounds"][1]["settings"]["clients"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_build_config_rejects_invalid_json() {
+        let cases = [
+            (
+                "INSERT INTO outbounds (tag, protocol, settings) VALUES ('proxy', 'vless', '{')",
+                "Outbound proxy has invalid settings",
+            ),
+            (
+                "INSERT INTO balancers (tag, selector) VALUES ('auto', 'proxy-')",
+                "Balancer auto has invalid selector",
+            ),
+            (
+                "INSERT INTO routing_rules (remark, domain, outbound_tag) VALUES ('ads', '[1]', 'direct')",
+                "Routing rule ads has invalid domain",
+            ),
+        ];
+        for (sql, message) in cases {
+            let pool = test_pool().await;
+            sqlx::query(sql).execute(&pool).await.unwrap();
+
+            let err = build_config(&mut pool.acquire().await.unwrap())
+                .await
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(message), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_config_redacts_secrets() {
+        let pool = test_pool().await;
+        seed(&pool).await;
+
+        let mut config = render_config(
+            &mut pool.acquire().await.unwrap(),
+            Some(&json!({ "metrics": { "tag": "metrics" } })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(config["metrics"]["tag"], "metrics");
+
+        redact_secrets(&mut config);
+        let client = &config["inbounds"][1]["settings"]["clients"][0];
+        assert_eq!(client["id"], "<redacted>");
+        assert_eq!(client["email"], "alice");
+        let server = &config["outbounds"][2]["settings"]["servers"][0];
+        assert_eq!(server["users"][0]["pass"], "<redacted>");
+        assert_eq!(server["address"], "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_config_change_rolls_back_rejected_config() {
+        let dir = env::temp_dir().join(format!("x-ui-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake_xray = dir.join("xray");
+        std::fs::write(
+            &fake_xray,
+            "#!/bin/sh\ngrep -q warp \"$4\" && exit 0\necho 'Failed to start: rejected'\nexit 23\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&fake_xray, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let (bin, config_path) = (fake_xray.to_str().unwrap(), dir.join("xray.json"));
+        let config_path = config_path.to_str().unwrap();
+
+        let pool = test_pool().await;
+        seed(&pool).await;
+        async fn count(pool: &SqlitePool, table: &str) -> i64 {
+            let sql = format!("SELECT COUNT(*) FROM {}", table);
+            let (count,): (i64,) = sqlx::query_as(&sql).fetch_one(pool).await.unwrap();
+            count
+        }
+
+        // The fake Xray rejects configs without the `warp` outbound.
+        let mut change = begin_change(&pool).await.unwrap();
+        sqlx::query("DELETE FROM outbounds WHERE tag = 'warp'")
+            .execute(&mut *change)
+            .await
+            .unwrap();
+        let err = change.stage(bin, config_path).await.err().unwrap();
+        assert!(err.to_string().contains("rejected"), "{}", err);
+        assert_eq!(count(&pool, "outbounds").await, 1);
+        assert!(!dir.join("xray.tmp.json").exists());
+
+        let mut change = begin_change(&pool).await.unwrap();
+        sqlx::query("DELETE FROM routing_rules")
+            .execute(&mut *change)
+            .await
+            .unwrap();
+        let staged = change.stage(bin, config_path).await.unwrap();
+        assert_eq!(count(&pool, "routing_rules").await, 0);
+        staged.discard().await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,23 @@
+use crate::errors::ApiResult;
+use sqlx::SqliteConnection;
+
+/// Reads a value from the `xray_settings` key/value table.
+pub async fn get_setting(conn: &mut SqliteConnection, key: &str) -> ApiResult<Option<String>> {
+    let row: Option<(String,)> = sqlx::query_as("SELECT value FROM xray_settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(|(value,)| value))
+}
+
+pub async fn set_setting(conn: &mut SqliteConnection, key: &str, value: &str) -> ApiResult<()> {
+    sqlx::query(
+        "INSERT INTO xray_settings (key, value) VALUES (?, ?) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+    )
+    .bind(key)
+    .bind(value)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
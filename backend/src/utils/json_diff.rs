@@ -0,0 +1,188 @@
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One difference between two JSON documents. `path` is a JSON Pointer (RFC 6901); array
+/// indexes refer to the new document, or to the old one for removed elements.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// Lists the leaf-level differences between `old` and `new`.
+///
+/// Arrays whose elements all carry a unique `tag` or `email` (inbounds, outbounds, clients)
+/// are matched by that key, so removing one entry does not show every later entry as changed.
+pub fn diff(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: String, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, old_value) in a {
+                let child = format!("{}/{}", path, escape(key));
+                match b.get(key) {
+                    Some(new_value) => diff_at(child, old_value, new_value, changes),
+                    None => changes.push(removed(child, old_value)),
+                }
+            }
+            for (key, new_value) in b {
+                if !a.contains_key(key) {
+                    changes.push(added(format!("{}/{}", path, escape(key)), new_value));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => match (array_keys(a), array_keys(b)) {
+            (Some(old_keys), Some(new_keys)) => {
+                for (i, key) in old_keys.iter().enumerate() {
+                    if !new_keys.contains(key) {
+                        changes.push(removed(format!("{}/{}", path, i), &a[i]));
+                    }
+                }
+                for (j, key) in new_keys.iter().enumerate() {
+                    let child = format!("{}/{}", path, j);
+                    match old_keys.iter().position(|k| k == key) {
+                        Some(i) => diff_at(child, &a[i], &b[j], changes),
+                        None => changes.push(added(child, &b[j])),
+                    }
+                }
+            }
+            _ => {
+                for i in 0..a.len().max(b.len()) {
+                    let child = format!("{}/{}", path, i);
+                    match (a.get(i), b.get(i)) {
+                        (Some(x), Some(y)) => diff_at(child, x, y, changes),
+                        (Some(x), None) => changes.push(removed(child, x)),
+                        (None, Some(y)) => changes.push(added(child, y)),
+                        (None, None) => {}
+                    }
+                }
+            }
+        },
+        _ if old != new => changes.push(JsonChange {
+            path,
+            kind: ChangeKind::Changed,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// The identity keys of an array of objects, if every element has a distinct `tag` or `email`.
+fn array_keys(items: &[Value]) -> Option<Vec<&str>> {
+    if items.is_empty() {
+        return Some(Vec::new());
+    }
+    let field = ["tag", "email"]
+        .into_iter()
+        .find(|f| items[0].get(f).and_then(|v| v.as_str()).is_some())?;
+    let keys: Vec<&str> = items
+        .iter()
+        .map(|item| item.get(field).and_then(|v| v.as_str()))
+        .collect::<Option<_>>()?;
+
+    let mut sorted = keys.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    (sorted.len() == keys.len()).then_some(keys)
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn added(path: String, value: &Value) -> JsonChange {
+    JsonChange {
+        path,
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(value.clone()),
+    }
+}
+
+fn removed(path: String, value: &Value) -> JsonChange {
+    JsonChange {
+        path,
+        kind: ChangeKind::Removed,
+        old: Some(value.clone()),
+        new: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_objects_and_arrays() {
+        let old = json!({
+            "log": { "loglevel": "error" },
+            "routing": { "rules": ["a", "b"] },
+            "stats": {},
+        });
+        let new = json!({
+            "log": { "loglevel": "warning" },
+            "routing": { "rules": ["a"] },
+            "dns": { "servers": ["1.1.1.1"] },
+        });
+
+        let changes = diff(&old, &new);
+        let summary: Vec<(&str, ChangeKind)> =
+            changes.iter().map(|c| (c.path.as_str(), c.kind)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/log/loglevel", ChangeKind::Changed),
+                ("/routing/rules/1", ChangeKind::Removed),
+                ("/stats", ChangeKind::Removed),
+                ("/dns", ChangeKind::Added),
+            ]
+        );
+        assert_eq!(changes[0].old, Some(json!("error")));
+        assert_eq!(changes[0].new, Some(json!("warning")));
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_matches_tagged_entries() {
+        let old = json!({ "inbounds": [
+            { "tag": "api", "port": 10085 },
+            { "tag": "in-1", "port": 443 },
+            { "tag": "in-2", "port": 8443 },
+        ]});
+        let new = json!({ "inbounds": [
+            { "tag": "api", "port": 10085 },
+            { "tag": "in-2", "port": 2053 },
+        ]});
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].path, "/inbounds/1");
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+        assert_eq!(changes[1].path, "/inbounds/1/port");
+        assert_eq!(changes[1].new, Some(json!(2053)));
+    }
+
+    #[test]
+    fn test_escape_pointer() {
+        let changes = diff(&json!({ "a/b~c": 1 }), &json!({ "a/b~c": 2 }));
+        assert_eq!(changes[0].path, "/a~1b~0c");
+    }
+}
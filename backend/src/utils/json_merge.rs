@@ -0,0 +1,125 @@
+use serde_json::Value;
+
+/// Arrays that collect entries from both documents instead of being replaced.
+const APPENDED_ARRAYS: &[&str] = &[
+    "/inbounds",
+    "/outbounds",
+    "/routing/rules",
+    "/routing/balancers",
+];
+
+/// Deep-merges the panel-generated config over an admin-supplied template.
+///
+/// Precedence, from the generated config's point of view:
+/// - objects merge key by key, recursively;
+/// - `inbounds`, `outbounds`, `routing.rules` and `routing.balancers` keep the generated
+///   entries first and append the template's, so panel rules match before template rules and
+///   the first generated outbound stays the default. A template entry whose `tag` is already
+///   used by a generated entry is an error;
+/// - any other value present in both documents, arrays included, is taken from the generated
+///   config. `null` in the generated config counts as absent.
+///
+/// The template therefore adds sections such as `fakedns`, `reverse`, `transport` or
+/// `metrics`, and fills fields the panel leaves unset, but cannot change what the panel
+/// manages.
+pub fn merge_template(template: Value, generated: Value) -> Result<Value, String> {
+    merge_at("", template, generated)
+}
+
+fn merge_at(path: &str, base: Value, overlay: Value) -> Result<Value, String> {
+    match (base, overlay) {
+        (Value::Object(mut base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    continue;
+                }
+                let child = format!("{}/{}", path, key);
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge_at(&child, existing, value)?,
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Ok(Value::Object(base))
+        }
+        (Value::Array(base), Value::Array(mut overlay)) if APPENDED_ARRAYS.contains(&path) => {
+            for entry in &base {
+                let Some(tag) = entry.get("tag").and_then(|t| t.as_str()) else {
+                    continue;
+                };
+                if overlay
+                    .iter()
+                    .any(|g| g.get("tag").and_then(|t| t.as_str()) == Some(tag))
+                {
+                    return Err(format!(
+                        "Template entry in {} uses tag {:?}, which the panel already manages",
+                        path, tag
+                    ));
+                }
+            }
+            overlay.extend(base);
+            Ok(Value::Array(overlay))
+        }
+        (_, overlay) => Ok(overlay),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_generated_values_win() {
+        let template = json!({
+            "log": { "loglevel": "debug", "dnsLog": true },
+            "fakedns": [{ "ipPool": "198.18.0.0/15", "poolSize": 65535 }],
+            "dns": { "servers": ["8.8.8.8"], "disableCache": true },
+        });
+        let generated = json!({
+            "log": { "loglevel": "warning" },
+            "dns": { "servers": ["1.1.1.1"] },
+            "stats": {},
+            "observatory": null,
+        });
+        let merged = merge_template(template, generated).unwrap();
+        assert_eq!(
+            merged,
+            json!({
+                "log": { "loglevel": "warning", "dnsLog": true },
+                "fakedns": [{ "ipPool": "198.18.0.0/15", "poolSize": 65535 }],
+                "dns": { "servers": ["1.1.1.1"], "disableCache": true },
+                "stats": {},
+            })
+        );
+    }
+
+    #[test]
+    fn test_tagged_lists_are_appended() {
+        let template = json!({
+            "outbounds": [{ "tag": "reverse-out", "protocol": "freedom" }],
+            "routing": { "rules": [{ "type": "field", "domain": ["full:private.example"], "outboundTag": "reverse-out" }] },
+        });
+        let generated = json!({
+            "outbounds": [{ "tag": "direct", "protocol": "freedom" }],
+            "routing": {
+                "domainStrategy": "AsIs",
+                "rules": [{ "type": "field", "inboundTag": ["api"], "outboundTag": "api" }],
+            },
+        });
+        let merged = merge_template(template, generated).unwrap();
+        assert_eq!(merged["outbounds"][0]["tag"], "direct");
+        assert_eq!(merged["outbounds"][1]["tag"], "reverse-out");
+        assert_eq!(merged["routing"]["rules"][0]["outboundTag"], "api");
+        assert_eq!(merged["routing"]["rules"][1]["outboundTag"], "reverse-out");
+        assert_eq!(merged["routing"]["domainStrategy"], "AsIs");
+    }
+
+    #[test]
+    fn test_tag_collision_is_rejected() {
+        let template = json!({ "inbounds": [{ "tag": "api", "port": 1 }] });
+        let generated = json!({ "inbounds": [{ "tag": "api", "port": 10085 }] });
+        let err = merge_template(template, generated).unwrap_err();
+        assert!(err.contains("\"api\""), "{}", err);
+    }
+}
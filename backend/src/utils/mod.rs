@@ -1,10 +1,14 @@
 // src/utils/mod.rs
 
 pub mod firewall;
+pub mod json_diff;
+pub mod json_merge;
 pub mod jwt;
 pub mod password;
+pub mod qr;
 pub mod reality;
 pub mod response;
+pub mod share_link;
 pub mod token_validator;
 pub mod validation;
 pub mod xray_config_builder;
@@ -0,0 +1,72 @@
+use crate::errors::ApiError;
+use base64::Engine;
+use qrcode::QrCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrFormat {
+    Png,
+    Svg,
+}
+
+impl QrFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, ApiError> {
+        match format.unwrap_or("png") {
+            "png" => Ok(QrFormat::Png),
+            "svg" => Ok(QrFormat::Svg),
+            other => Err(ApiError::BadRequest(format!(
+                "Unsupported QR code format: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Encodes `text` as a QR code and returns it as a `data:` URI usable in an `<img>` tag.
+pub fn render_data_uri(text: &str, format: QrFormat) -> Result<String, ApiError> {
+    let code = QrCode::new(text.as_bytes())
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode QR code: {}", e)))?;
+
+    let (mime, bytes) = match format {
+        QrFormat::Svg => {
+            let svg = code
+                .render::<qrcode::render::svg::Color>()
+                .min_dimensions(256, 256)
+                .build();
+            ("image/svg+xml", svg.into_bytes())
+        }
+        QrFormat::Png => {
+            let image = code
+                .render::<image::Luma<u8>>()
+                .min_dimensions(256, 256)
+                .build();
+            let mut png = Vec::new();
+            image::DynamicImage::ImageLuma8(image)
+                .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+                .map_err(|e| ApiError::InternalError(format!("Failed to encode PNG: {}", e)))?;
+            ("image/png", png)
+        }
+    };
+
+    Ok(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_data_uri() {
+        let png = render_data_uri("vless://test@example.com:443", QrFormat::Png).unwrap();
+        assert!(png.starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+        let svg = render_data_uri("vless://test@example.com:443", QrFormat::Svg).unwrap();
+        assert!(svg.starts_with("data:image/svg+xml;base64,"));
+
+        assert!(QrFormat::parse(Some("gif")).is_err());
+        assert_eq!(QrFormat::parse(None).unwrap(), QrFormat::Png);
+    }
+}
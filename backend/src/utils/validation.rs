@@ -37,6 +37,22 @@ pub fn validate_password(password: &str) -> Result<(), ApiError> {
     Ok(())
 }
 
+pub fn validate_client_email(email: &str) -> Result<(), ApiError> {
+    if email.is_empty() || email.len() > 64 {
+        return Err(ApiError::BadRequest(
+            "Client email must be between 1 and 64 characters".to_string(),
+        ));
+    }
+
+    if email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(ApiError::BadRequest(
+            "Client email cannot contain whitespace".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,4 +78,14 @@ mod tests {
         assert!(validate_password("123").is_err());
         assert!(validate_password(&"a".repeat(129)).is_err());
     }
+
+    #[test]
+    fn test_validate_client_email() {
+        assert!(validate_client_email("alice@example.com").is_ok());
+        assert!(validate_client_email("user-1").is_ok());
+
+        assert!(validate_client_email("").is_err());
+        assert!(validate_client_email("a b").is_err());
+        assert!(validate_client_email(&"a".repeat(65)).is_err());
+    }
 }
@@ -88,6 +88,12 @@ export const AddInboundModal = () => {
             };
         }
 
+        // The first client seeds a new inbound; afterwards clients are managed on their own,
+        // and a saved list would replace them.
+        if (editingNode) {
+            delete settings.clients;
+        }
+
         let streamSettings: any = {
             network: form.network,
             security: form.security,
@@ -18,7 +18,7 @@ interface SysStatus {
     load: string;
     xuiVersion: string;
     xrayVersion: string;
-    xrayStatus: 'running' | 'stop' | 'error';
+    xrayStatus: 'running' | 'stopped' | 'error';
     tcpCount: number;
     udpCount: number;
     netTraffic: Traffic;
@@ -45,7 +45,7 @@ export const useGlobalStore = create<GlobalState>((set, get) => ({
         load: '0 | 0 | 0',
         xuiVersion: 'v2.0.0',
         xrayVersion: 'unknown',
-        xrayStatus: 'stop',
+        xrayStatus: 'stopped',
         tcpCount: 0,
         udpCount: 0,
         netTraffic: { up: '0 B/s', down: '0 B/s', totalUp: '0 B', totalDown: '0 B' }
@@ -15,8 +15,13 @@ export interface SystemDisk {
 }
 
 export interface XrayStatus {
-    state: 'running' | 'stop' | 'error';
+    state: 'running' | 'stopped' | 'error';
     version: string;
+    pid: number | null;
+    exitCode: number | null;
+    uptime: number;
+    restartCount: number;
+    error?: string;
 }
 
 export interface NetworkTraffic {
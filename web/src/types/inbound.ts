@@ -117,6 +117,7 @@ export interface Inbound {
     down: number;
     total: number;
     expiry: number;
+    disabledReason?: 'expired' | 'quota_exceeded';
 
     createdAt?: string;
     updatedAt?: string;